@@ -0,0 +1,330 @@
+# German
+locale-name = Deutsch
+-brand-name = Axum Sass Template
+site-title = { -brand-name }
+
+## Navigation
+
+nav-notifications = Benachrichtigungen
+nav-admin = Verwaltung
+nav-preferences = Einstellungen
+nav-log-in = Anmelden
+nav-log-out = Abmelden
+nav-register = Registrieren
+nav-language = Sprache
+
+## Login and registration
+
+field-email = E-Mail
+field-password = Passwort
+login-title = Anmeldung
+login-legend = Benutzeranmeldung
+login-submit = Anmelden
+login-failed = Ungültige Anmeldedaten
+login-succeeded = Erfolgreich angemeldet als { $email }
+register-title = Registrieren
+register-legend = Konto registrieren
+register-submit = Registrieren
+register-succeeded = Dein Konto wurde registriert!
+email-undeliverable = Wir konnten keine E-Mail an deine Adresse zustellen. Bitte ändere deine E-Mail-Adresse.
+
+## Validation
+
+email-invalid = Gib eine gültige E-Mail-Adresse ein, zum Beispiel name@example.com
+email-taken = Für diese E-Mail-Adresse gibt es schon ein Konto. Möchtest du dich stattdessen anmelden?
+password-invalid = Das Passwort hat kein gültiges Format.
+password-too-short = Das Passwort muss mindestens { $min } Zeichen lang sein
+password-no-lowercase = Das Passwort muss mindestens einen Kleinbuchstaben enthalten
+password-no-uppercase = Das Passwort muss mindestens einen Großbuchstaben enthalten
+password-no-digit = Das Passwort muss mindestens eine Ziffer enthalten
+password-no-special-character = Das Passwort muss mindestens ein Sonderzeichen enthalten
+locale-invalid = Wähle eine der angebotenen Sprachen
+timezone-invalid = Wähle eine Zeitzone aus der Liste, zum Beispiel Europe/Berlin
+
+## Preferences
+
+preferences-title = Einstellungen
+preferences-legend = Sprache und Zeit
+preferences-locale = Sprache
+preferences-locale-browser = Wie mein Browser
+preferences-timezone = Zeitzone
+preferences-submit = Speichern
+preferences-saved = Deine Einstellungen wurden gespeichert
+
+## Error pages
+
+error-not-found-title = Seite nicht gefunden
+error-not-found-detail = Die gesuchte Seite gibt es nicht. Prüfe die Adresse oder beginne noch einmal auf der Startseite.
+error-server-title = Etwas ist schiefgelaufen
+error-server-detail = Bei uns ist etwas schiefgelaufen. Bitte versuche es später noch einmal.
+error-reference = Referenz:
+error-back-home = Zurück zur Startseite
+
+## Account
+
+notification-preferences-saved = Benachrichtigungseinstellungen gespeichert
+api-token-revoked = API-Token widerrufen
+api-token-not-found = API-Token nicht gefunden
+api-token-name-required = Gib dem Token einen Namen mit höchstens { $max } Zeichen
+api-token-invalid-scope = Wähle, worauf das Token zugreifen darf
+api-token-invalid-expiry = Wähle, wann das Token abläuft
+account-export-requested = Dein Datenexport wird vorbereitet. Wir schicken dir einen Link per E-Mail, sobald er fertig ist.
+account-export-in-progress = Dein Datenexport wird bereits vorbereitet
+account-export-ready = Dein Datenexport ist fertig
+account-export-not-found = Dieser Export ist abgelaufen. Bitte fordere einen neuen an.
+incorrect-password = Falsches Passwort
+account-deletion-scheduled = Die Löschung deines Kontos ist geplant
+account-deletion-scheduled-body = Dein Konto und seine Daten werden am { $date } gelöscht. Melde dich vorher an und brich die Löschung ab, um es zu behalten.
+account-deletion-cancelled = Dein Konto wird nicht gelöscht
+account-deletion-not-scheduled = Für dein Konto ist keine Löschung geplant
+welcome-email-subject = Willkommen bei { -brand-name }
+
+## Notifications
+
+notification-not-found = Benachrichtigung nicht gefunden
+notifications-marked-read = Alle Benachrichtigungen als gelesen markiert
+
+## Admin
+
+suppression-cleared = Sperre aufgehoben
+suppression-not-found = Sperre nicht gefunden
+audit-event-count = { $count ->
+    [one] { $count } Ereignis
+   *[other] { $count } Ereignisse
+}
+
+## Error responses, for pages and problem+json alike
+
+problem-not-found-title = Nicht gefunden
+problem-not-found-detail = Die gesuchte Seite gibt es nicht.
+problem-unauthorized-title = Nicht angemeldet
+problem-unauthorized-detail = Melde dich an, um fortzufahren.
+problem-forbidden-title = Keine Berechtigung
+problem-forbidden-detail = Dazu hast du keine Berechtigung.
+problem-validation-title = Ungültige Eingaben
+problem-validation-detail = Einige der gesendeten Werte sind ungültig.
+problem-conflict-title = Konflikt
+problem-rate-limited-title = Zu viele Anfragen
+problem-rate-limited-detail = Zu viele Anfragen. Bitte versuche es später noch einmal.
+problem-internal-title = Interner Serverfehler
+problem-internal-detail = Bei uns ist etwas schiefgelaufen. Bitte versuche es später noch einmal.
+
+## Relative times
+
+relative-just-now = gerade eben
+relative-minutes-ago = { $count ->
+    [one] vor { $count } Minute
+   *[other] vor { $count } Minuten
+}
+relative-in-minutes = { $count ->
+    [one] in { $count } Minute
+   *[other] in { $count } Minuten
+}
+relative-hours-ago = { $count ->
+    [one] vor { $count } Stunde
+   *[other] vor { $count } Stunden
+}
+relative-in-hours = { $count ->
+    [one] in { $count } Stunde
+   *[other] in { $count } Stunden
+}
+relative-days-ago = { $count ->
+    [one] vor { $count } Tag
+   *[other] vor { $count } Tagen
+}
+relative-in-days = { $count ->
+    [one] in { $count } Tag
+   *[other] in { $count } Tagen
+}
+relative-yesterday = gestern um { $time }
+relative-date = am { $day }. { $month ->
+    [1] Januar
+    [2] Februar
+    [3] März
+    [4] April
+    [5] Mai
+    [6] Juni
+    [7] Juli
+    [8] August
+    [9] September
+    [10] Oktober
+    [11] November
+   *[12] Dezember
+} { $year }
+
+## Shared by several pages
+
+common-when = Wann
+common-ip-address = IP-Adresse
+common-newer = Neuere
+common-older = Ältere
+common-never = Nie
+common-save = Speichern
+audit-action = { $action ->
+    [login] Anmeldung
+    [login_failed] Anmeldung fehlgeschlagen
+    [logout] Abmeldung
+    [registered] Registrierung
+    [password_changed] Passwort geändert
+    [email_changed] E-Mail-Adresse geändert
+    [role_granted] Rolle vergeben
+    [role_revoked] Rolle entzogen
+    [data_export_requested] Datenexport angefordert
+    [deletion_requested] Löschung angefordert
+    [deletion_cancelled] Löschung abgebrochen
+    [account_deleted] Konto gelöscht
+   *[other] { $action }
+}
+
+## Homepage
+
+homepage-title = Startseite
+homepage-welcome = Willkommen bei { -brand-name }!
+
+## Account pages
+
+account-delete-title = Konto löschen
+account-delete-scheduled-for = Dein Konto wird am { $date } gelöscht.
+account-delete-change-mind = Bis dahin kannst du dich weiterhin anmelden und es dir anders überlegen.
+account-delete-keep = Konto behalten
+account-delete-explanation = { $days ->
+    [one] Dein Konto und alles, was dazugehört, wird einen Tag nach deiner Bestätigung gelöscht.
+   *[other] Dein Konto und alles, was dazugehört, wird { $days } Tage nach deiner Bestätigung gelöscht.
+} Bis dahin kannst du dich anmelden und die Löschung abbrechen.
+account-delete-export-first = Vielleicht möchtest du vorher deine Daten exportieren.
+account-delete-confirm-password = Bestätige dein Passwort
+account-delete-submit = Mein Konto löschen
+account-export-title = Deine Daten exportieren
+account-export-explanation = Lade ein Archiv mit allem herunter, was wir über dich speichern: dein Profil, Rollen, Sitzungen, Benachrichtigungseinstellungen, API-Tokens, Benachrichtigungen und Sicherheitsaktivitäten. Wir schicken dir einen Link per E-Mail, sobald es fertig ist. { $hours ->
+    [one] Der Link ist eine Stunde lang gültig.
+   *[other] Der Link ist { $hours } Stunden lang gültig.
+}
+account-export-submit = Export anfordern
+account-export-requested-at = Angefordert
+account-export-status = Status
+account-export-expires = Läuft ab
+account-export-download = Herunterladen
+export-status = { $status ->
+    [pending] wartend
+    [processing] wird vorbereitet
+    [ready] fertig
+    [failed] fehlgeschlagen
+    [expired] abgelaufen
+   *[other] { $status }
+}
+notification-settings-title = Benachrichtigungseinstellungen
+notification-settings-undeliverable = Wir konnten keine E-Mail an deine Adresse zustellen. Bitte aktualisiere deine E-Mail-Adresse, um weiterhin Benachrichtigungen zu erhalten.
+notification-settings-legend = E-Mail-Benachrichtigungen
+notification-settings-product-updates = Produktneuigkeiten
+notification-settings-digests = Zusammenfassungen
+notification-settings-security-alerts = Sicherheitswarnungen (werden immer gesendet)
+security-title = Letzte Sicherheitsaktivitäten
+security-explanation = Anmeldungen und Änderungen an deinem Konto. Wenn dir etwas unbekannt vorkommt, ändere dein Passwort.
+security-no-activity = Noch keine Aktivitäten.
+security-activity = Aktivität
+security-by = Von
+security-by-you = Du
+security-by-unknown = Unbekannt
+security-device = Gerät
+api-tokens-title = API-Tokens
+api-tokens-explanation = Mit Tokens können Skripte die API in deinem Namen nutzen. Sende sie in diesem Header:
+api-tokens-copy-now = Kopiere dein neues Token jetzt. Es wird nicht noch einmal angezeigt.
+api-tokens-copy = Kopieren
+api-tokens-new = Neues Token
+api-tokens-name = Name
+api-tokens-access = Zugriff
+api-tokens-read-only = Nur lesen
+api-tokens-read-write = Lesen und schreiben
+api-tokens-expires = Läuft ab
+api-tokens-expires-in = { $days ->
+    [one] In einem Tag
+   *[other] In { $days } Tagen
+}
+api-tokens-create = Token erstellen
+api-tokens-token = Token
+api-tokens-created = Erstellt
+api-tokens-last-used = Zuletzt benutzt
+api-tokens-revoked = Widerrufen
+api-tokens-expired = Abgelaufen
+api-tokens-revoke = Widerrufen
+api-tokens-none = Du hast keine API-Tokens.
+token-scope = { $scope ->
+    [read] lesen
+    [write] schreiben
+   *[other] { $scope }
+}
+
+## Notifications page
+
+notifications-mark-all-read = Alle als gelesen markieren
+notifications-open = Öffnen
+notifications-mark-read = Als gelesen markieren
+notifications-none = Du hast keine Benachrichtigungen.
+
+## Admin pages
+
+audit-title = Audit-Log
+audit-filter-action = Aktion
+audit-filter-any = Alle
+audit-filter-user = Benutzer
+audit-filter-user-placeholder = E-Mail-Adresse oder Benutzer-ID
+audit-filter-from = Von
+audit-filter-to = Bis
+audit-filter-submit = Filtern
+audit-export = Exportieren:
+audit-none = Keine passenden Ereignisse.
+audit-actor = Ausgeführt von
+audit-target = Betroffen
+audit-user-agent = User-Agent
+audit-details = Details
+suppressions-title = E-Mail-Sperren
+suppressions-none = Keine gesperrten Adressen.
+suppressions-reason = Grund
+suppressions-detail = Details
+suppressions-created-at = Gesperrt am
+suppressions-clear = Aufheben
+suppression-reason = { $reason ->
+    [hard_bounce] dauerhafter Zustellfehler
+    [soft_bounce] vorübergehender Zustellfehler
+    [complaint] Beschwerde
+   *[other] { $reason }
+}
+
+## Unsubscribing
+
+unsubscribe-title = Abmelden
+unsubscribe-confirm = Keine E-Mails mit { $category } mehr erhalten?
+unsubscribe-done = Du erhältst keine E-Mails mit { $category } mehr.
+unsubscribe-submit = Abmelden
+unsubscribe-link-invalid = Der Abmeldelink ist ungültig.
+email-category = { $category ->
+    [product_updates] Produktneuigkeiten
+    [digests] Zusammenfassungen
+    [security_alerts] Sicherheitswarnungen
+   *[other] Kontoinformationen
+}
+
+## API explorer
+
+api-explorer-title = API-Explorer
+api-explorer-intro = Endpunkte der JSON-API unter { $base }, gelesen aus ihrem OpenAPI-Dokument. Anfragen von dieser Seite verwenden deine Browsersitzung.
+api-explorer-openapi-document = OpenAPI-Dokument
+api-explorer-scripts = Skripte sollten stattdessen einen Authorization: Bearer-Header mit einem API-Token senden.
+api-explorer-manage-tokens = API-Tokens verwalten
+api-explorer-loading = Wird geladen…
+api-explorer-load-failed = Das OpenAPI-Dokument konnte nicht geladen werden.
+api-explorer-body = Inhalt
+api-explorer-send = Senden
+
+## Emails
+
+welcome-email-greeting = Hallo, dein Konto für { $email } wurde erstellt.
+welcome-email-log-in = Du kannst dich unter { $url } anmelden.
+export-email-body = Das Archiv mit deinen Kontodaten steht zum Herunterladen bereit.
+export-email-link = Deine Daten herunterladen
+export-email-expiry = { $hours ->
+    [one] Der Link ist eine Stunde lang gültig, und nur solange du angemeldet bist.
+   *[other] Der Link ist { $hours } Stunden lang gültig, und nur solange du angemeldet bist.
+}
+notification-email-link = In der App ansehen
+unsubscribe-email-reason = Du erhältst diese E-Mail wegen deiner Benachrichtigungseinstellungen.
@@ -0,0 +1,331 @@
+# English, the default locale. Messages missing from other catalogs are shown
+# in English. Validation messages are named after the validator's error code.
+locale-name = English
+-brand-name = Axum Sass Template
+site-title = { -brand-name }
+
+## Navigation
+
+nav-notifications = Notifications
+nav-admin = Admin
+nav-preferences = Preferences
+nav-log-in = Log in
+nav-log-out = Log out
+nav-register = Register
+nav-language = Language
+
+## Login and registration
+
+field-email = Email
+field-password = Password
+login-title = Login
+login-legend = User login
+login-submit = login
+login-failed = Invalid Credentials
+login-succeeded = Successfully logged in as { $email }
+register-title = Register
+register-legend = Account Registration
+register-submit = Register
+register-succeeded = Successfully registered account!
+email-undeliverable = We could not deliver email to your address. Please update your email.
+
+## Validation
+
+email-invalid = Enter a valid email address, like name@example.com
+email-taken = An account with this email already exists. Log in instead?
+password-invalid = Password is not a valid format.
+password-too-short = Password must be at least { $min } characters long
+password-no-lowercase = Password must contain at least one lowercase letter
+password-no-uppercase = Password must contain at least one uppercase letter
+password-no-digit = Password must contain at least one digit
+password-no-special-character = Password must contain at least one special character
+locale-invalid = Choose one of the languages offered
+timezone-invalid = Choose a timezone from the list, like Europe/Berlin
+
+## Preferences
+
+preferences-title = Preferences
+preferences-legend = Language and time
+preferences-locale = Language
+preferences-locale-browser = Same as my browser
+preferences-timezone = Timezone
+preferences-submit = Save
+preferences-saved = Your preferences have been saved
+
+## Error pages
+
+error-not-found-title = Page not found
+error-not-found-detail = The page you are looking for does not exist. Check the address, or start again from the homepage.
+error-server-title = Something went wrong
+error-server-detail = Something went wrong on our end. Please try again later.
+error-reference = Reference:
+error-back-home = Back to the homepage
+
+## Account
+
+notification-preferences-saved = Notification preferences saved
+api-token-revoked = API token revoked
+api-token-not-found = API token not found
+api-token-name-required = Give the token a name of at most { $max } characters
+api-token-invalid-scope = Choose what the token can access
+api-token-invalid-expiry = Choose when the token expires
+account-export-requested = Your data export is being prepared. We will email you a link when it is ready.
+account-export-in-progress = Your data export is already being prepared
+account-export-ready = Your data export is ready
+account-export-not-found = This export has expired. Please request a new one.
+incorrect-password = Incorrect password
+account-deletion-scheduled = Your account is scheduled for deletion
+account-deletion-scheduled-body = Your account and its data will be deleted on { $date }. Log in and cancel before then to keep it.
+account-deletion-cancelled = Your account will not be deleted
+account-deletion-not-scheduled = Your account is not scheduled for deletion
+welcome-email-subject = Welcome to { -brand-name }
+
+## Notifications
+
+notification-not-found = Notification not found
+notifications-marked-read = All notifications marked as read
+
+## Admin
+
+suppression-cleared = Suppression cleared
+suppression-not-found = Suppression not found
+audit-event-count = { $count ->
+    [one] { $count } event
+   *[other] { $count } events
+}
+
+## Error responses, for pages and problem+json alike
+
+problem-not-found-title = Not Found
+problem-not-found-detail = The page you are looking for does not exist.
+problem-unauthorized-title = Unauthorized
+problem-unauthorized-detail = You need to log in to continue.
+problem-forbidden-title = Forbidden
+problem-forbidden-detail = You do not have permission to do that.
+problem-validation-title = Validation Failed
+problem-validation-detail = Some of the submitted values are invalid.
+problem-conflict-title = Conflict
+problem-rate-limited-title = Too Many Requests
+problem-rate-limited-detail = Too many requests. Please try again later.
+problem-internal-title = Internal Server Error
+problem-internal-detail = Something went wrong on our end. Please try again later.
+
+## Relative times
+
+relative-just-now = just now
+relative-minutes-ago = { $count ->
+    [one] { $count } minute ago
+   *[other] { $count } minutes ago
+}
+relative-in-minutes = { $count ->
+    [one] in { $count } minute
+   *[other] in { $count } minutes
+}
+relative-hours-ago = { $count ->
+    [one] { $count } hour ago
+   *[other] { $count } hours ago
+}
+relative-in-hours = { $count ->
+    [one] in { $count } hour
+   *[other] in { $count } hours
+}
+relative-days-ago = { $count ->
+    [one] { $count } day ago
+   *[other] { $count } days ago
+}
+relative-in-days = { $count ->
+    [one] in { $count } day
+   *[other] in { $count } days
+}
+relative-yesterday = yesterday at { $time }
+relative-date = on { $day } { $month ->
+    [1] January
+    [2] February
+    [3] March
+    [4] April
+    [5] May
+    [6] June
+    [7] July
+    [8] August
+    [9] September
+    [10] October
+    [11] November
+   *[12] December
+} { $year }
+
+## Shared by several pages
+
+common-when = When
+common-ip-address = IP address
+common-newer = Newer
+common-older = Older
+common-never = Never
+common-save = Save
+audit-action = { $action ->
+    [login] login
+    [login_failed] login failed
+    [logout] logout
+    [registered] registered
+    [password_changed] password changed
+    [email_changed] email changed
+    [role_granted] role granted
+    [role_revoked] role revoked
+    [data_export_requested] data export requested
+    [deletion_requested] deletion requested
+    [deletion_cancelled] deletion cancelled
+    [account_deleted] account deleted
+   *[other] { $action }
+}
+
+## Homepage
+
+homepage-title = Home
+homepage-welcome = Welcome to { -brand-name }!
+
+## Account pages
+
+account-delete-title = Delete your account
+account-delete-scheduled-for = Your account will be deleted on { $date }.
+account-delete-change-mind = Until then you can still log in and change your mind.
+account-delete-keep = Keep my account
+account-delete-explanation = { $days ->
+    [one] Your account and everything tied to it will be deleted one day after you confirm.
+   *[other] Your account and everything tied to it will be deleted { $days } days after you confirm.
+} Until then you can log in and cancel.
+account-delete-export-first = You may want to export your data first.
+account-delete-confirm-password = Confirm your password
+account-delete-submit = Delete my account
+account-export-title = Export your data
+account-export-explanation = Download an archive of everything we store about you: your profile, roles, sessions, notification settings, API tokens, notifications and security activity. We will email you a link when it is ready. { $hours ->
+    [one] The link works for one hour.
+   *[other] The link works for { $hours } hours.
+}
+account-export-submit = Request export
+account-export-requested-at = Requested
+account-export-status = Status
+account-export-expires = Expires
+account-export-download = Download
+export-status = { $status ->
+    [pending] pending
+    [processing] being prepared
+    [ready] ready
+    [failed] failed
+    [expired] expired
+   *[other] { $status }
+}
+notification-settings-title = Notification settings
+notification-settings-undeliverable = We could not deliver email to your address. Please update your email to keep receiving notifications.
+notification-settings-legend = Email notifications
+notification-settings-product-updates = Product updates
+notification-settings-digests = Digests
+notification-settings-security-alerts = Security alerts (always sent)
+security-title = Recent security activity
+security-explanation = Sign-ins and changes to your account. If you don't recognise something, change your password.
+security-no-activity = No activity yet.
+security-activity = Activity
+security-by = By
+security-by-you = You
+security-by-unknown = Unknown
+security-device = Device
+api-tokens-title = API tokens
+api-tokens-explanation = Tokens let scripts use the API as you. Send them in this header:
+api-tokens-copy-now = Copy your new token now. It will not be shown again.
+api-tokens-copy = Copy
+api-tokens-new = New token
+api-tokens-name = Name
+api-tokens-access = Access
+api-tokens-read-only = Read only
+api-tokens-read-write = Read and write
+api-tokens-expires = Expires
+api-tokens-expires-in = { $days ->
+    [one] In one day
+   *[other] In { $days } days
+}
+api-tokens-create = Create token
+api-tokens-token = Token
+api-tokens-created = Created
+api-tokens-last-used = Last used
+api-tokens-revoked = Revoked
+api-tokens-expired = Expired
+api-tokens-revoke = Revoke
+api-tokens-none = You have no API tokens.
+token-scope = { $scope ->
+    [read] read
+    [write] write
+   *[other] { $scope }
+}
+
+## Notifications page
+
+notifications-mark-all-read = Mark all as read
+notifications-open = Open
+notifications-mark-read = Mark as read
+notifications-none = You have no notifications.
+
+## Admin pages
+
+audit-title = Audit log
+audit-filter-action = Action
+audit-filter-any = Any
+audit-filter-user = User
+audit-filter-user-placeholder = Email or user id
+audit-filter-from = From
+audit-filter-to = To
+audit-filter-submit = Filter
+audit-export = Export:
+audit-none = No matching events.
+audit-actor = Actor
+audit-target = Target
+audit-user-agent = User agent
+audit-details = Details
+suppressions-title = Email suppressions
+suppressions-none = No suppressed addresses.
+suppressions-reason = Reason
+suppressions-detail = Detail
+suppressions-created-at = Suppressed at
+suppressions-clear = Clear
+suppression-reason = { $reason ->
+    [hard_bounce] hard bounce
+    [soft_bounce] soft bounce
+    [complaint] complaint
+   *[other] { $reason }
+}
+
+## Unsubscribing
+
+unsubscribe-title = Unsubscribe
+unsubscribe-confirm = Stop receiving { $category } emails?
+unsubscribe-done = You have been unsubscribed from { $category } emails.
+unsubscribe-submit = Unsubscribe
+unsubscribe-link-invalid = The unsubscribe link is invalid.
+email-category = { $category ->
+    [product_updates] product updates
+    [digests] digests
+    [security_alerts] security alerts
+   *[other] account
+}
+
+## API explorer
+
+api-explorer-title = API explorer
+api-explorer-intro = Endpoints of the JSON API under { $base }, read from its OpenAPI document. Requests sent from this page use your browser session.
+api-explorer-openapi-document = OpenAPI document
+api-explorer-scripts = Scripts should send an Authorization: Bearer header with an API token instead.
+api-explorer-manage-tokens = Manage API tokens
+api-explorer-loading = Loading…
+api-explorer-load-failed = The OpenAPI document could not be loaded.
+api-explorer-body = Body
+api-explorer-send = Send
+
+## Emails
+
+welcome-email-greeting = Hello, your account for { $email } has been created.
+welcome-email-log-in = You can log in at { $url }
+export-email-body = The archive of your account data is ready to download.
+export-email-link = Download your data
+export-email-expiry = { $hours ->
+    [one] The link works for one hour, and only while you are logged in.
+   *[other] The link works for { $hours } hours, and only while you are logged in.
+}
+notification-email-link = View it in the app
+unsubscribe-email-reason = You are receiving this email because of your notification settings.
@@ -0,0 +1,13 @@
+-- Security alerts and transactional emails are not optional so they are not stored here.
+CREATE TABLE notification_preferences (
+    user_id uuid PRIMARY KEY NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    product_updates BOOLEAN NOT NULL DEFAULT TRUE,
+    digests BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_notification_preferences_updated_at
+BEFORE UPDATE ON notification_preferences
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
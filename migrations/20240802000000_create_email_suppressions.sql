@@ -0,0 +1,17 @@
+-- Addresses that hard bounced or complained. Emails are stored lowercased.
+CREATE TABLE email_suppressions (
+    id SERIAL PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE,
+    reason VARCHAR(50) NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_email_suppressions_updated_at
+BEFORE UPDATE ON email_suppressions
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Set when the user's address lands on the suppression list so they are prompted to fix it.
+ALTER TABLE users ADD COLUMN email_undeliverable BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,22 @@
+-- Personal API tokens. Only a SHA-256 hash of the token is stored; the prefix
+-- is the public part of the token used to look it up.
+CREATE TABLE api_tokens (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL UNIQUE,
+    token_hash TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
+
+CREATE TRIGGER update_api_tokens_updated_at
+BEFORE UPDATE ON api_tokens
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
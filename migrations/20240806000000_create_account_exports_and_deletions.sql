@@ -0,0 +1,33 @@
+-- Archives of everything stored about a user, generated in the background.
+-- The archive is kept until the download link expires.
+CREATE TABLE account_exports (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'processing', 'ready', 'failed')),
+    archive BYTEA,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX account_exports_user_id_idx ON account_exports (user_id, created_at DESC);
+CREATE INDEX account_exports_pending_idx ON account_exports (created_at) WHERE status = 'pending';
+-- One export in progress per user at a time
+CREATE UNIQUE INDEX account_exports_in_progress_idx ON account_exports (user_id)
+    WHERE status IN ('pending', 'processing');
+
+CREATE TRIGGER update_account_exports_updated_at
+BEFORE UPDATE ON account_exports
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Accounts whose owner asked for them to be deleted. The account is deleted
+-- for good at `scheduled_for`, unless the request is cancelled before then.
+CREATE TABLE account_deletions (
+    user_id uuid PRIMARY KEY NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX account_deletions_scheduled_for_idx ON account_deletions (scheduled_for);
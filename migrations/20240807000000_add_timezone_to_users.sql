@@ -0,0 +1,2 @@
+-- The IANA timezone dates and times are shown in, e.g. "Europe/Berlin"
+ALTER TABLE users ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
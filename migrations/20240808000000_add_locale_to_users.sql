@@ -0,0 +1,2 @@
+-- The language pages are shown in, e.g. "de". NULL follows the browser.
+ALTER TABLE users ADD COLUMN locale TEXT;
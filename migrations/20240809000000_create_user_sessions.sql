@@ -0,0 +1,9 @@
+-- The user each logged in session belongs to, so their sessions can be found
+-- without reading the session data. Rows outlive their session until pruned.
+CREATE TABLE user_sessions (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
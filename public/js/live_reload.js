@@ -0,0 +1,43 @@
+// Development only: reloads the page when templates or other assets change and
+// swaps in the recompiled stylesheets when SCSS changes. Included by base.html when live
+// reload is enabled.
+(function () {
+    if (!window.EventSource) {
+        return;
+    }
+
+    var source = new EventSource("/dev/reload");
+    var disconnected = false;
+
+    source.addEventListener("templates", function () {
+        window.location.reload();
+    });
+
+    // Pages link the old fingerprints until they are rendered again
+    source.addEventListener("assets", function () {
+        window.location.reload();
+    });
+
+    source.addEventListener("styles", function () {
+        // Pages standing in for a compile error are replaced by the real page
+        if (document.documentElement.dataset.liveReload === "page") {
+            window.location.reload();
+            return;
+        }
+        document.querySelectorAll('link[rel="stylesheet"]').forEach(function (link) {
+            var url = new URL(link.href);
+            url.searchParams.set("reload", Date.now());
+            link.href = url.toString();
+        });
+    });
+
+    // The server restarted, e.g. after a Rust change, so the page may be stale
+    source.addEventListener("error", function () {
+        disconnected = true;
+    });
+    source.addEventListener("open", function () {
+        if (disconnected) {
+            window.location.reload();
+        }
+    });
+})();
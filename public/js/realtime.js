@@ -0,0 +1,49 @@
+// Listens for realtime events from /events and shows toasts and notifications.
+// Every event is also dispatched on `document` as `realtime:<type>` so pages
+// can react to them, e.g. to show job progress.
+(function () {
+    if (!window.EventSource) {
+        return;
+    }
+
+    var toasts = document.getElementById("toasts");
+    var badge = document.getElementById("notification-badge");
+
+    function showToast(level, message) {
+        if (!toasts) {
+            return;
+        }
+        var toast = document.createElement("div");
+        toast.className = "toast toast-" + level;
+        toast.textContent = message;
+        toasts.appendChild(toast);
+        setTimeout(function () { toast.remove(); }, 5000);
+    }
+
+    function dispatch(event) {
+        var data = JSON.parse(event.data);
+        document.dispatchEvent(new CustomEvent("realtime:" + data.type, { detail: data }));
+        return data;
+    }
+
+    var source = new EventSource("/events");
+    source.addEventListener("toast", function (event) {
+        var data = dispatch(event);
+        showToast(data.level, data.message);
+    });
+    source.addEventListener("notification", function (event) {
+        var data = dispatch(event);
+        showToast("info", data.title);
+        if (badge) {
+            badge.textContent = data.unread_count;
+            badge.hidden = data.unread_count === 0;
+        }
+    });
+    source.addEventListener("job_progress", dispatch);
+    // Anonymous visitors get a 401, after which the browser gives up
+    source.addEventListener("error", function () {
+        if (source.readyState === EventSource.CLOSED) {
+            source.close();
+        }
+    });
+})();
@@ -0,0 +1,131 @@
+//! src/account_deletion.rs
+//! Account self-deletion. Asking for deletion schedules it after a grace
+//! period, during which the user can still log in and cancel. Once the grace
+//! period is over the user row is deleted, which cascades to everything that
+//! references it. Audit events are kept, as the security record of the
+//! account.
+use sqlx::PgPool;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use crate::audit::{self, AuditContext, NewAuditEvent};
+use crate::domain::AuditAction;
+use crate::session;
+
+/// How often accounts due for deletion are deleted
+const DELETE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Schedules the deletion of the user's account in `grace_period_days`.
+/// Returns when it will be deleted, which is unchanged if the deletion was
+/// already scheduled.
+pub async fn schedule(
+    db: &PgPool,
+    user_id: Uuid,
+    grace_period_days: i32,
+    context: &AuditContext,
+) -> Result<OffsetDateTime, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let scheduled: Option<(OffsetDateTime,)> = sqlx::query_as(
+        "INSERT INTO account_deletions (user_id, scheduled_for) VALUES ($1, NOW() + make_interval(days => $2))
+        ON CONFLICT (user_id) DO NOTHING
+        RETURNING scheduled_for"
+    )
+        .bind(user_id)
+        .bind(grace_period_days)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let scheduled_for = match scheduled {
+        Some((scheduled_for,)) => {
+            let event = NewAuditEvent::new(AuditAction::DeletionRequested)
+                .actor(user_id)
+                .target(user_id)
+                .metadata(serde_json::json!({ "grace_period_days": grace_period_days }));
+            audit::record(&mut *transaction, &event, context).await?;
+            scheduled_for
+        },
+        None => {
+            let (scheduled_for,): (OffsetDateTime,) = sqlx::query_as(
+                "SELECT scheduled_for FROM account_deletions WHERE user_id = $1"
+            )
+                .bind(user_id)
+                .fetch_one(&mut *transaction)
+                .await?;
+            scheduled_for
+        },
+    };
+    transaction.commit().await?;
+
+    Ok(scheduled_for)
+}
+
+/// When the user's account will be deleted, if they asked for it
+pub async fn scheduled_for(db: &PgPool, user_id: Uuid) -> Result<Option<OffsetDateTime>, sqlx::Error> {
+    let scheduled: Option<(OffsetDateTime,)> = sqlx::query_as(
+        "SELECT scheduled_for FROM account_deletions WHERE user_id = $1"
+    )
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(scheduled.map(|(scheduled_for,)| scheduled_for))
+}
+
+/// Returns false if no deletion was scheduled
+pub async fn cancel(db: &PgPool, user_id: Uuid, context: &AuditContext) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let result = sqlx::query("DELETE FROM account_deletions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    let cancelled = result.rows_affected() > 0;
+    if cancelled {
+        let event = NewAuditEvent::new(AuditAction::DeletionCancelled).actor(user_id).target(user_id);
+        audit::record(&mut *transaction, &event, context).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(cancelled)
+}
+
+/// Deletes every account whose grace period is over, logging its sessions
+/// out. Returns how many accounts were deleted.
+pub async fn delete_due(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut deleted = 0;
+    loop {
+        let mut transaction = db.begin().await?;
+        let due: Option<(Uuid,)> = sqlx::query_as(
+            "SELECT user_id FROM account_deletions WHERE scheduled_for <= NOW()
+            ORDER BY scheduled_for
+            FOR UPDATE SKIP LOCKED
+            LIMIT 1"
+        )
+            .fetch_optional(&mut *transaction)
+            .await?;
+        let Some((user_id,)) = due else {
+            return Ok(deleted);
+        };
+
+        session::delete_for_user(&mut transaction, user_id).await?;
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+        let event = NewAuditEvent::new(AuditAction::AccountDeleted).target(user_id);
+        audit::record(&mut *transaction, &event, &AuditContext::default()).await?;
+        transaction.commit().await?;
+        deleted += 1;
+    }
+}
+
+/// Deletes accounts due for deletion every `DELETE_INTERVAL`, until aborted
+pub async fn continuously_delete_due(db: PgPool) {
+    let mut interval = tokio::time::interval(DELETE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match delete_due(&db).await {
+            Ok(0) => {},
+            Ok(deleted) => tracing::info!(deleted, "Deleted accounts past their grace period"),
+            Err(err) => tracing::error!(error = %err, "Failed to delete accounts past their grace period"),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! src/account_exports.rs
+//! Exports of everything stored about a user. Asking for an export queues
+//! it; the archive is built in the background, kept in the database and the
+//! user is emailed a link to download it until it expires.
+//!
+//! ```ignore
+//! if account_exports::request(&state.db, user.id()).await?.is_some() {
+//!     tokio::spawn(account_exports::process_pending(state.clone()));
+//! }
+//! ```
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use crate::api_tokens::{self, ApiToken};
+use crate::audit::{self, AuditEvent};
+use crate::constants::{email_templates, route_paths};
+use crate::domain::{EmailCategory, NotificationKind};
+use crate::emailer::{self, UserEmailParams};
+use crate::notification_preferences::NotificationPreferences;
+use crate::notifications::{self, NewNotification, Notification};
+use crate::session;
+use crate::startup::AppState;
+
+/// How often pending exports are picked up and expired ones deleted
+const PROCESS_INTERVAL: Duration = Duration::from_secs(60);
+/// Exports stuck in `processing` this long were interrupted and are retried
+const STALE_AFTER: &str = "15 minutes";
+
+const SELECT_EXPORTS: &str = "SELECT id, user_id, status, expires_at, created_at,
+    (status = 'ready' AND expires_at > NOW()) AS downloadable
+    FROM account_exports";
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AccountExport {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// `pending`, `processing`, `ready` or `failed`
+    pub status: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Ready and not expired yet
+    pub downloadable: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Profile {
+    pub id: Uuid,
+    pub email: String,
+    pub email_undeliverable: bool,
+    pub timezone: String,
+    pub locale: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Session {
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+/// Everything stored about a user. Each field is a file in the archive.
+#[derive(Debug, Serialize)]
+pub struct AccountData {
+    pub profile: Profile,
+    pub roles: Vec<String>,
+    pub sessions: Vec<Session>,
+    pub notification_preferences: NotificationPreferences,
+    pub api_tokens: Vec<ApiToken>,
+    pub notifications: Vec<Notification>,
+    pub audit_events: Vec<AuditEvent>,
+}
+
+impl AccountData {
+    pub async fn collect(db: &PgPool, user_id: Uuid) -> Result<Self, sqlx::Error> {
+        let profile: Profile = sqlx::query_as(
+            "SELECT id, email, email_undeliverable, timezone, locale, created_at, updated_at FROM users WHERE id = $1"
+        )
+            .bind(user_id)
+            .fetch_one(db)
+            .await?;
+        let roles: Vec<(String,)> = sqlx::query_as(
+            "SELECT roles.name FROM roles
+            INNER JOIN user_roles ON user_roles.role_id = roles.id
+            WHERE user_roles.user_id = $1 ORDER BY roles.name"
+        )
+            .bind(user_id)
+            .fetch_all(db)
+            .await?;
+        let notification_count = notifications::count(db, user_id).await?;
+
+        let (sessions, notification_preferences, api_tokens, notifications, audit_events) = tokio::try_join!(
+            session::list_for_user(db, user_id),
+            NotificationPreferences::fetch(db, user_id),
+            api_tokens::list(db, user_id),
+            notifications::list(db, user_id, notification_count as i64, 0),
+            audit::list_for_user(db, user_id, audit::EXPORT_LIMIT),
+        )?;
+
+        Ok(Self {
+            profile,
+            roles: roles.into_iter().map(|(name,)| name).collect(),
+            sessions: sessions.into_iter().map(|expires_at| Session { expires_at }).collect(),
+            notification_preferences,
+            api_tokens,
+            notifications,
+            audit_events,
+        })
+    }
+
+    /// A ZIP archive with a pretty printed JSON file per kind of data
+    pub fn to_zip(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let serde_json::Value::Object(sections) = serde_json::to_value(self)? else {
+            anyhow::bail!("Account data must serialize to an object");
+        };
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, section) in sections {
+            zip.start_file(format!("{}.json", name), options)?;
+            zip.write_all(&serde_json::to_vec_pretty(&section)?)?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+/// Queues an export. Returns `None` if one is already in progress.
+pub async fn request(db: &PgPool, user_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    let export_id: Option<(Uuid,)> = sqlx::query_as(
+        "INSERT INTO account_exports (id, user_id) VALUES ($1, $2)
+        ON CONFLICT (user_id) WHERE status IN ('pending', 'processing') DO NOTHING
+        RETURNING id"
+    )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(export_id.map(|(id,)| id))
+}
+
+/// The user's exports, newest first
+pub async fn list(db: &PgPool, user_id: Uuid) -> Result<Vec<AccountExport>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE user_id = $1 ORDER BY created_at DESC", SELECT_EXPORTS))
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+/// The archive of the user's export, if it is ready and not expired
+pub async fn find_archive(db: &PgPool, user_id: Uuid, export_id: Uuid) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let archive: Option<(Vec<u8>,)> = sqlx::query_as(
+        "SELECT archive FROM account_exports
+        WHERE id = $1 AND user_id = $2 AND status = 'ready' AND expires_at > NOW() AND archive IS NOT NULL"
+    )
+        .bind(export_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(archive.map(|(archive,)| archive))
+}
+
+/// Builds every pending export, one at a time. Returns how many were built.
+pub async fn process_pending(state: AppState) -> u64 {
+    let mut processed = 0;
+    loop {
+        match claim_next(&state.db).await {
+            Ok(Some((export_id, user_id))) => {
+                if let Err(err) = process(&state, export_id, user_id).await {
+                    tracing::error!(error = %err, %export_id, "Failed to build an account export");
+                    if let Err(err) = mark_failed(&state.db, export_id).await {
+                        tracing::error!(error = %err, %export_id, "Failed to mark an account export as failed");
+                    }
+                }
+                processed += 1;
+            },
+            Ok(None) => return processed,
+            Err(err) => {
+                tracing::error!(error = %err, "Failed to pick up pending account exports");
+                return processed;
+            },
+        }
+    }
+}
+
+/// Marks the oldest pending export as processing so no other instance
+/// builds it too
+async fn claim_next(db: &PgPool) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "UPDATE account_exports SET status = 'processing'
+        WHERE id = (
+            SELECT id FROM account_exports
+            WHERE status = 'pending' OR (status = 'processing' AND updated_at < NOW() - INTERVAL '{}')
+            ORDER BY created_at
+            FOR UPDATE SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING id, user_id",
+        STALE_AFTER
+    ))
+        .fetch_optional(db)
+        .await
+}
+
+async fn process(state: &AppState, export_id: Uuid, user_id: Uuid) -> Result<(), anyhow::Error> {
+    let data = AccountData::collect(&state.db, user_id).await?;
+    let archive = data.to_zip()?;
+    sqlx::query(
+        "UPDATE account_exports
+        SET status = 'ready', archive = $1, expires_at = NOW() + make_interval(hours => $2)
+        WHERE id = $3"
+    )
+        .bind(archive)
+        .bind(state.account_settings.export_expiry_hours)
+        .bind(export_id)
+        .execute(&state.db)
+        .await?;
+
+    // Sent in the language the user chose, if they did
+    let locale = data.profile.locale.as_deref().and_then(|locale| state.i18n.supported(locale)).unwrap_or_default();
+    let title = state.i18n.message(&locale, "account-export-ready");
+    let url = format!("{}{}", state.base_url, download_path(export_id));
+    let expiry_hours = state.account_settings.export_expiry_hours.to_string();
+    let mut context = HashMap::new();
+    context.insert("url", url.as_str());
+    context.insert("expiry_hours", expiry_hours.as_str());
+    let params = UserEmailParams {
+        user_id,
+        to: &data.profile.email,
+        category: EmailCategory::Transactional,
+        subject: &title,
+        template_name: email_templates::ACCOUNT_EXPORT,
+        context: &context,
+    };
+    if let Err(err) = emailer::send_user_email(state, params).await {
+        tracing::error!(error = %err, %export_id, "Failed to email an account export link");
+    }
+
+    // The export is ready and its link sent, so failing to notify must not
+    // get it marked as failed
+    let notification = NewNotification::new(NotificationKind::Info, title)
+        .with_url(route_paths::ACCOUNT_EXPORT);
+    if let Err(err) = notifications::notify(state, user_id, notification).await {
+        tracing::error!(error = %err, %export_id, "Failed to notify about an account export");
+    }
+
+    Ok(())
+}
+
+async fn mark_failed(db: &PgPool, export_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE account_exports SET status = 'failed', archive = NULL WHERE id = $1")
+        .bind(export_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub fn download_path(export_id: Uuid) -> String {
+    route_paths::ACCOUNT_EXPORT_DOWNLOAD.replace(":id", &export_id.to_string())
+}
+
+/// Deletes exports whose link expired and failed exports older than a day.
+/// Returns how many were deleted.
+pub async fn prune(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM account_exports
+        WHERE (status = 'ready' AND expires_at < NOW())
+            OR (status = 'failed' AND created_at < NOW() - INTERVAL '1 day')"
+    )
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Builds pending exports and prunes expired ones every `PROCESS_INTERVAL`,
+/// until aborted. Exports are also built right after they are requested;
+/// this picks up the ones an instance didn't get to.
+pub async fn continuously_process(state: AppState) {
+    let mut interval = tokio::time::interval(PROCESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        process_pending(state.clone()).await;
+        match prune(&state.db).await {
+            Ok(0) => {},
+            Ok(deleted) => tracing::info!(deleted, "Pruned expired account exports"),
+            Err(err) => tracing::error!(error = %err, "Failed to prune expired account exports"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountData, Profile};
+    use crate::notification_preferences::NotificationPreferences;
+    use std::io::Read;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    #[test]
+    fn archive_has_a_json_file_per_section() {
+        let data = AccountData {
+            profile: Profile {
+                id: Uuid::new_v4(),
+                email: "ursula@example.com".to_string(),
+                email_undeliverable: false,
+                timezone: "UTC".to_string(),
+                locale: None,
+                created_at: OffsetDateTime::UNIX_EPOCH,
+                updated_at: OffsetDateTime::UNIX_EPOCH,
+            },
+            roles: vec!["admin".to_string()],
+            sessions: vec![],
+            notification_preferences: NotificationPreferences::default(),
+            api_tokens: vec![],
+            notifications: vec![],
+            audit_events: vec![],
+        };
+
+        let archive = data.to_zip().unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, [
+            "api_tokens.json",
+            "audit_events.json",
+            "notification_preferences.json",
+            "notifications.json",
+            "profile.json",
+            "roles.json",
+            "sessions.json",
+        ]);
+
+        let mut profile = String::new();
+        zip.by_name("profile.json").unwrap().read_to_string(&mut profile).unwrap();
+        let profile: serde_json::Value = serde_json::from_str(&profile).unwrap();
+        assert_eq!(profile["email"], "ursula@example.com");
+    }
+}
@@ -0,0 +1,212 @@
+//! src/api_tokens.rs
+//! Personal API tokens. `authenticate_bearer` lets requests with an
+//! `Authorization: Bearer` header in as the owner of the token, so handlers
+//! get the same `AuthSession::user` they get for cookie sessions.
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_login::AuthnBackend;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::domain::{ApiTokenScope, PlainApiToken};
+use crate::error::AppError;
+use crate::startup::AppState;
+use crate::user::AuthSession;
+use crate::utils::{constant_time_eq, e500};
+
+/// `last_used_at` is only updated when it is older than this, so busy tokens
+/// do not write on every request
+const LAST_USED_RESOLUTION: &str = "1 minute";
+
+const SELECT_TOKENS: &str = "SELECT id, user_id, name, prefix, token_hash, scopes, expires_at, last_used_at, revoked_at, created_at,
+    (revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())) AS active
+    FROM api_tokens";
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct ApiToken {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip)]
+    token_hash: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revoked_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Neither revoked nor expired
+    pub active: bool,
+}
+
+impl ApiToken {
+    pub fn scopes(&self) -> Vec<ApiTokenScope> {
+        self.scopes
+            .iter()
+            .filter_map(|scope| ApiTokenScope::try_from(scope.as_str()).ok())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewApiToken {
+    pub name: String,
+    pub scopes: Vec<ApiTokenScope>,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// Added to the request extensions when the request was authenticated with
+/// an API token rather than the session cookie
+#[derive(Debug, Clone)]
+pub struct ApiTokenAuth {
+    pub token_id: Uuid,
+    pub scopes: Vec<ApiTokenScope>,
+}
+
+/// Creates a token and returns it in plain text. It can't be shown again.
+pub async fn create(db: &PgPool, user_id: Uuid, new_token: &NewApiToken) -> Result<PlainApiToken, sqlx::Error> {
+    let token = PlainApiToken::generate();
+    let scopes: Vec<&str> = new_token.scopes.iter().map(ApiTokenScope::as_str).collect();
+    sqlx::query(
+        "INSERT INTO api_tokens (id, user_id, name, prefix, token_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(&new_token.name)
+        .bind(token.prefix())
+        .bind(token.hash())
+        .bind(&scopes)
+        .bind(new_token.expires_at)
+        .execute(db)
+        .await?;
+
+    Ok(token)
+}
+
+pub async fn list(db: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE user_id = $1 ORDER BY created_at DESC", SELECT_TOKENS))
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn list_page(db: &PgPool, user_id: Uuid, limit: i64, offset: i64) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE user_id = $1 ORDER BY created_at DESC, id LIMIT $2 OFFSET $3", SELECT_TOKENS))
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count(db: &PgPool, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM api_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+    Ok(count as u64)
+}
+
+/// Returns false if the user has no such active token
+pub async fn revoke(db: &PgPool, user_id: Uuid, token_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE api_tokens SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(token_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Finds the token if it exists, matches its hash and is still active
+pub async fn find_active(db: &PgPool, token: &PlainApiToken) -> Result<Option<ApiToken>, sqlx::Error> {
+    let api_token: Option<ApiToken> = sqlx::query_as(&format!("{} WHERE prefix = $1", SELECT_TOKENS))
+        .bind(token.prefix())
+        .fetch_optional(db)
+        .await?;
+
+    Ok(api_token.filter(|api_token| api_token.active && constant_time_eq(&api_token.token_hash, &token.hash())))
+}
+
+pub async fn touch(db: &PgPool, token_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
+        "UPDATE api_tokens SET last_used_at = NOW()
+        WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '{}')",
+        LAST_USED_RESOLUTION
+    ))
+        .bind(token_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Middleware logging in requests that carry an `Authorization: Bearer`
+/// token. Requests without one are left to the session cookie. Invalid,
+/// expired and revoked tokens get a 401, and tokens without the scope the
+/// request method needs get a 403.
+pub async fn authenticate_bearer(
+    Extension(state): Extension<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(bearer) = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return next.run(request).await;
+    };
+
+    let api_token = match PlainApiToken::parse(bearer.trim()) {
+        Ok(token) => match find_active(&state.db, &token).await.map_err(e500) {
+            Ok(Some(api_token)) => api_token,
+            Ok(None) => return unauthorized(),
+            Err(err) => return err.into_response(),
+        },
+        Err(_) => return unauthorized(),
+    };
+
+    let scopes = api_token.scopes();
+    if !scopes.contains(&ApiTokenScope::required_for(request.method())) {
+        tracing::warn!(token = %api_token.prefix, method = %request.method(), "API token is missing the scope for this request");
+        return AppError::Forbidden.into_response();
+    }
+
+    let Some(auth_session) = request.extensions_mut().get_mut::<AuthSession>() else {
+        return e500("The auth layer is missing").into_response();
+    };
+    match auth_session.backend.get_user(&api_token.user_id).await.map_err(e500) {
+        Ok(Some(user)) => auth_session.user = Some(user),
+        Ok(None) => return unauthorized(),
+        Err(err) => return err.into_response(),
+    }
+
+    if let Err(err) = touch(&state.db, api_token.id).await {
+        tracing::error!(error = %err, "Failed to update when the API token was last used");
+    }
+
+    request.extensions_mut().insert(ApiTokenAuth { token_id: api_token.id, scopes });
+    next.run(request).await
+}
+
+fn unauthorized() -> Response {
+    let mut response = AppError::Unauthorized.into_response();
+    response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
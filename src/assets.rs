@@ -0,0 +1,230 @@
+//! src/assets.rs
+//! Fingerprinted static assets. At startup every file in `public/` is hashed
+//! into a manifest, and templates link assets with `{{ asset(path="css/main.css") }}`,
+//! which resolves to `/public/css/main.<hash>.css`. Fingerprinted URLs change
+//! whenever the file does, so they are served with `Cache-Control: immutable`.
+//! Plain URLs still work but are revalidated on every use.
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Uri},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
+use crate::compression;
+
+pub const PUBLIC_DIR: &str = "public";
+pub const URL_PREFIX: &str = "/public";
+/// Hex characters of the content hash put in file names
+const HASH_LENGTH: usize = 16;
+const IMMUTABLE: HeaderValue = HeaderValue::from_static("public, max-age=31536000, immutable");
+const REVALIDATE: HeaderValue = HeaderValue::from_static("no-cache");
+
+/// The manifest shared by the `asset` function and the static file service.
+/// Behind a lock so it can be rebuilt when assets change during development.
+pub type SharedAssets = Arc<RwLock<AssetManifest>>;
+
+/// The content hash of every asset, by its path relative to `public/`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetManifest {
+    hashes: HashMap<String, String>,
+}
+
+impl AssetManifest {
+    /// Hashes every file under `dir`. Precompressed copies and files whose
+    /// path has characters that would need escaping in a URL are left out.
+    pub fn build(dir: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let dir = dir.as_ref();
+        let mut hashes = HashMap::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(current) = dirs.pop() {
+            for entry in fs::read_dir(&current)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                // Served in place of the original by `ServeDir`
+                if compression::is_precompressed(&path) {
+                    continue;
+                }
+                let Some(name) = path.strip_prefix(dir).ok().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                let name = name.replace(std::path::MAIN_SEPARATOR, "/");
+                if !is_url_safe(&name) {
+                    tracing::warn!(asset = %name, "Skipping an asset whose path would need escaping in a URL");
+                    continue;
+                }
+                let hash = hex::encode(Sha256::digest(fs::read(&path)?));
+                hashes.insert(name, hash[..HASH_LENGTH].to_string());
+            }
+        }
+
+        Ok(Self { hashes })
+    }
+
+    /// The fingerprinted URL of an asset, e.g. `/public/css/main.<hash>.css`
+    pub fn url(&self, path: &str) -> Option<String> {
+        let path = path.trim_start_matches('/');
+        let hash = self.hashes.get(path)?;
+        Some(format!("{}/{}", URL_PREFIX, fingerprint(path, hash)))
+    }
+
+    /// Splits a fingerprinted path into the asset's own path and whether the
+    /// hash is the current one. `None` if the path is not fingerprinted.
+    fn resolve(&self, path: &str) -> Option<(String, bool)> {
+        let (asset, hash) = strip_fingerprint(path)?;
+        let current = self.hashes.get(&asset)?;
+        let is_current = current == hash;
+        Some((asset, is_current))
+    }
+}
+
+fn is_url_safe(path: &str) -> bool {
+    path.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-' | b'/'))
+}
+
+/// `css/main.css` becomes `css/main.<hash>.css`
+fn fingerprint(path: &str, hash: &str) -> String {
+    let (dir, file_name) = path.rsplit_once('/').map_or(("", path), |(dir, name)| (dir, name));
+    let file_name = match file_name.split_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}.{}.{}", stem, hash, extension),
+        _ => format!("{}.{}", file_name, hash),
+    };
+    if dir.is_empty() { file_name } else { format!("{}/{}", dir, file_name) }
+}
+
+/// The reverse of `fingerprint`: `css/main.<hash>.css` becomes `css/main.css`
+fn strip_fingerprint(path: &str) -> Option<(String, &str)> {
+    let path = path.trim_start_matches('/');
+    let (dir, file_name) = path.rsplit_once('/').map_or(("", path), |(dir, name)| (dir, name));
+    let mut parts = file_name.splitn(3, '.');
+    let stem = parts.next()?;
+    let hash = parts.next()?;
+    if stem.is_empty() || hash.len() != HASH_LENGTH || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let file_name = match parts.next() {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem.to_string(),
+    };
+    let asset = if dir.is_empty() { file_name } else { format!("{}/{}", dir, file_name) };
+    Some((asset, hash))
+}
+
+/// Tera function resolving an asset to its fingerprinted URL:
+/// `{{ asset(path="css/main.css") }}`. Unknown assets are an error so typos
+/// don't go unnoticed.
+pub struct AssetUrl(pub SharedAssets);
+
+impl tera::Function for AssetUrl {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let path = args
+            .get("path")
+            .and_then(|path| path.as_str())
+            .ok_or("asset requires a `path` argument")?;
+        let manifest = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        let url = manifest.url(path).ok_or_else(|| format!("{} is not an asset in {}/", path, PUBLIC_DIR))?;
+        Ok(tera::Value::String(url))
+    }
+
+    // Asset paths only ever contain URL safe characters
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Middleware in front of the static file service. Fingerprinted paths are
+/// served from the file they name and cached for good when the hash is the
+/// current one. Anything else has to be revalidated, including outdated
+/// fingerprints, which still get the current file.
+pub async fn serve_fingerprinted(
+    State(assets): State<SharedAssets>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let resolved = {
+        let manifest = assets.read().unwrap_or_else(PoisonError::into_inner);
+        manifest.resolve(request.uri().path())
+    };
+    let mut cache_control = REVALIDATE;
+    if let Some((asset, is_current)) = resolved {
+        let path_and_query = match request.uri().query() {
+            Some(query) => format!("/{}?{}", asset, query),
+            None => format!("/{}", asset),
+        };
+        if let Ok(uri) = path_and_query.parse::<Uri>() {
+            *request.uri_mut() = uri;
+            if is_current {
+                cache_control = IMMUTABLE;
+            }
+        }
+    }
+
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        response.headers_mut().insert(header::CACHE_CONTROL, cache_control);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint, strip_fingerprint, AssetManifest};
+    use std::fs;
+
+    const HASH: &str = "0123456789abcdef";
+
+    #[test]
+    fn fingerprints_go_before_the_extension() {
+        assert_eq!(fingerprint("css/main.css", HASH), "css/main.0123456789abcdef.css");
+        assert_eq!(fingerprint("js/app.min.js", HASH), "js/app.0123456789abcdef.min.js");
+        assert_eq!(fingerprint("LICENSE", HASH), "LICENSE.0123456789abcdef");
+        assert_eq!(fingerprint(".well-known", HASH), ".well-known.0123456789abcdef");
+    }
+
+    #[test]
+    fn fingerprints_can_be_stripped() {
+        for path in ["css/main.css", "js/app.min.js", "LICENSE", "favicon.ico"] {
+            let fingerprinted = fingerprint(path, HASH);
+            assert_eq!(strip_fingerprint(&fingerprinted), Some((path.to_string(), HASH)));
+        }
+        assert_eq!(strip_fingerprint("/css/main.css"), None);
+        assert_eq!(strip_fingerprint("css/main.notahash.css"), None);
+    }
+
+    #[test]
+    fn manifest_hashes_nested_files_by_content() {
+        let dir = std::env::temp_dir().join(format!("assets-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(dir.join("css/main.css"), "body {}").unwrap();
+        fs::write(dir.join("css/same.css"), "body {}").unwrap();
+        fs::write(dir.join("app.js"), "alert(1)").unwrap();
+        fs::write(dir.join("app.js.gz"), "").unwrap();
+        fs::write(dir.join("needs escaping.js"), "").unwrap();
+
+        let manifest = AssetManifest::build(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let main = manifest.url("css/main.css").unwrap();
+        assert!(main.starts_with("/public/css/main.") && main.ends_with(".css"));
+        assert_eq!(
+            main.trim_start_matches("/public/css/main."),
+            manifest.url("/css/same.css").unwrap().trim_start_matches("/public/css/same."),
+        );
+        assert!(manifest.url("app.js").is_some());
+        assert_eq!(manifest.url("app.js.gz"), None);
+        assert_eq!(manifest.url("needs escaping.js"), None);
+        assert_eq!(manifest.url("missing.css"), None);
+
+        let (asset, is_current) = manifest.resolve(main.trim_start_matches("/public")).unwrap();
+        assert_eq!(asset, "css/main.css");
+        assert!(is_current);
+        assert_eq!(manifest.resolve(&format!("/css/main.{}.css", HASH)), Some(("css/main.css".to_string(), false)));
+    }
+}
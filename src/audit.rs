@@ -0,0 +1,356 @@
+//! src/audit.rs
+//! The audit log. Security relevant events are appended to `audit_events`
+//! with who did what to whom, from where. Rows can't be changed or deleted;
+//! a trigger rejects it. As rows outlive deleted accounts, they refer to users
+//! by id only; their emails are joined in from `users` while they exist.
+//!
+//! ```ignore
+//! audit::record(&state.db, &NewAuditEvent::new(AuditAction::Logout).actor(user.id()), &audit_context).await?;
+//! ```
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, QueryBuilder};
+use std::convert::Infallible;
+use time::{Date, OffsetDateTime};
+use uuid::Uuid;
+use crate::domain::AuditAction;
+use crate::error::FieldErrors;
+use crate::proxy::TrustedProxies;
+
+/// User agents are cut to this many characters
+const MAX_USER_AGENT_LENGTH: usize = 512;
+/// Exports are capped at this many events. Narrow the filter to get the rest.
+pub const EXPORT_LIMIT: i64 = 50_000;
+
+const SELECT_EVENTS: &str = "SELECT audit_events.id, audit_events.action, audit_events.actor_id, actor.email AS actor_email,
+    audit_events.target_id, target.email AS target_email, audit_events.ip, audit_events.user_agent,
+    audit_events.metadata, audit_events.created_at
+    FROM audit_events
+    LEFT JOIN users actor ON actor.id = audit_events.actor_id
+    LEFT JOIN users target ON target.id = audit_events.target_id";
+
+/// Where a request came from
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Installed for every request by `startup::run`
+        let trusted_proxies = parts.extensions.get::<TrustedProxies>().copied().unwrap_or_default();
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Ok(Self {
+            ip: trusted_proxies.client_ip(&parts.headers, &parts.extensions),
+            user_agent,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub action: AuditAction,
+    /// Who did it, if anyone was logged in
+    pub actor_id: Option<Uuid>,
+    /// Whose account it was done to
+    pub target_id: Option<Uuid>,
+    pub metadata: serde_json::Value,
+}
+
+impl NewAuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            actor_id: None,
+            target_id: None,
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn target(mut self, target_id: Uuid) -> Self {
+        self.target_id = Some(target_id);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub action: String,
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub target_email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub metadata: serde_json::Value,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Appends an event. Takes any executor so the event can be written in the
+/// same transaction as the change it records.
+pub async fn record<'e, E>(executor: E, event: &NewAuditEvent, context: &AuditContext) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO audit_events (id, action, actor_id, target_id, ip, user_agent, metadata)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+        .bind(Uuid::new_v4())
+        .bind(event.action.as_str())
+        .bind(event.actor_id)
+        .bind(event.target_id)
+        .bind(&context.ip)
+        .bind(&context.user_agent)
+        .bind(&event.metadata)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Records an event, only logging a failure. For events that must not fail
+/// the request they happened in, like a login.
+pub async fn record_or_log(db: &PgPool, event: &NewAuditEvent, context: &AuditContext) {
+    if let Err(err) = record(db, event, context).await {
+        tracing::error!(error = %err, action = event.action.as_str(), "Failed to record an audit event");
+    }
+}
+
+/// The filters of the admin viewer and the exports, as submitted. Empty
+/// fields don't filter.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditFilterForm {
+    #[serde(default)]
+    pub action: String,
+    /// An email address or user id, matching the actor or the target
+    #[serde(default)]
+    pub user: String,
+    /// `YYYY-MM-DD`, inclusive
+    #[serde(default)]
+    pub from: String,
+    /// `YYYY-MM-DD`, inclusive
+    #[serde(default)]
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub user: Option<String>,
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+}
+
+impl TryFrom<&AuditFilterForm> for AuditFilter {
+    type Error = FieldErrors;
+
+    fn try_from(form: &AuditFilterForm) -> Result<Self, Self::Error> {
+        let mut errors = FieldErrors::new();
+        let format = time::format_description::well_known::Iso8601::DATE;
+        let mut date = |field: &str, value: &str| match value.trim() {
+            "" => None,
+            value => Date::parse(value, &format)
+                .map_err(|_| errors.add(field, "must be a date like 2024-08-31"))
+                .ok(),
+        };
+        let from = date("from", &form.from);
+        let to = date("to", &form.to);
+        let action = match form.action.trim() {
+            "" => None,
+            action => AuditAction::try_from(action).map_err(|err| errors.add("action", err)).ok(),
+        };
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let user = Some(form.user.trim().to_lowercase()).filter(|user| !user.is_empty());
+        Ok(Self { action, user, from, to })
+    }
+}
+
+impl AuditFilter {
+    fn push_where(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        builder.push(" WHERE TRUE");
+        if let Some(action) = self.action {
+            builder.push(" AND audit_events.action = ").push_bind(action.as_str());
+        }
+        if let Some(ref user) = self.user {
+            builder
+                .push(" AND (lower(actor.email) = ").push_bind(user.clone())
+                .push(" OR lower(target.email) = ").push_bind(user.clone())
+                .push(" OR audit_events.actor_id::text = ").push_bind(user.clone())
+                .push(" OR audit_events.target_id::text = ").push_bind(user.clone())
+                .push(")");
+        }
+        if let Some(from) = self.from {
+            builder.push(" AND audit_events.created_at >= ").push_bind(from.midnight().assume_utc());
+        }
+        if let Some(to) = self.to {
+            let end = to.next_day().unwrap_or(to).midnight().assume_utc();
+            builder.push(" AND audit_events.created_at < ").push_bind(end);
+        }
+    }
+}
+
+/// Matching events, newest first
+pub async fn list(db: &PgPool, filter: &AuditFilter, limit: i64, offset: i64) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    let mut builder = QueryBuilder::new(SELECT_EVENTS);
+    filter.push_where(&mut builder);
+    builder
+        .push(" ORDER BY audit_events.created_at DESC, audit_events.id LIMIT ").push_bind(limit)
+        .push(" OFFSET ").push_bind(offset);
+    builder.build_query_as().fetch_all(db).await
+}
+
+pub async fn count(db: &PgPool, filter: &AuditFilter) -> Result<u64, sqlx::Error> {
+    let mut builder = QueryBuilder::new(
+        "SELECT COUNT(*) FROM audit_events
+        LEFT JOIN users actor ON actor.id = audit_events.actor_id
+        LEFT JOIN users target ON target.id = audit_events.target_id"
+    );
+    filter.push_where(&mut builder);
+    let (count,): (i64,) = builder.build_query_as().fetch_one(db).await?;
+
+    Ok(count as u64)
+}
+
+/// Recent events done by or to the user, newest first
+pub async fn list_for_user(db: &PgPool, user_id: Uuid, limit: i64) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "{} WHERE audit_events.actor_id = $1 OR audit_events.target_id = $1
+        ORDER BY audit_events.created_at DESC, audit_events.id LIMIT $2",
+        SELECT_EVENTS
+    ))
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+/// Renders events as CSV with a header row
+pub fn to_csv(events: &[AuditEvent]) -> String {
+    let mut csv = String::from("id,created_at,action,actor_id,actor_email,target_id,target_email,ip,user_agent,metadata\r\n");
+    for event in events {
+        let created_at = event.created_at.format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
+        let fields = [
+            event.id.to_string(),
+            created_at,
+            event.action.clone(),
+            event.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            event.actor_email.clone().unwrap_or_default(),
+            event.target_id.map(|id| id.to_string()).unwrap_or_default(),
+            event.target_email.clone().unwrap_or_default(),
+            event.ip.clone().unwrap_or_default(),
+            event.user_agent.clone().unwrap_or_default(),
+            event.metadata.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Quotes a CSV field when needed. Fields that spreadsheets would run as a
+/// formula are prefixed with `'`.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, AuditContext, AuditFilter, AuditFilterForm};
+    use crate::domain::AuditAction;
+    use crate::proxy::TrustedProxies;
+    use axum::extract::FromRequestParts;
+    use axum::http::Request;
+    use time::{Date, Month};
+
+    #[tokio::test]
+    async fn the_ip_comes_from_the_trusted_proxies() {
+        let request = Request::builder()
+            .header("X-Forwarded-For", "198.51.100.7, 203.0.113.9")
+            .extension(TrustedProxies::new(1))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let Ok(context) = AuditContext::from_request_parts(&mut parts, &()).await;
+        assert_eq!(context.ip.as_deref(), Some("203.0.113.9"));
+    }
+
+    #[test]
+    fn empty_filters_match_everything() {
+        let filter = AuditFilter::try_from(&AuditFilterForm::default()).unwrap();
+        assert_eq!(filter, AuditFilter::default());
+    }
+
+    #[test]
+    fn filters_are_parsed() {
+        let form = AuditFilterForm {
+            action: "login_failed".into(),
+            user: " Someone@Example.com ".into(),
+            from: "2024-08-01".into(),
+            to: "2024-08-31".into(),
+        };
+        let filter = AuditFilter::try_from(&form).unwrap();
+        assert_eq!(filter.action, Some(AuditAction::LoginFailed));
+        assert_eq!(filter.user.as_deref(), Some("someone@example.com"));
+        assert_eq!(filter.from, Some(Date::from_calendar_date(2024, Month::August, 1).unwrap()));
+        assert_eq!(filter.to, Some(Date::from_calendar_date(2024, Month::August, 31).unwrap()));
+    }
+
+    #[test]
+    fn invalid_filters_are_reported_per_field() {
+        let form = AuditFilterForm { action: "nope".into(), from: "yesterday".into(), ..Default::default() };
+        let errors = AuditFilter::try_from(&form).unwrap_err();
+        assert!(errors.get("action").is_some());
+        assert!(errors.get("from").is_some());
+        assert!(errors.get("to").is_none());
+    }
+
+    #[test]
+    fn csv_fields_are_escaped() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+    }
+}
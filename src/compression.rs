@@ -0,0 +1,220 @@
+//! src/compression.rs
+//! Brotli and gzip compression. The CSS and JavaScript in `public/` are
+//! compressed once at startup into `.br` and `.gz` files next to the
+//! originals, which `ServeDir` picks from based on `Accept-Encoding`.
+//! Rendered pages and JSON are compressed as they are sent, when they are
+//! big enough and their content type is allowed.
+use axum::{body::HttpBody, http::{header, Response}};
+use brotli::enc::BrotliEncoderParams;
+use flate2::{write::GzEncoder, Compression};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower_http::compression::{predicate::SizeAbove, CompressionLayer, Predicate};
+use crate::configuration::CompressionSettings;
+
+/// Extensions of the assets worth compressing ahead of time
+const PRECOMPRESSED_EXTENSIONS: [&str; 3] = ["css", "js", "svg"];
+/// Extensions of the precompressed copies
+const COMPRESSED_EXTENSIONS: [&str; 2] = ["br", "gz"];
+
+/// Whether the file is a precompressed copy of another asset
+pub fn is_precompressed(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| COMPRESSED_EXTENSIONS.contains(&extension))
+}
+
+/// Writes a `.br` and a `.gz` copy of every CSS, JavaScript and SVG file
+/// under `dir`. Copies newer than their original are left alone, so this is
+/// cheap to run again. Returns how many copies were written.
+pub fn precompress_dir(dir: impl AsRef<Path>) -> Result<usize, io::Error> {
+    let mut written = 0;
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let compressible = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| PRECOMPRESSED_EXTENSIONS.contains(&extension));
+            if compressible {
+                written += precompress_file(&path)?;
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+fn precompress_file(path: &Path) -> Result<usize, io::Error> {
+    let modified = fs::metadata(path)?.modified()?;
+    let mut contents = None;
+    let mut written = 0;
+    for extension in COMPRESSED_EXTENSIONS {
+        let copy = compressed_path(path, extension);
+        let is_fresh = fs::metadata(&copy)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|copy_modified| copy_modified >= modified);
+        if is_fresh {
+            continue;
+        }
+
+        let contents = match &contents {
+            Some(contents) => contents,
+            None => contents.insert(fs::read(path)?),
+        };
+        let compressed = match extension {
+            "br" => brotli(contents)?,
+            _ => gzip(contents)?,
+        };
+        fs::write(&copy, compressed)?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// `css/main.css` becomes `css/main.css.br`, as `ServeDir` expects
+fn compressed_path(path: &Path, extension: &str) -> PathBuf {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(".");
+    copy.push(extension);
+    PathBuf::from(copy)
+}
+
+fn brotli(contents: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let params = BrotliEncoderParams { quality: 11, ..Default::default() };
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut &contents[..], &mut compressed, &params)?;
+    Ok(compressed)
+}
+
+fn gzip(contents: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(contents)?;
+    encoder.finish()
+}
+
+/// Decides which responses are compressed as they are sent
+#[derive(Clone)]
+pub struct ShouldCompress {
+    enabled: bool,
+    min_size: SizeAbove,
+    content_types: Arc<[String]>,
+}
+
+impl ShouldCompress {
+    pub fn from_settings(settings: &CompressionSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            min_size: SizeAbove::new(settings.min_size),
+            content_types: settings.content_types.iter().map(|content_type| content_type.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    fn allows_content_type(&self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        self.content_types.contains(&media_type)
+    }
+}
+
+impl Predicate for ShouldCompress {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok());
+        self.enabled
+            && content_type.is_some_and(|content_type| self.allows_content_type(content_type))
+            && self.min_size.should_compress(response)
+    }
+}
+
+/// Compresses responses with brotli or gzip as allowed by the settings.
+/// Responses that are already compressed, like precompressed assets, are
+/// left alone.
+pub fn layer(settings: &CompressionSettings) -> CompressionLayer<ShouldCompress> {
+    CompressionLayer::new()
+        .no_deflate()
+        .no_zstd()
+        .compress_when(ShouldCompress::from_settings(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_precompressed, precompress_dir, ShouldCompress};
+    use crate::configuration::CompressionSettings;
+    use axum::body::Body;
+    use axum::http::{header, Response};
+    use std::fs;
+    use std::io::Read;
+    use std::path::Path;
+    use tower_http::compression::Predicate;
+
+    fn response(content_type: &str, size: usize) -> Response<Body> {
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(vec![b'a'; size]))
+            .unwrap()
+    }
+
+    #[test]
+    fn assets_are_precompressed_once() {
+        let dir = std::env::temp_dir().join(format!("compression-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("css")).unwrap();
+        let css = "body { color: red; }\n".repeat(100);
+        fs::write(dir.join("css/main.css"), &css).unwrap();
+        fs::write(dir.join("logo.png"), [0u8; 64]).unwrap();
+
+        assert_eq!(precompress_dir(&dir).unwrap(), 2);
+        assert_eq!(precompress_dir(&dir).unwrap(), 0);
+
+        let mut gunzipped = String::new();
+        flate2::read::GzDecoder::new(&fs::read(dir.join("css/main.css.gz")).unwrap()[..])
+            .read_to_string(&mut gunzipped)
+            .unwrap();
+        assert_eq!(gunzipped, css);
+        let mut unbrotlied = String::new();
+        brotli::Decompressor::new(&fs::read(dir.join("css/main.css.br")).unwrap()[..], 4096)
+            .read_to_string(&mut unbrotlied)
+            .unwrap();
+        assert_eq!(unbrotlied, css);
+        assert!(!dir.join("logo.png.gz").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copies_are_recognised() {
+        assert!(is_precompressed(Path::new("css/main.css.br")));
+        assert!(is_precompressed(Path::new("css/main.css.gz")));
+        assert!(!is_precompressed(Path::new("css/main.css")));
+    }
+
+    #[test]
+    fn only_big_enough_responses_of_allowed_types_are_compressed() {
+        let should_compress = ShouldCompress::from_settings(&CompressionSettings::default());
+
+        assert!(should_compress.should_compress(&response("text/html; charset=utf-8", 2048)));
+        assert!(should_compress.should_compress(&response("application/json", 2048)));
+        assert!(!should_compress.should_compress(&response("text/html", 100)));
+        assert!(!should_compress.should_compress(&response("image/png", 2048)));
+        assert!(!should_compress.should_compress(&response("text/event-stream", 2048)));
+    }
+
+    #[test]
+    fn nothing_is_compressed_when_disabled() {
+        let settings = CompressionSettings { enabled: false, ..Default::default() };
+        let should_compress = ShouldCompress::from_settings(&settings);
+
+        assert!(!should_compress.should_compress(&response("text/html", 2048)));
+    }
+}
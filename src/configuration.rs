@@ -6,7 +6,9 @@ use sqlx::ConnectOptions;
 use std::convert::{TryFrom, TryInto};
 
 /// The possible runtime environment for our application.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Environment {
+    #[default]
     Local,
     Production,
 }
@@ -42,6 +44,9 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email: EmailSettings,
     pub redis_uri: Secret<String>,
+    /// Set from `APP_ENVIRONMENT` when the configuration is loaded
+    #[serde(skip)]
+    pub environment: Environment,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -51,6 +56,259 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersSettings,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    #[serde(default)]
+    pub account: AccountSettings,
+    #[serde(default)]
+    pub dev_reload: DevReloadSettings,
+    #[serde(default)]
+    pub compression: CompressionSettings,
+    #[serde(default)]
+    pub scss: ScssSettings,
+}
+
+/// Session cookie settings. Cookies are always `Secure` in production.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SessionSettings {
+    /// Master secret the cookie signing key is derived from. At least 32 bytes.
+    pub secret: Secret<String>,
+    /// Secrets that were replaced by `secret`. Cookies signed with them are
+    /// still accepted and re-signed with the current key, so rotating the
+    /// secret does not log everyone out. Remove them once sessions expire.
+    #[serde(default)]
+    pub previous_secrets: Vec<Secret<String>>,
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    #[serde(default)]
+    pub cookie_domain: Option<String>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default = "default_true")]
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: SameSite,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+/// Response security headers. HSTS is only sent in production.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SecurityHeadersSettings {
+    /// Send `Content-Security-Policy-Report-Only` instead of enforcing the
+    /// policy, so violations are only reported to `/csp-report`
+    pub csp_report_only: bool,
+    /// Extra sources allowed by `script-src`, `style-src`, `img-src` and
+    /// `connect-src` on top of `'self'`
+    pub script_src: Vec<String>,
+    pub style_src: Vec<String>,
+    pub img_src: Vec<String>,
+    pub connect_src: Vec<String>,
+    pub frame_ancestors: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+    pub hsts_max_age: u64,
+}
+
+impl Default for SecurityHeadersSettings {
+    fn default() -> Self {
+        Self {
+            csp_report_only: false,
+            script_src: Vec::new(),
+            style_src: Vec::new(),
+            img_src: Vec::new(),
+            connect_src: Vec::new(),
+            frame_ancestors: "'none'".to_string(),
+            referrer_policy: "strict-origin-when-cross-origin".to_string(),
+            permissions_policy: "camera=(), microphone=(), geolocation=(), payment=()".to_string(),
+            hsts_max_age: 63_072_000,
+        }
+    }
+}
+
+/// Where rate limit counters are kept. Use `redis` when running more than one
+/// instance so the limits are shared.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitStore {
+    #[default]
+    Memory,
+    Redis,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    pub store: RateLimitStore,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store: RateLimitStore::Memory,
+        }
+    }
+}
+
+/// The reverse proxies in front of the app, which tell it the client's
+/// address. Used by rate limits and the audit log alike.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// Take the client IP from `X-Forwarded-For`. Only enable this behind a
+    /// proxy that appends to the header.
+    pub trust_forwarded_for: bool,
+    /// How many proxies in front of the app append to `X-Forwarded-For`. The
+    /// client IP is the entry this many places from the right; the client
+    /// can put anything left of it.
+    pub trusted_proxy_hops: usize,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            trust_forwarded_for: false,
+            trusted_proxy_hops: 1,
+        }
+    }
+}
+
+/// How long in-app notifications are kept
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Days read notifications are kept
+    pub retention_days: i32,
+    /// Days notifications are kept when they were never read
+    pub unread_retention_days: i32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            retention_days: 90,
+            unread_retention_days: 365,
+        }
+    }
+}
+
+/// Data exports and account deletion
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AccountSettings {
+    /// Hours an export can be downloaded for after it is ready
+    pub export_expiry_hours: i32,
+    /// Days between asking for an account to be deleted and it being deleted
+    pub deletion_grace_period_days: i32,
+}
+
+impl Default for AccountSettings {
+    fn default() -> Self {
+        Self {
+            export_expiry_hours: 72,
+            deletion_grace_period_days: 14,
+        }
+    }
+}
+
+/// Reloading templates and SCSS when they change. Only ever done in the
+/// local environment.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DevReloadSettings {
+    pub enabled: bool,
+    /// Reload open tabs after a change
+    pub live_reload: bool,
+    /// How often to look for changes, in milliseconds
+    pub poll_interval_ms: u64,
+}
+
+impl Default for DevReloadSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            live_reload: true,
+            poll_interval_ms: 500,
+        }
+    }
+}
+
+/// Compiling `scss/` to `public/css/`
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ScssSettings {
+    /// Directories `@use` and `@import` also look in, besides `scss/`
+    pub load_paths: Vec<std::path::PathBuf>,
+    /// Defaults to compressed in production and expanded locally
+    pub style: Option<ScssStyle>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScssStyle {
+    Expanded,
+    Compressed,
+}
+
+/// Compressing responses with brotli or gzip, whichever the client prefers
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CompressionSettings {
+    /// Compress rendered responses as they are sent
+    pub enabled: bool,
+    /// Write `.br` and `.gz` copies of the CSS and JavaScript in `public/` at
+    /// startup, served instead of the original when the client accepts them
+    pub precompress_assets: bool,
+    /// Responses smaller than this are sent as they are, in bytes
+    pub min_size: u16,
+    /// Content types that are compressed. Anything else, like images or
+    /// event streams, is sent as it is.
+    pub content_types: Vec<String>,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            precompress_assets: true,
+            min_size: 1024,
+            content_types: [
+                "text/html",
+                "text/css",
+                "text/javascript",
+                "application/javascript",
+                "application/json",
+                "image/svg+xml",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+fn default_cookie_name() -> String {
+    "id".to_string()
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -74,6 +332,8 @@ pub struct EmailSettings {
     pub admin_email: String,
     pub support_email: String,
     pub welcome_email: String,
+    /// Shared with the email provider to sign bounce and complaint webhooks
+    pub webhook_secret: Secret<String>,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -133,8 +393,7 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
      * Try to convert the configuration values to read into
      * our Settings type below
      */
-    match settings.build() {
-        Ok(config) => config.try_deserialize::<Settings>(),
-        Err(e) => Err(e), 
-    }
+    let mut settings = settings.build()?.try_deserialize::<Settings>()?;
+    settings.environment = environment;
+    Ok(settings)
 }
@@ -8,31 +8,98 @@ pub mod html_templates {
     pub const REGISTER: &str = "register.html";
     pub const LOGIN: &str = "login.html";
     pub const HOMEPAGE: &str = "homepage.html";
+    pub const E404: &str = "404.html";
     pub const E500: &str = "500.html";
+    pub const ERROR: &str = "error.html";
+    pub const SCSS_ERROR: &str = "scss_error.html";
+    pub const FLASHES: &str = "partials/_flashes.html";
+    pub const UNSUBSCRIBE: &str = "unsubscribe.html";
+    pub const ACCOUNT_NOTIFICATIONS: &str = "account/notifications.html";
+    pub const ACCOUNT_TOKENS: &str = "account/tokens.html";
+    pub const ACCOUNT_SECURITY: &str = "account/security.html";
+    pub const ACCOUNT_EXPORT: &str = "account/export.html";
+    pub const ACCOUNT_DELETE: &str = "account/delete.html";
+    pub const ACCOUNT_PREFERENCES: &str = "account/preferences.html";
+    pub const API_EXPLORER: &str = "api/explorer.html";
+    pub const NOTIFICATIONS: &str = "notifications.html";
+    pub const ADMIN_SUPPRESSIONS: &str = "admin/suppressions.html";
+    pub const ADMIN_AUDIT: &str = "admin/audit.html";
 }
 
 /// email templates
 pub mod email_templates {
     pub const EMAIL_VERIFICATION: &str = "emails/email_verification.html";
+    pub const UNSUBSCRIBE_FOOTER: &str = "emails/partials/_unsubscribe_footer.html";
+    pub const NOTIFICATION: &str = "emails/notification.html";
+    pub const ACCOUNT_EXPORT: &str = "emails/account_export.html";
 }
 
 /// Strings
 pub mod strings {
-    pub const WELCOME_EMAIL_SUBJECT: &str = "Welcome to Axum Sass Template";
     pub const INTERNAL_SERVER_ERROR: &str = "Internal Server Error";
-    pub const REGISTER_ACCOUNT_SUCCESS: &str = "Successfully registered account!";
-    pub const INVALID_CREDENTIALS: &str = "Invalid Credentials";
     pub const FAILED_TO_COMPILE_SCSS: &str = "Failed to compile SCSS";
     pub const FAILED_TO_WRITE_SCSS: &str = "Failed to write SCSS";
 }
 
+/// Defines the route path constants along with `ALL`, every route by the name
+/// of its constant, which `url_for` in templates looks routes up in
+macro_rules! route_paths {
+    ($($name:ident = $path:literal;)*) => {
+        $(pub const $name: &str = $path;)*
+        pub const ALL: &[(&str, &str)] = &[$((stringify!($name), $path)),*];
+    };
+}
+
 /// paths
 pub mod route_paths {
-    pub const ROOT: &str = "/";
-    pub const REGISTER: &str = "/register";
-    pub const LOGIN: &str = "/login";
-    pub const LOGOUT: &str = "/logout";
-    pub const HEALTH: &str = "/health";
-    pub const PROTECTED: &str = "/protected";
+    route_paths! {
+        ROOT = "/";
+        REGISTER = "/register";
+        LOGIN = "/login";
+        LOGOUT = "/logout";
+        HEALTH = "/health";
+        PROTECTED = "/protected";
+        UNSUBSCRIBE = "/unsubscribe";
+        ACCOUNT_NOTIFICATIONS = "/account/notifications";
+        ACCOUNT_TOKENS = "/account/tokens";
+        ACCOUNT_TOKEN_REVOKE = "/account/tokens/:id/revoke";
+        ACCOUNT_SECURITY = "/account/security";
+        ACCOUNT_EXPORT = "/account/export";
+        ACCOUNT_EXPORT_DOWNLOAD = "/account/export/:id/download";
+        ACCOUNT_DELETE = "/account/delete";
+        ACCOUNT_DELETE_CANCEL = "/account/delete/cancel";
+        ACCOUNT_PREFERENCES = "/account/preferences";
+        WEBHOOKS_EMAIL_EVENTS = "/webhooks/email-events";
+        ADMIN_SUPPRESSIONS = "/admin/suppressions";
+        ADMIN_SUPPRESSION_CLEAR = "/admin/suppressions/:id/clear";
+        ADMIN_AUDIT = "/admin/audit";
+        ADMIN_AUDIT_EXPORT = "/admin/audit/export";
+        CSP_REPORT = "/csp-report";
+        API_V1 = "/api/v1";
+        NOTIFICATIONS = "/notifications";
+        NOTIFICATION_READ = "/notifications/:id/read";
+        NOTIFICATIONS_READ_ALL = "/notifications/read-all";
+        EVENTS = "/events";
+        EVENTS_WS = "/events/ws";
+        DEV_RELOAD = "/dev/reload";
+    }
 }
 
+/// paths of the JSON API, relative to `route_paths::API_V1`
+pub mod api_paths {
+    pub const OPENAPI: &str = "/openapi.json";
+    pub const DOCS: &str = "/docs";
+    pub const USERS_ME: &str = "/users/me";
+    pub const NOTIFICATION_PREFERENCES: &str = "/account/notification-preferences";
+    pub const TOKENS: &str = "/account/tokens";
+    pub const PREFERENCES: &str = "/account/preferences";
+    pub const NOTIFICATIONS: &str = "/notifications";
+    pub const NOTIFICATION_READ: &str = "/notifications/:id/read";
+    pub const NOTIFICATIONS_READ_ALL: &str = "/notifications/read-all";
+}
+
+/// roles from the `roles` table
+pub mod roles {
+    pub const ADMIN: &str = "admin";
+    pub const BASIC: &str = "basic";
+}
@@ -0,0 +1,181 @@
+//! src/csrf.rs
+//! Synchronizer token CSRF protection. Every session gets a random token which
+//! forms send back in a hidden `csrf_token` field (or the `X-CSRF-Token` header
+//! for scripts). `verify_csrf_token` rejects state-changing requests without it.
+use axum::{
+    async_trait,
+    body::{to_bytes, Body},
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::tower_sessions::Session;
+use std::collections::HashMap;
+use crate::constants::route_paths;
+use crate::error::AppError;
+use crate::utils::{constant_time_eq, e500};
+use crate::api_tokens::ApiTokenAuth;
+
+const SESSION_KEY: &str = "csrf_token";
+pub const FORM_FIELD: &str = "csrf_token";
+pub const HEADER: &str = "X-CSRF-Token";
+/// Form bodies bigger than this are rejected before looking for the token
+const MAX_FORM_BYTES: usize = 2 * 1024 * 1024;
+
+/// Paths that authenticate state-changing requests some other way and are
+/// called by other servers and mail clients rather than our own forms.
+const EXEMPT_PATHS: &[&str] = &[
+    route_paths::WEBHOOKS_EMAIL_EVENTS,
+    route_paths::UNSUBSCRIBE,
+    route_paths::CSP_REPORT,
+];
+
+/// The CSRF token of the current session. A token is created the first time
+/// it is extracted, so only extract it for pages, which `Render` already does.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, message)| e500(message))?;
+
+        if let Some(token) = session.get::<String>(SESSION_KEY).await.map_err(e500)? {
+            return Ok(Self(token));
+        }
+
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        session.insert(SESSION_KEY, &token).await.map_err(e500)?;
+        Ok(Self(token))
+    }
+}
+
+impl AsRef<str> for CsrfToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Middleware rejecting POST, PUT, PATCH and DELETE requests whose token does
+/// not match the one stored in the session. Requests authenticated with an API
+/// token are not sent by browsers on their own, so they don't need one.
+pub async fn verify_csrf_token(session: Session, request: Request, next: Next) -> Response {
+    if is_safe_method(request.method())
+        || is_exempt(request.uri().path())
+        || request.extensions().get::<ApiTokenAuth>().is_some()
+    {
+        return next.run(request).await;
+    }
+
+    let expected = match session.get::<String>(SESSION_KEY).await {
+        Ok(expected) => expected,
+        Err(err) => return e500(err).into_response(),
+    };
+
+    let (parts, body) = request.into_parts();
+    let header_token = parts
+        .headers
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let (submitted, body) = match header_token {
+        Some(token) => (Some(token), body),
+        None if is_form(&parts.headers) => {
+            let Ok(bytes) = to_bytes(body, MAX_FORM_BYTES).await else {
+                return AppError::Forbidden.into_response();
+            };
+            let fields: HashMap<String, String> = serde_urlencoded::from_bytes(&bytes).unwrap_or_default();
+            (fields.get(FORM_FIELD).cloned(), Body::from(bytes))
+        },
+        None => (None, body),
+    };
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if constant_time_eq(&expected, &submitted) => {
+            next.run(Request::from_parts(parts, body)).await
+        },
+        _ => {
+            tracing::warn!(path = %parts.uri.path(), method = %parts.method, "Rejected request with a missing or invalid CSRF token");
+            AppError::Forbidden.into_response()
+        },
+    }
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PATHS.iter().any(|exempt| path == *exempt || path.starts_with(&format!("{}/", exempt)))
+}
+
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"))
+}
+
+/// Tera function emitting the hidden form field: `{{ csrf_field(token=csrf_token) }}`
+pub struct CsrfField;
+
+impl tera::Function for CsrfField {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let token = args
+            .get("token")
+            .and_then(|token| token.as_str())
+            .ok_or("csrf_field requires a `token` argument")?;
+        let escaped = tera::escape_html(token);
+        Ok(tera::Value::String(format!(
+            r#"<input type="hidden" name="{}" value="{}" />"#,
+            FORM_FIELD, escaped
+        )))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_exempt, CsrfField};
+    use crate::utils::constant_time_eq;
+    use std::collections::HashMap;
+    use tera::Function;
+
+    #[test]
+    fn tokens_are_compared_exactly() {
+        assert!(constant_time_eq("abc123", "abc123"));
+        assert!(!constant_time_eq("abc123", "abc124"));
+        assert!(!constant_time_eq("abc123", "abc1234"));
+        assert!(!constant_time_eq("", "a"));
+    }
+
+    #[test]
+    fn webhooks_and_unsubscribe_are_exempt() {
+        assert!(is_exempt("/webhooks/email-events"));
+        assert!(is_exempt("/unsubscribe"));
+        assert!(!is_exempt("/unsubscribe-all"));
+        assert!(!is_exempt("/login"));
+    }
+
+    #[test]
+    fn csrf_field_renders_hidden_input() {
+        let mut args = HashMap::new();
+        args.insert("token".to_string(), tera::Value::String("abc\"<".into()));
+        let field = CsrfField.call(&args).unwrap();
+        assert_eq!(
+            field.as_str().unwrap(),
+            r#"<input type="hidden" name="csrf_token" value="abc&quot;&lt;" />"#
+        );
+    }
+}
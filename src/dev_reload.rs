@@ -0,0 +1,403 @@
+//! src/dev_reload.rs
+//! Development only. Watches `templates/`, `scss/` and `public/`, reloads
+//! Tera, recompiles the SCSS and rebuilds the asset manifest in place when
+//! they change, and tells open tabs to reload over `/dev/reload` (SSE) so
+//! edits show up without a restart.
+//!
+//! Files are polled rather than watched with OS notifications, which keeps
+//! it dependency free and works the same on every platform and in containers.
+use axum::{
+    extract::State,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
+    routing::get,
+    Router,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use crate::assets::{self, AssetManifest, SharedAssets};
+use crate::compression;
+use crate::configuration::DevReloadSettings;
+use crate::constants::route_paths;
+use crate::scss::ScssCompiler;
+use crate::template_helpers::SharedTera;
+
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// What changed, sent to open tabs as the SSE event name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Templates,
+    Styles,
+    Assets,
+}
+
+impl Change {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Change::Templates => "templates",
+            Change::Styles => "styles",
+            Change::Assets => "assets",
+        }
+    }
+}
+
+/// Tera function telling `base.html` whether to include the live reload
+/// script: `{% if live_reload() %}`
+pub struct LiveReload(pub bool);
+
+impl tera::Function for LiveReload {
+    fn call(&self, _: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        Ok(tera::Value::Bool(self.0))
+    }
+}
+
+/// The modification time of every file under a directory
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+/// What the watched directories looked like at the last poll
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshots {
+    templates: Snapshot,
+    styles: Snapshot,
+    assets: Snapshot,
+}
+
+#[derive(Clone)]
+pub struct DevReload {
+    tera: SharedTera,
+    assets: SharedAssets,
+    scss_compiler: ScssCompiler,
+    templates_dir: PathBuf,
+    public_dir: PathBuf,
+    precompress: bool,
+    poll_interval: Duration,
+    changes: broadcast::Sender<Change>,
+}
+
+impl DevReload {
+    pub fn new(
+        tera: SharedTera,
+        assets: SharedAssets,
+        scss_compiler: ScssCompiler,
+        settings: &DevReloadSettings,
+    ) -> Self {
+        Self::with_dirs(tera, assets, scss_compiler, settings, TEMPLATES_DIR, assets::PUBLIC_DIR)
+    }
+
+    /// `scss_compiler` brings its own directories
+    pub fn with_dirs(
+        tera: SharedTera,
+        assets: SharedAssets,
+        scss_compiler: ScssCompiler,
+        settings: &DevReloadSettings,
+        templates_dir: impl Into<PathBuf>,
+        public_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            tera,
+            assets,
+            scss_compiler,
+            templates_dir: templates_dir.into(),
+            public_dir: public_dir.into(),
+            precompress: false,
+            poll_interval: Duration::from_millis(settings.poll_interval_ms),
+            changes: broadcast::channel(16).0,
+        }
+    }
+
+    /// Writes `.br` and `.gz` copies of changed assets before fingerprinting them
+    pub fn precompress_assets(mut self, precompress: bool) -> Self {
+        self.precompress = precompress;
+        self
+    }
+
+    /// Receives a `Change` after every successful reload
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+
+    /// Polls for changes until aborted. Walking the directories, reloading
+    /// and compiling block, so each poll runs on the blocking thread pool.
+    pub async fn watch(self) {
+        let dev_reload = Arc::new(self);
+        let mut interval = tokio::time::interval(dev_reload.poll_interval);
+        let mut snapshots = None;
+        loop {
+            interval.tick().await;
+            let polling = Arc::clone(&dev_reload);
+            let polled = tokio::task::spawn_blocking(move || match snapshots {
+                Some(mut snapshots) => {
+                    let changes = polling.poll(&mut snapshots);
+                    (snapshots, changes)
+                },
+                None => (polling.snapshots(), Vec::new()),
+            })
+                .await;
+            match polled {
+                Ok((polled_snapshots, changes)) => {
+                    snapshots = Some(polled_snapshots);
+                    for change in changes {
+                        // Fails only when no tab is listening
+                        let _ = dev_reload.changes.send(change);
+                    }
+                },
+                // The snapshots went with the panicked task and are taken afresh
+                Err(err) => {
+                    tracing::error!(error = %err, "Polling for changes panicked");
+                    snapshots = None;
+                },
+            }
+        }
+    }
+
+    fn snapshots(&self) -> Snapshots {
+        Snapshots {
+            templates: snapshot(&self.templates_dir),
+            styles: snapshot(self.scss_compiler.scss_dir()),
+            assets: snapshot(&self.public_dir),
+        }
+    }
+
+    /// Reloads whatever changed since the snapshots were taken, updating
+    /// them. Returns what was reloaded; failures are logged and the previous
+    /// templates, CSS or manifest stay in use until the next change.
+    fn poll(&self, snapshots: &mut Snapshots) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        let current = snapshot(&self.templates_dir);
+        if current != snapshots.templates {
+            snapshots.templates = current;
+            // A copy is reloaded so a broken template leaves the current ones
+            // in place. It keeps the registered functions and filters.
+            let mut reloaded = self.tera.read().unwrap_or_else(PoisonError::into_inner).clone();
+            match reloaded.full_reload() {
+                Ok(()) => {
+                    *self.tera.write().unwrap_or_else(PoisonError::into_inner) = reloaded;
+                    tracing::info!("Reloaded templates");
+                    changes.push(Change::Templates);
+                },
+                Err(err) => tracing::error!(error = ?err, "Failed to reload templates"),
+            }
+        }
+
+        let current = snapshot(self.scss_compiler.scss_dir());
+        if current != snapshots.styles {
+            snapshots.styles = current;
+            // The error, if any, is shown in place of pages until the next change
+            match self.scss_compiler.compile() {
+                Ok(_) => {
+                    tracing::info!("Recompiled SCSS");
+                    changes.push(Change::Styles);
+                },
+                Err(err) => tracing::error!(error = ?err, "Failed to recompile SCSS"),
+            }
+        }
+
+        // Checked last so the recompiled CSS gets its new fingerprint
+        let current = snapshot(&self.public_dir);
+        if current != snapshots.assets {
+            if self.precompress {
+                if let Err(err) = compression::precompress_dir(&self.public_dir) {
+                    tracing::error!(error = ?err, "Failed to precompress assets");
+                }
+            }
+            // Taken after precompressing so the copies aren't seen as a change
+            snapshots.assets = snapshot(&self.public_dir);
+            match AssetManifest::build(&self.public_dir) {
+                Ok(manifest) => {
+                    *self.assets.write().unwrap_or_else(PoisonError::into_inner) = manifest;
+                    tracing::info!("Rebuilt the asset manifest");
+                    // Recompiled styles are swapped in without a page reload
+                    if !changes.contains(&Change::Styles) {
+                        changes.push(Change::Assets);
+                    }
+                },
+                Err(err) => tracing::error!(error = ?err, "Failed to rebuild the asset manifest"),
+            }
+        }
+
+        changes
+    }
+}
+
+fn snapshot(dir: &Path) -> Snapshot {
+    let mut files = Snapshot::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => dirs.push(path),
+                Ok(metadata) => {
+                    files.insert(path, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+                },
+                Err(_) => {},
+            }
+        }
+    }
+    files
+}
+
+pub fn routes(dev_reload: DevReload) -> Router<()> {
+    Router::new()
+        .route(route_paths::DEV_RELOAD, get(self::get::reload))
+        .with_state(dev_reload)
+}
+
+mod get {
+    use super::*;
+
+    /// Streams a `templates`, `styles` or `assets` event after every reload
+    pub async fn reload(State(dev_reload): State<DevReload>) -> impl IntoResponse {
+        let stream = BroadcastStream::new(dev_reload.subscribe())
+            .filter_map(Result::ok)
+            .map(|change| Ok::<_, Infallible>(Event::default().event(change.name()).data(change.name())));
+        Sse::new(stream).keep_alive(KeepAlive::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snapshot, Change, DevReload};
+    use crate::assets::AssetManifest;
+    use crate::configuration::{DevReloadSettings, Environment, ScssSettings};
+    use crate::scss::ScssCompiler;
+    use crate::test_support::TempDir;
+    use std::fs;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    fn dirs() -> TempDir {
+        let dirs = TempDir::new("dev-reload");
+        for dir in ["templates/partials", "scss", "public"] {
+            fs::create_dir_all(dirs.join(dir)).unwrap();
+        }
+        dirs
+    }
+
+    fn dev_reload(dirs: &TempDir) -> DevReload {
+        let glob = format!("{}/templates/**/*html", dirs.path().display());
+        let tera = Arc::new(RwLock::new(tera::Tera::new(&glob).unwrap()));
+        let assets = Arc::new(RwLock::new(AssetManifest::build(dirs.join("public")).unwrap()));
+        let scss_compiler = ScssCompiler::new(&ScssSettings::default(), Environment::Local)
+            .with_dirs(dirs.join("scss"), dirs.join("public/css"));
+        DevReload::with_dirs(
+            tera,
+            assets,
+            scss_compiler,
+            &DevReloadSettings::default(),
+            dirs.join("templates"),
+            dirs.join("public"),
+        )
+    }
+
+    fn render(dev_reload: &DevReload) -> String {
+        dev_reload.tera.read().unwrap().render("page.html", &tera::Context::new()).unwrap()
+    }
+
+    #[test]
+    fn snapshots_include_nested_files() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "page");
+        dirs.write("templates/partials/_part.html", "part");
+
+        assert_eq!(snapshot(&dirs.join("templates")).len(), 2);
+    }
+
+    #[test]
+    fn changed_templates_are_reloaded() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "before");
+        let dev_reload = dev_reload(&dirs);
+        let mut snapshots = dev_reload.snapshots();
+
+        assert!(dev_reload.poll(&mut snapshots).is_empty());
+        dirs.write("templates/page.html", "after");
+        assert_eq!(dev_reload.poll(&mut snapshots), [Change::Templates]);
+        assert_eq!(render(&dev_reload), "after");
+    }
+
+    #[test]
+    fn broken_templates_keep_the_previous_ones() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "before");
+        let dev_reload = dev_reload(&dirs);
+        let mut snapshots = dev_reload.snapshots();
+
+        dirs.write("templates/page.html", "{% if %}");
+        assert!(dev_reload.poll(&mut snapshots).is_empty());
+        assert_eq!(render(&dev_reload), "before");
+    }
+
+    #[test]
+    fn changed_scss_is_recompiled() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "page");
+        let dev_reload = dev_reload(&dirs);
+        let mut snapshots = dev_reload.snapshots();
+
+        dirs.write("scss/main.scss", "$color: red; body { color: $color; }");
+        assert_eq!(dev_reload.poll(&mut snapshots), [Change::Styles]);
+        let css = fs::read_to_string(dirs.join("public/css/main.css")).unwrap();
+        assert!(css.contains("color: red"));
+        assert!(dev_reload.assets.read().unwrap().url("css/main.css").is_some());
+    }
+
+    #[test]
+    fn changed_assets_are_fingerprinted_again() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "page");
+        dirs.write("public/app.js", "before");
+        let dev_reload = dev_reload(&dirs);
+        let mut snapshots = dev_reload.snapshots();
+        let before = dev_reload.assets.read().unwrap().url("app.js").unwrap();
+
+        dirs.write("public/app.js", "after");
+        assert_eq!(dev_reload.poll(&mut snapshots), [Change::Assets]);
+        let after = dev_reload.assets.read().unwrap().url("app.js").unwrap();
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn changed_assets_are_precompressed() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "page");
+        let dev_reload = dev_reload(&dirs).precompress_assets(true);
+        let mut snapshots = dev_reload.snapshots();
+
+        dirs.write("public/app.js", "alert(1)");
+        assert_eq!(dev_reload.poll(&mut snapshots), [Change::Assets]);
+        assert!(dirs.join("public/app.js.br").exists());
+        assert!(dirs.join("public/app.js.gz").exists());
+        assert!(dev_reload.poll(&mut snapshots).is_empty());
+    }
+
+    #[tokio::test]
+    async fn watching_sends_changes() {
+        let dirs = dirs();
+        dirs.write("templates/page.html", "before");
+        let mut dev_reload = dev_reload(&dirs);
+        dev_reload.poll_interval = Duration::from_millis(10);
+        let mut changes = dev_reload.subscribe();
+        let watching = tokio::spawn(dev_reload.clone().watch());
+
+        // Lets the first poll take the snapshots
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        dirs.write("templates/page.html", "after");
+        let change = tokio::time::timeout(Duration::from_secs(5), changes.recv()).await;
+        watching.abort();
+        assert_eq!(change.unwrap().unwrap(), Change::Templates);
+        assert_eq!(render(&dev_reload), "after");
+    }
+}
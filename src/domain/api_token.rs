@@ -0,0 +1,105 @@
+use sha2::{Digest, Sha256};
+
+const TOKEN_PREFIX: &str = "ast";
+const PREFIX_BYTES: usize = 6;
+const SECRET_BYTES: usize = 32;
+
+/// A personal API token in plain text: `ast_<prefix>_<secret>`. The prefix
+/// is stored as is so the token can be looked up; only the SHA-256 hash of
+/// the whole token is stored, so it can only be shown once.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PlainApiToken(String);
+
+impl PlainApiToken {
+    pub fn generate() -> Self {
+        Self(format!(
+            "{}_{}_{}",
+            TOKEN_PREFIX,
+            hex::encode(rand::random::<[u8; PREFIX_BYTES]>()),
+            hex::encode(rand::random::<[u8; SECRET_BYTES]>()),
+        ))
+    }
+
+    pub fn parse(s: &str) -> Result<PlainApiToken, String> {
+        let invalid = || "Not a valid API token.".to_string();
+        let mut parts = s.split('_');
+        let (Some(TOKEN_PREFIX), Some(prefix), Some(secret), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        let is_hex = |part: &str, bytes: usize| {
+            part.len() == bytes * 2 && part.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+        };
+        if !is_hex(prefix, PREFIX_BYTES) || !is_hex(secret, SECRET_BYTES) {
+            return Err(invalid());
+        }
+
+        Ok(Self(s.to_string()))
+    }
+
+    /// The public part used to find the token
+    pub fn prefix(&self) -> &str {
+        self.0.split('_').nth(1).unwrap_or_default()
+    }
+
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.0.as_bytes()))
+    }
+
+    /// The token itself, to show to its owner once
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for PlainApiToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PlainApiToken({}_{}_[redacted])", TOKEN_PREFIX, self.prefix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlainApiToken;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn generated_tokens_parse() {
+        let token = PlainApiToken::generate();
+        assert_ok!(PlainApiToken::parse(token.expose()));
+        assert_eq!(token.prefix().len(), 12);
+        assert!(token.expose().starts_with(&format!("ast_{}_", token.prefix())));
+    }
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        assert_ne!(PlainApiToken::generate(), PlainApiToken::generate());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let token = PlainApiToken::generate();
+        assert_err!(PlainApiToken::parse(""));
+        assert_err!(PlainApiToken::parse("ast_abc_def"));
+        assert_err!(PlainApiToken::parse(&token.expose().replacen("ast", "xyz", 1)));
+        assert_err!(PlainApiToken::parse(&format!("{}_extra", token.expose())));
+        assert_err!(PlainApiToken::parse(&token.expose().to_uppercase()));
+    }
+
+    #[test]
+    fn debug_output_hides_the_secret() {
+        let token = PlainApiToken::generate();
+        let debug = format!("{:?}", token);
+        assert!(debug.contains(token.prefix()));
+        assert!(!debug.contains(token.expose()));
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        let token = PlainApiToken::generate();
+        assert_eq!(token.hash(), PlainApiToken::parse(token.expose()).unwrap().hash());
+        assert_eq!(token.hash().len(), 64);
+    }
+}
@@ -0,0 +1,67 @@
+use axum::http::Method;
+
+/// What an API token is allowed to do. Tokens without `Write` can only make
+/// safe (GET, HEAD, OPTIONS) requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiTokenScope {
+    Read,
+    Write,
+}
+
+impl ApiTokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::Read => "read",
+            ApiTokenScope::Write => "write",
+        }
+    }
+
+    /// The scope a request with `method` needs
+    pub fn required_for(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => ApiTokenScope::Read,
+            _ => ApiTokenScope::Write,
+        }
+    }
+}
+
+impl TryFrom<&str> for ApiTokenScope {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            other => Err(format!("{} is not a valid token scope.", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiTokenScope;
+    use axum::http::Method;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn scopes_round_trip_through_their_string_form() {
+        for scope in [ApiTokenScope::Read, ApiTokenScope::Write] {
+            assert_ok_eq!(ApiTokenScope::try_from(scope.as_str()), scope);
+        }
+    }
+
+    #[test]
+    fn unknown_scopes_are_rejected() {
+        assert_err!(ApiTokenScope::try_from("admin"));
+        assert_err!(ApiTokenScope::try_from("Read"));
+    }
+
+    #[test]
+    fn mutating_methods_need_write() {
+        assert_eq!(ApiTokenScope::required_for(&Method::GET), ApiTokenScope::Read);
+        assert_eq!(ApiTokenScope::required_for(&Method::HEAD), ApiTokenScope::Read);
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert_eq!(ApiTokenScope::required_for(&method), ApiTokenScope::Write);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+/// A security relevant event recorded in `audit_events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    Registered,
+    PasswordChanged,
+    EmailChanged,
+    RoleGranted,
+    RoleRevoked,
+    DataExportRequested,
+    DeletionRequested,
+    DeletionCancelled,
+    AccountDeleted,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 12] = [
+        AuditAction::Login,
+        AuditAction::LoginFailed,
+        AuditAction::Logout,
+        AuditAction::Registered,
+        AuditAction::PasswordChanged,
+        AuditAction::EmailChanged,
+        AuditAction::RoleGranted,
+        AuditAction::RoleRevoked,
+        AuditAction::DataExportRequested,
+        AuditAction::DeletionRequested,
+        AuditAction::DeletionCancelled,
+        AuditAction::AccountDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::Logout => "logout",
+            AuditAction::Registered => "registered",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::EmailChanged => "email_changed",
+            AuditAction::RoleGranted => "role_granted",
+            AuditAction::RoleRevoked => "role_revoked",
+            AuditAction::DataExportRequested => "data_export_requested",
+            AuditAction::DeletionRequested => "deletion_requested",
+            AuditAction::DeletionCancelled => "deletion_cancelled",
+            AuditAction::AccountDeleted => "account_deleted",
+        }
+    }
+}
+
+impl TryFrom<&str> for AuditAction {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid audit action.", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditAction;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn actions_round_trip_through_their_string_form() {
+        for action in AuditAction::ALL {
+            assert_ok_eq!(AuditAction::try_from(action.as_str()), action);
+        }
+    }
+
+    #[test]
+    fn unknown_action_is_rejected() {
+        assert_err!(AuditAction::try_from("deleted_everything"));
+    }
+}
@@ -0,0 +1,72 @@
+/// The kind of email being sent. The category decides whether a user is able to
+/// opt out of the email and whether an unsubscribe link is attached to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailCategory {
+    /// Account emails the user asked for (verification, password resets, etc.)
+    Transactional,
+    /// Security alerts can never be turned off
+    SecurityAlerts,
+    ProductUpdates,
+    Digests,
+}
+
+impl EmailCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailCategory::Transactional => "transactional",
+            EmailCategory::SecurityAlerts => "security_alerts",
+            EmailCategory::ProductUpdates => "product_updates",
+            EmailCategory::Digests => "digests",
+        }
+    }
+
+    /// Returns true for the categories a user is allowed to unsubscribe from
+    pub fn is_optional(&self) -> bool {
+        matches!(self, EmailCategory::ProductUpdates | EmailCategory::Digests)
+    }
+}
+
+impl TryFrom<&str> for EmailCategory {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "transactional" => Ok(Self::Transactional),
+            "security_alerts" => Ok(Self::SecurityAlerts),
+            "product_updates" => Ok(Self::ProductUpdates),
+            "digests" => Ok(Self::Digests),
+            other => Err(format!("{} is not a valid email category.", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailCategory;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn categories_round_trip_through_their_string_form() {
+        for category in [
+            EmailCategory::Transactional,
+            EmailCategory::SecurityAlerts,
+            EmailCategory::ProductUpdates,
+            EmailCategory::Digests,
+        ] {
+            assert_ok_eq!(EmailCategory::try_from(category.as_str()), category);
+        }
+    }
+
+    #[test]
+    fn unknown_category_is_rejected() {
+        assert_err!(EmailCategory::try_from("marketing"));
+    }
+
+    #[test]
+    fn security_alerts_are_not_optional() {
+        assert!(!EmailCategory::SecurityAlerts.is_optional());
+        assert!(!EmailCategory::Transactional.is_optional());
+        assert!(EmailCategory::ProductUpdates.is_optional());
+        assert!(EmailCategory::Digests.is_optional());
+    }
+}
@@ -1,7 +1,21 @@
+mod api_token;
+mod api_token_scope;
+mod audit_action;
+mod email_category;
 mod new_user;
+mod notification_kind;
+mod safe_redirect;
+mod unsubscribe_token;
 mod user_email;
 mod user_password;
 
+pub use api_token::PlainApiToken;
+pub use api_token_scope::ApiTokenScope;
+pub use audit_action::AuditAction;
+pub use email_category::EmailCategory;
 pub use new_user::NewUser;
+pub use notification_kind::NotificationKind;
+pub use safe_redirect::SafeRedirect;
+pub use unsubscribe_token::UnsubscribeToken;
 pub use user_email::UserEmail;
 pub use user_password::UserPassword;
@@ -0,0 +1,76 @@
+use super::EmailCategory;
+
+/// What an in-app notification is about. The kind decides whether the
+/// notification is also emailed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// Only shown in the app
+    Info,
+    /// Always emailed as well
+    SecurityAlert,
+    /// Emailed unless the user turned off product updates
+    ProductUpdate,
+    /// Emailed unless the user turned off digests
+    Digest,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Info => "info",
+            NotificationKind::SecurityAlert => "security_alert",
+            NotificationKind::ProductUpdate => "product_update",
+            NotificationKind::Digest => "digest",
+        }
+    }
+
+    /// The email category of the copy sent by email, if any
+    pub fn email_category(&self) -> Option<EmailCategory> {
+        match self {
+            NotificationKind::Info => None,
+            NotificationKind::SecurityAlert => Some(EmailCategory::SecurityAlerts),
+            NotificationKind::ProductUpdate => Some(EmailCategory::ProductUpdates),
+            NotificationKind::Digest => Some(EmailCategory::Digests),
+        }
+    }
+}
+
+impl TryFrom<&str> for NotificationKind {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "info" => Ok(Self::Info),
+            "security_alert" => Ok(Self::SecurityAlert),
+            "product_update" => Ok(Self::ProductUpdate),
+            "digest" => Ok(Self::Digest),
+            other => Err(format!("{} is not a valid notification kind.", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NotificationKind;
+    use crate::domain::EmailCategory;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn kinds_round_trip_through_their_string_form() {
+        for kind in [
+            NotificationKind::Info,
+            NotificationKind::SecurityAlert,
+            NotificationKind::ProductUpdate,
+            NotificationKind::Digest,
+        ] {
+            assert_ok_eq!(NotificationKind::try_from(kind.as_str()), kind);
+        }
+        assert_err!(NotificationKind::try_from("marketing"));
+    }
+
+    #[test]
+    fn only_some_kinds_are_emailed() {
+        assert_eq!(NotificationKind::Info.email_category(), None);
+        assert_eq!(NotificationKind::SecurityAlert.email_category(), Some(EmailCategory::SecurityAlerts));
+    }
+}
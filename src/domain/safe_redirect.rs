@@ -0,0 +1,103 @@
+use axum::http::Uri;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A redirect target that is guaranteed to stay on this site. Only relative
+/// paths are allowed, so `next=https://evil.example` can't be used to bounce
+/// users to another origin after they log in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct SafeRedirect(String);
+
+impl SafeRedirect {
+    pub fn parse(s: &str) -> Result<SafeRedirect, String> {
+        let invalid = || format!("{} is not a valid redirect.", s);
+
+        // Must be an absolute path on this origin. `//host` and `/\host` are
+        // treated by browsers as protocol relative URLs to another host.
+        if !s.starts_with('/') || s.starts_with("//") {
+            return Err(invalid());
+        }
+        if s.contains('\\') || s.chars().any(|c| c.is_control() || c.is_whitespace()) {
+            return Err(invalid());
+        }
+
+        let uri: Uri = s.parse().map_err(|_| invalid())?;
+        if uri.scheme().is_some() || uri.authority().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self(s.to_string()))
+    }
+
+    /// Deserializes an optional redirect, dropping values that are not safe
+    /// instead of failing the whole request.
+    pub fn deserialize_lenient<'de, D>(deserializer: D) -> Result<Option<SafeRedirect>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: Option<String> = Option::deserialize(deserializer)?;
+        Ok(value.and_then(|value| SafeRedirect::parse(&value).ok()))
+    }
+
+    /// Returns `path?next=<url encoded redirect>`
+    pub fn append_to(&self, path: &str) -> String {
+        let query = serde_urlencoded::to_string([("next", self.as_ref())]).unwrap_or_default();
+        format!("{}?{}", path, query)
+    }
+}
+
+impl AsRef<str> for SafeRedirect {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SafeRedirect;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn relative_paths_are_accepted() {
+        assert_ok!(SafeRedirect::parse("/"));
+        assert_ok!(SafeRedirect::parse("/protected"));
+        assert_ok!(SafeRedirect::parse("/account/notifications?tab=email&page=2"));
+        assert_ok!(SafeRedirect::parse("/search?q=a%20b#results"));
+    }
+
+    #[test]
+    fn absolute_urls_are_rejected() {
+        assert_err!(SafeRedirect::parse("https://evil.example"));
+        assert_err!(SafeRedirect::parse("http://evil.example/protected"));
+        assert_err!(SafeRedirect::parse("javascript:alert(1)"));
+        assert_err!(SafeRedirect::parse("evil.example"));
+        assert_err!(SafeRedirect::parse(""));
+    }
+
+    #[test]
+    fn protocol_relative_urls_are_rejected() {
+        assert_err!(SafeRedirect::parse("//evil.example"));
+        assert_err!(SafeRedirect::parse("///evil.example"));
+    }
+
+    #[test]
+    fn backslashes_are_rejected() {
+        assert_err!(SafeRedirect::parse("/\\evil.example"));
+        assert_err!(SafeRedirect::parse("\\\\evil.example"));
+        assert_err!(SafeRedirect::parse("/path\\to"));
+    }
+
+    #[test]
+    fn control_characters_are_rejected() {
+        assert_err!(SafeRedirect::parse("/\t/evil.example"));
+        assert_err!(SafeRedirect::parse("/\n/evil.example"));
+        assert_err!(SafeRedirect::parse("/protected\r\nSet-Cookie: a=b"));
+        assert_err!(SafeRedirect::parse("/ /evil.example"));
+    }
+
+    #[test]
+    fn next_is_url_encoded_when_appended() {
+        let redirect = SafeRedirect::parse("/account?tab=email&page=2").unwrap();
+        assert_eq!(redirect.append_to("/login"), "/login?next=%2Faccount%3Ftab%3Demail%26page%3D2");
+    }
+}
@@ -0,0 +1,109 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+use crate::domain::EmailCategory;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A signed token that lets a user unsubscribe from a single email category
+/// without logging in. The token has the shape `<user_id>.<category>.<signature>`
+/// where the signature is a hex encoded HMAC-SHA256 of the first two parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsubscribeToken {
+    pub user_id: Uuid,
+    pub category: EmailCategory,
+}
+
+impl UnsubscribeToken {
+    pub fn new(user_id: Uuid, category: EmailCategory) -> Self {
+        Self { user_id, category }
+    }
+
+    /// Returns the token in its signed string form, ready to be put in a URL
+    pub fn sign(&self, hmac_secret: &Secret<String>) -> String {
+        let payload = self.payload();
+        let signature = hex::encode(mac(hmac_secret, &payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Parses a signed token, rejecting anything that was not signed with `hmac_secret`
+    pub fn parse(token: &str, hmac_secret: &Secret<String>) -> Result<UnsubscribeToken, String> {
+        let invalid = || "The unsubscribe link is invalid.".to_string();
+
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        mac(hmac_secret, payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let (user_id, category) = payload.split_once('.').ok_or_else(invalid)?;
+        let user_id = Uuid::parse_str(user_id).map_err(|_| invalid())?;
+        let category = EmailCategory::try_from(category).map_err(|_| invalid())?;
+        if !category.is_optional() {
+            return Err(invalid());
+        }
+
+        Ok(Self { user_id, category })
+    }
+
+    fn payload(&self) -> String {
+        format!("{}.{}", self.user_id, self.category.as_str())
+    }
+}
+
+fn mac(hmac_secret: &Secret<String>, payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(b"unsubscribe:");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsubscribeToken;
+    use crate::domain::EmailCategory;
+    use claims::{assert_err, assert_ok_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-very-secret-key".to_string())
+    }
+
+    #[test]
+    fn signed_token_is_parsed_successfully() {
+        let token = UnsubscribeToken::new(Uuid::new_v4(), EmailCategory::Digests);
+        let signed = token.sign(&secret());
+        assert_ok_eq!(UnsubscribeToken::parse(&signed, &secret()), token);
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let token = UnsubscribeToken::new(Uuid::new_v4(), EmailCategory::Digests);
+        let signed = token.sign(&Secret::new("another-key".to_string()));
+        assert_err!(UnsubscribeToken::parse(&signed, &secret()));
+    }
+
+    #[test]
+    fn tampered_category_is_rejected() {
+        let token = UnsubscribeToken::new(Uuid::new_v4(), EmailCategory::Digests);
+        let signed = token.sign(&secret()).replace("digests", "product_updates");
+        assert_err!(UnsubscribeToken::parse(&signed, &secret()));
+    }
+
+    #[test]
+    fn security_alerts_cannot_be_unsubscribed_from() {
+        let token = UnsubscribeToken::new(Uuid::new_v4(), EmailCategory::SecurityAlerts);
+        let signed = token.sign(&secret());
+        assert_err!(UnsubscribeToken::parse(&signed, &secret()));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert_err!(UnsubscribeToken::parse("", &secret()));
+        assert_err!(UnsubscribeToken::parse("not-a-token", &secret()));
+        assert_err!(UnsubscribeToken::parse("a.b.c", &secret()));
+    }
+}
@@ -1,4 +1,4 @@
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Debug, Validate)]
 pub struct UserEmail {
@@ -7,11 +7,12 @@ pub struct UserEmail {
 }
 
 impl UserEmail {
-    pub fn parse(s: String) -> Result<UserEmail, String> {
+    /// The error's code is the message in `locales/` explaining it
+    pub fn parse(s: String) -> Result<UserEmail, ValidationError> {
         let email = UserEmail { email: s };
         match email.validate() {
             Ok(_) => Ok(email),
-            Err(_) => Err(format!("{} is not a valid subscriber email.", email.email)),
+            Err(_) => Err(ValidationError::new("email-invalid")),
         }
     }
 }
@@ -2,6 +2,8 @@ use validator::{Validate, ValidationError};
 use secrecy::{Secret, ExposeSecret};
 use serde::{Serialize, Deserialize, Serializer};
 
+const MIN_LENGTH: usize = 8;
+
 // Wrapper type around Secret<String>
 #[derive(Debug, Deserialize)]
 pub struct SecretString(Secret<String>);
@@ -28,23 +30,26 @@ impl Serialize for SecretString {
     }
 }
 
-// Function to validate the password
+// Function to validate the password. Each rule's code is the message in
+// `locales/` shown next to the password field.
 fn validate_password(secret_password: &SecretString) -> Result<(), ValidationError> {
     let password = secret_password.as_ref().expose_secret();
-    if password.len() < 8 {
-        return Err(ValidationError::new("Password must be at least 8 characters long"));
+    if password.len() < MIN_LENGTH {
+        let mut error = ValidationError::new("password-too-short");
+        error.add_param("min".into(), &MIN_LENGTH);
+        return Err(error);
     }
     if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        return Err(ValidationError::new("Password must contain at least one lowercase letter"));
+        return Err(ValidationError::new("password-no-lowercase"));
     }
     if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        return Err(ValidationError::new("Password must contain at least one uppercase letter"));
+        return Err(ValidationError::new("password-no-uppercase"));
     }
     if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err(ValidationError::new("Password must contain at least one digit"));
+        return Err(ValidationError::new("password-no-digit"));
     }
     if !password.chars().any(|c| !c.is_alphanumeric()) {
-        return Err(ValidationError::new("Password must contain at least one special character"));
+        return Err(ValidationError::new("password-no-special-character"));
     }
     Ok(())
 }
@@ -56,14 +61,24 @@ pub struct UserPassword {
 }
 
 impl UserPassword {
-    pub fn parse(s: Secret<String>) -> Result<UserPassword, String> {
+    /// The error is the first rule the password breaks
+    pub fn parse(s: Secret<String>) -> Result<UserPassword, ValidationError> {
         let secret_password = SecretString::from(s);
         let user_password = UserPassword {
             password: secret_password,
         };
         match user_password.validate() {
             Ok(_) => Ok(user_password),
-            Err(_) => Err("Password is not a valid format.".to_string()),
+            Err(errors) => {
+                let error = errors
+                    .field_errors()
+                    .into_values()
+                    .flat_map(|errors| errors.iter())
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| ValidationError::new("password-invalid"));
+                Err(error)
+            },
         }
     }
 }
@@ -122,6 +137,15 @@ mod tests {
         assert_err!(UserPassword::parse(Secret::new(password)));
     }
 
+    #[test]
+    fn the_broken_rule_is_named() {
+        let error = UserPassword::parse(Secret::new("password1!".to_string())).unwrap_err();
+        assert_eq!(error.code, "password-no-uppercase");
+        let error = UserPassword::parse(Secret::new("Pa1!".to_string())).unwrap_err();
+        assert_eq!(error.code, "password-too-short");
+        assert_eq!(error.params["min"], 8);
+    }
+
     #[derive(Debug, Clone)]
     struct ValidPasswordFixture(pub String);
 
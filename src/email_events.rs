@@ -0,0 +1,250 @@
+//! src/email_events.rs
+//! Delivery events reported back to us about emails we sent. Events either come
+//! from the email provider's webhook as JSON, or as raw bounce messages (RFC 3464
+//! delivery status notifications and RFC 5965 abuse reports) which is handy for
+//! feeding in mbox files captured during local testing.
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailEventKind {
+    HardBounce,
+    SoftBounce,
+    Complaint,
+}
+
+impl EmailEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailEventKind::HardBounce => "hard_bounce",
+            EmailEventKind::SoftBounce => "soft_bounce",
+            EmailEventKind::Complaint => "complaint",
+        }
+    }
+
+    /// Hard bounces and complaints mean we must stop emailing the address
+    pub fn suppresses(&self) -> bool {
+        matches!(self, EmailEventKind::HardBounce | EmailEventKind::Complaint)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EmailEvent {
+    #[serde(rename = "type")]
+    pub kind: EmailEventKind,
+    pub email: String,
+    pub detail: Option<String>,
+}
+
+/// JSON body accepted by the email events webhook
+#[derive(Debug, Deserialize)]
+pub struct EmailEventBatch {
+    pub events: Vec<EmailEvent>,
+}
+
+/// Splits an mbox file into its messages and parses every one of them.
+/// A single message without the mbox `From ` separator is accepted as well.
+pub fn parse_mbox(mbox: &str) -> Vec<EmailEvent> {
+    let mut messages: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut previous_line_blank = true;
+
+    for line in mbox.lines() {
+        if line.starts_with("From ") && previous_line_blank {
+            if !current.trim().is_empty() {
+                messages.push(std::mem::take(&mut current));
+            }
+            current.clear();
+        } else {
+            current.push_str(line);
+            current.push('\n');
+        }
+        previous_line_blank = line.trim().is_empty();
+    }
+    if !current.trim().is_empty() {
+        messages.push(current);
+    }
+
+    messages.iter().flat_map(|message| parse_message(message)).collect()
+}
+
+/// Parses a single bounce or abuse report message
+pub fn parse_message(message: &str) -> Vec<EmailEvent> {
+    let blocks = field_blocks(message);
+
+    let is_complaint = blocks
+        .iter()
+        .any(|block| field(block, "feedback-type").is_some());
+    if is_complaint {
+        let recipient = blocks.iter().find_map(|block| {
+            field(block, "original-rcpt-to").or_else(|| field(block, "removal-recipient"))
+        });
+        let feedback_type = blocks.iter().find_map(|block| field(block, "feedback-type"));
+        return recipient
+            .map(|email| EmailEvent {
+                kind: EmailEventKind::Complaint,
+                email: strip_address_type(&email),
+                detail: feedback_type,
+            })
+            .into_iter()
+            .collect();
+    }
+
+    blocks
+        .iter()
+        .filter_map(|block| {
+            let recipient = field(block, "final-recipient")?;
+            let action = field(block, "action")?.to_lowercase();
+            let status = field(block, "status").unwrap_or_default();
+            let kind = match action.as_str() {
+                "failed" if status.starts_with('5') => EmailEventKind::HardBounce,
+                "failed" | "delayed" => EmailEventKind::SoftBounce,
+                _ => return None,
+            };
+            let detail = field(block, "diagnostic-code").or(Some(status));
+            Some(EmailEvent {
+                kind,
+                email: strip_address_type(&recipient),
+                detail,
+            })
+        })
+        .collect()
+}
+
+/// Groups the `Name: value` lines of a message into blank line separated blocks,
+/// unfolding continuation lines along the way.
+fn field_blocks(message: &str) -> Vec<Vec<(String, String)>> {
+    let mut blocks = Vec::new();
+    let mut block: Vec<(String, String)> = Vec::new();
+
+    for line in message.lines() {
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = block.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            block.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+fn field(block: &[(String, String)], name: &str) -> Option<String> {
+    block
+        .iter()
+        .find(|(field_name, _)| field_name == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// DSN recipients are written as `rfc822; user@example.com`
+fn strip_address_type(recipient: &str) -> String {
+    let address = match recipient.split_once(';') {
+        Some((_, address)) => address,
+        None => recipient,
+    };
+    address
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mbox, parse_message, EmailEventKind};
+
+    const HARD_BOUNCE: &str = "From: Mail Delivery System <MAILER-DAEMON@example.com>
+To: admin@example.com
+Subject: Undelivered Mail Returned to Sender
+Content-Type: multipart/report; report-type=delivery-status; boundary=\"XYZ\"
+
+--XYZ
+Content-Type: message/delivery-status
+
+Reporting-MTA: dns; mx.example.com
+
+Final-Recipient: rfc822; Gone@Example.com
+Original-Recipient: rfc822;gone@example.com
+Action: failed
+Status: 5.1.1
+Diagnostic-Code: smtp; 550 5.1.1 <gone@example.com>:
+    Recipient address rejected: User unknown
+
+--XYZ--
+";
+
+    const SOFT_BOUNCE: &str = "Subject: Delayed Mail
+
+Reporting-MTA: dns; mx.example.com
+
+Final-Recipient: rfc822; full@example.com
+Action: delayed
+Status: 4.2.2
+";
+
+    const COMPLAINT: &str = "Subject: FW: Spam report
+Content-Type: multipart/report; report-type=feedback-report; boundary=\"ABC\"
+
+--ABC
+Content-Type: message/feedback-report
+
+Feedback-Type: abuse
+User-Agent: SomeGenerator/1.0
+Version: 1
+Original-Rcpt-To: <angry@example.com>
+
+--ABC--
+";
+
+    #[test]
+    fn hard_bounce_is_parsed() {
+        let events = parse_message(HARD_BOUNCE);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EmailEventKind::HardBounce);
+        assert_eq!(events[0].email, "gone@example.com");
+        assert!(events[0].detail.as_deref().unwrap().contains("User unknown"));
+    }
+
+    #[test]
+    fn delayed_delivery_is_a_soft_bounce() {
+        let events = parse_message(SOFT_BOUNCE);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EmailEventKind::SoftBounce);
+        assert!(!events[0].kind.suppresses());
+    }
+
+    #[test]
+    fn abuse_report_is_a_complaint() {
+        let events = parse_message(COMPLAINT);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EmailEventKind::Complaint);
+        assert_eq!(events[0].email, "angry@example.com");
+    }
+
+    #[test]
+    fn mbox_with_several_messages_is_split() {
+        let mbox = format!(
+            "From MAILER-DAEMON Mon Jul 29 10:00:00 2024\n{}\nFrom feedback@example.com Mon Jul 29 11:00:00 2024\n{}",
+            HARD_BOUNCE, COMPLAINT
+        );
+        let events = parse_mbox(&mbox);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, EmailEventKind::HardBounce);
+        assert_eq!(events[1].kind, EmailEventKind::Complaint);
+    }
+
+    #[test]
+    fn regular_email_produces_no_events() {
+        let events = parse_mbox("From: someone@example.com\nSubject: hi\n\nJust saying hello\n");
+        assert!(events.is_empty());
+    }
+}
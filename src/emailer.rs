@@ -1,33 +1,177 @@
 // use secrecy::ExposeSecret;
 // use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
-use lettre::message::header::ContentType;
+use lettre::message::header::{ContentType, Header, HeaderName, HeaderValue};
 use std::collections::HashMap;
-use tera::{Context, Tera};
+use tera::Context;
+use uuid::Uuid;
 use crate::configuration::EmailSettings;
+use crate::constants::{email_templates, route_paths};
+use crate::domain::{EmailCategory, UnsubscribeToken};
+use crate::i18n::Locale;
+use crate::notification_preferences::NotificationPreferences;
+use crate::startup::AppState;
+use crate::suppressions;
+use crate::template_helpers;
 
+/// RFC 2369 `List-Unsubscribe` header
+#[derive(Debug, Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(s.trim_start_matches('<').trim_end_matches('>').to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// RFC 8058 `List-Unsubscribe-Post` header. Tells mail clients they can unsubscribe
+/// with a single POST to the `List-Unsubscribe` URL.
+#[derive(Debug, Clone)]
+struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+/// Parameters for an email sent to a registered user
+pub struct UserEmailParams<'a> {
+    pub user_id: Uuid,
+    pub to: &'a str,
+    pub category: EmailCategory,
+    pub subject: &'a str,
+    pub template_name: &'a str,
+    pub context: &'a HashMap<&'a str, &'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendOutcome {
+    Sent,
+    /// The user turned off this category of email
+    OptedOut,
+    /// The address is on the suppression list
+    Suppressed,
+}
+
+/// Sends an email to any address that is not on the suppression list. The
+/// context needs a `locale` for templates that are translated.
 pub async fn send_email(
+    state: &AppState,
     to: &str,
     subject: &str,
     template_name: &str,
     context: &HashMap<&str, &str>,
-    tera: &Tera,
-    email_settings: &EmailSettings,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<SendOutcome, Box<dyn std::error::Error>> {
+    if suppressions::is_suppressed(&state.db, to).await? {
+        return Ok(SendOutcome::Suppressed);
+    }
+
+    let tera_context = build_context(context);
+    let email_body = template_helpers::render(&state.tera, template_name, &tera_context)?;
+    let email = build_message(to, subject, email_body, None, &state.email_settings)?;
+
+    deliver(&email, &state.email_settings)?;
+    Ok(SendOutcome::Sent)
+}
+
+/// Sends an email to a registered user, respecting their notification preferences
+/// and the suppression list. Emails in an optional category get an unsubscribe link
+/// appended to the body along with the `List-Unsubscribe` headers. Templates
+/// get the user's `locale`.
+pub async fn send_user_email(
+    state: &AppState,
+    params: UserEmailParams<'_>,
+) -> Result<SendOutcome, Box<dyn std::error::Error>> {
+    if suppressions::is_suppressed(&state.db, params.to).await? {
+        return Ok(SendOutcome::Suppressed);
+    }
+
+    let unsubscribe_url = if params.category.is_optional() {
+        let preferences = NotificationPreferences::fetch(&state.db, params.user_id).await?;
+        if !preferences.allows(params.category) {
+            return Ok(SendOutcome::OptedOut);
+        }
+        Some(unsubscribe_url(state, params.user_id, params.category))
+    } else {
+        None
+    };
+
+    let mut tera_context = build_context(params.context);
+    tera_context.insert("locale", &user_locale(state, params.user_id).await?);
+    let mut email_body = template_helpers::render(&state.tera, params.template_name, &tera_context)?;
+    if let Some(ref url) = unsubscribe_url {
+        tera_context.insert("unsubscribe_url", url);
+        email_body.push_str(&template_helpers::render(&state.tera, email_templates::UNSUBSCRIBE_FOOTER, &tera_context)?);
+    }
+    let email = build_message(params.to, params.subject, email_body, unsubscribe_url, &state.email_settings)?;
+
+    deliver(&email, &state.email_settings)?;
+    Ok(SendOutcome::Sent)
+}
+
+pub fn unsubscribe_url(state: &AppState, user_id: Uuid, category: EmailCategory) -> String {
+    let token = UnsubscribeToken::new(user_id, category).sign(&state.hmac_secret);
+    format!("{}{}?token={}", state.base_url, route_paths::UNSUBSCRIBE, token)
+}
+
+/// The language the user chose, or English
+async fn user_locale(state: &AppState, user_id: Uuid) -> Result<Locale, sqlx::Error> {
+    let locale: Option<(Option<String>,)> = sqlx::query_as("SELECT locale FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?;
+    let locale = locale.and_then(|(locale,)| locale).and_then(|locale| state.i18n.supported(&locale));
+    Ok(locale.unwrap_or_default())
+}
+
+fn build_context(context: &HashMap<&str, &str>) -> Context {
     let mut tera_context = Context::new();
     for (key, value) in context {
         tera_context.insert(*key, value);
     }
+    tera_context
+}
 
-    let email_body = tera.render(template_name, &tera_context)?;
-
-    let email = Message::builder()
+fn build_message(
+    to: &str,
+    subject: &str,
+    email_body: String,
+    unsubscribe_url: Option<String>,
+    email_settings: &EmailSettings,
+) -> Result<Message, Box<dyn std::error::Error>> {
+    let mut builder = Message::builder()
         .from(email_settings.admin_email.parse()?)
         .to(to.parse()?)
         .subject(subject)
-        .header(ContentType::TEXT_HTML)
-        .body(email_body)?;
+        .header(ContentType::TEXT_HTML);
 
+    if let Some(url) = unsubscribe_url {
+        builder = builder
+            .header(ListUnsubscribe(url))
+            .header(ListUnsubscribePost);
+    }
+
+    Ok(builder.body(email_body)?)
+}
+
+fn deliver(email: &Message, email_settings: &EmailSettings) -> Result<(), Box<dyn std::error::Error>> {
     // let creds = Credentials::new(email_settings.smtp_username.into(),
     // email_settings.smtp_password.expose_secret().into());
     // let mailer = SmtpTransport::relay(email_settings.admin_email)?
@@ -38,8 +182,45 @@ pub async fn send_email(
         .port(email_settings.smtp_port)
         .build();
 
-    mailer.send(&email)?;
+    mailer.send(email)?;
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::build_message;
+    use crate::configuration::EmailSettings;
+    use secrecy::Secret;
+
+    fn email_settings() -> EmailSettings {
+        EmailSettings {
+            smtp_host: "localhost".into(),
+            smtp_port: 1025,
+            smtp_username: "emailer".into(),
+            smtp_password: Secret::new("password".into()),
+            admin_email: "admin@example.com".into(),
+            support_email: "support@example.com".into(),
+            welcome_email: "welcome@example.com".into(),
+            webhook_secret: Secret::new("webhook-secret".into()),
+        }
+    }
+
+    #[test]
+    fn unsubscribe_headers_are_set_when_there_is_an_unsubscribe_url() {
+        let url = "http://127.0.0.1/unsubscribe?token=abc".to_string();
+        let email = build_message("user@example.com", "Digest", "body".into(), Some(url), &email_settings()).unwrap();
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+
+        assert!(formatted.contains("List-Unsubscribe: <http://127.0.0.1/unsubscribe?token=abc>"));
+        assert!(formatted.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    }
+
+    #[test]
+    fn transactional_emails_have_no_unsubscribe_headers() {
+        let email = build_message("user@example.com", "Welcome", "body".into(), None, &email_settings()).unwrap();
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+
+        assert!(!formatted.contains("List-Unsubscribe"));
+    }
+}
@@ -0,0 +1,325 @@
+//! src/error.rs
+//! The application wide error type. Handlers return `AppError` and the
+//! `render_errors` middleware turns it into a branded HTML page for browsers or
+//! an RFC 7807 `application/problem+json` body for clients asking for JSON.
+//! Internal causes are logged with a correlation id and never sent to the user.
+//! Unknown routes and handler panics end up here too, through `not_found` and
+//! `handle_panic`.
+use axum::{
+    extract::{rejection::JsonRejection, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use serde::Serialize;
+use std::any::Any;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::constants::route_paths;
+use crate::i18n::{I18n, Locale};
+use crate::startup::AppState;
+use crate::template_helpers::error_page_template;
+use crate::security_headers::CspNonce;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Validation messages keyed by the name of the form field they belong to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(transparent)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.entry(field.into()).or_default().push(message.into());
+    }
+
+    pub fn get(&self, field: &str) -> Option<&[String]> {
+        self.0.get(field).map(Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Not Found")]
+    NotFound,
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Validation Failed")]
+    Validation { errors: FieldErrors },
+    #[error("Conflict")]
+    Conflict { detail: String },
+    #[error("Too Many Requests")]
+    RateLimited { retry_after: Option<u64> },
+    /// The message is only ever logged
+    #[error("Internal Server Error")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Message keys of the title and of the explanation shown to the user.
+    /// Explanations must never include internals. A conflict brings its own
+    /// explanation.
+    fn message_keys(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            AppError::NotFound => ("problem-not-found-title", Some("problem-not-found-detail")),
+            AppError::Unauthorized => ("problem-unauthorized-title", Some("problem-unauthorized-detail")),
+            AppError::Forbidden => ("problem-forbidden-title", Some("problem-forbidden-detail")),
+            AppError::Validation { .. } => ("problem-validation-title", Some("problem-validation-detail")),
+            AppError::Conflict { .. } => ("problem-conflict-title", None),
+            AppError::RateLimited { .. } => ("problem-rate-limited-title", Some("problem-rate-limited-detail")),
+            AppError::Internal(_) => ("problem-internal-title", Some("problem-internal-detail")),
+        }
+    }
+}
+
+/// RFC 7807 problem details. This is attached to error responses as an extension
+/// so `render_errors` can pick how to render it once it knows about the request.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub correlation_id: Uuid,
+    #[serde(skip_serializing_if = "FieldErrors::is_empty")]
+    pub errors: FieldErrors,
+    /// Message keys of the title and detail, which `render_errors` translates
+    #[serde(skip)]
+    pub message_keys: Option<(&'static str, Option<&'static str>)>,
+}
+
+impl ProblemDetails {
+    /// Puts the title and detail in the language of the request
+    pub fn translate(&mut self, i18n: &I18n, locale: &Locale) {
+        let Some((title_key, detail_key)) = self.message_keys.take() else {
+            return;
+        };
+        self.title = i18n.message(locale, title_key);
+        if let Some(detail_key) = detail_key {
+            self.detail = i18n.message(locale, detail_key);
+        }
+    }
+
+    fn json_response(&self, status: StatusCode) -> Response {
+        let body = serde_json::to_string(self).unwrap_or_default();
+        (status, [(header::CONTENT_TYPE, PROBLEM_JSON)], body).into_response()
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let correlation_id = Uuid::new_v4();
+        let status = self.status_code();
+        match &self {
+            AppError::Internal(cause) => {
+                tracing::error!(%correlation_id, error = %cause, "Internal server error");
+            },
+            other => {
+                tracing::info!(%correlation_id, status = status.as_u16(), error = %other, "Request failed");
+            },
+        }
+
+        let retry_after = match &self {
+            AppError::RateLimited { retry_after } => *retry_after,
+            _ => None,
+        };
+        // `render_errors` translates the title and detail once it knows the
+        // request's locale. Until then the detail is its message key.
+        let message_keys = self.message_keys();
+        let problem = ProblemDetails {
+            problem_type: "about:blank",
+            title: self.to_string(),
+            status: status.as_u16(),
+            detail: match &self {
+                AppError::Conflict { detail } => detail.clone(),
+                _ => message_keys.1.unwrap_or_default().to_string(),
+            },
+            instance: None,
+            correlation_id,
+            errors: match self {
+                AppError::Validation { errors } => errors,
+                _ => FieldErrors::new(),
+            },
+            message_keys: Some(message_keys),
+        };
+
+        // Without the `render_errors` middleware clients still get a safe problem+json body
+        let mut response = problem.json_response(status);
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response.extensions_mut().insert(problem);
+        response
+    }
+}
+
+/// Malformed JSON bodies are reported like any other validation error
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        let mut errors = FieldErrors::new();
+        errors.add("body", rejection.body_text());
+        AppError::Validation { errors }
+    }
+}
+
+/// Fallback for requests no route or static file matches
+pub async fn not_found() -> AppError {
+    AppError::NotFound
+}
+
+/// Responds to a panicking handler like to any other internal error, so the
+/// panic is logged and `render_errors` shows the 500 page or problem JSON.
+/// Used with `tower_http::catch_panic::CatchPanicLayer`.
+pub fn handle_panic(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("no message");
+    AppError::Internal(format!("Handler panicked: {}", message)).into_response()
+}
+
+/// Returns true for paths of the JSON API, which never renders HTML
+pub fn is_api_path(path: &str) -> bool {
+    path == route_paths::API_V1 || path.starts_with(&format!("{}/", route_paths::API_V1))
+}
+
+/// Returns true when the client prefers JSON over an HTML page
+pub fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            let accept = accept.to_ascii_lowercase();
+            accept.contains("json") && !accept.contains("text/html")
+        })
+}
+
+/// Middleware rendering `AppError` responses for the client that made the request
+pub async fn render_errors(
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let wants_json = wants_json(request.headers()) || is_api_path(&path);
+    let csp_nonce = request.extensions().get::<CspNonce>().cloned();
+    let locale = request.extensions().get::<Locale>().cloned().unwrap_or_default();
+
+    let mut response = next.run(request).await;
+    let Some(mut problem) = response.extensions_mut().remove::<ProblemDetails>() else {
+        return response;
+    };
+    problem.instance = Some(path);
+    problem.translate(&state.i18n, &locale);
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let rendered = if wants_json {
+        problem.json_response(parts.status)
+    } else {
+        Html(error_page_template(&state.tera, &problem, csp_nonce.as_ref(), Some(&locale))).into_response()
+    };
+    let (rendered_parts, body) = rendered.into_parts();
+    if let Some(content_type) = rendered_parts.headers.get(header::CONTENT_TYPE) {
+        parts.headers.insert(header::CONTENT_TYPE, content_type.clone());
+    }
+
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_panic, is_api_path, wants_json, AppError, FieldErrors, ProblemDetails};
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn internal_errors_do_not_leak_their_cause() {
+        let response = AppError::Internal("password=hunter42 connection refused".into()).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let problem = response.extensions().get::<ProblemDetails>().unwrap().clone();
+        assert!(!problem.detail.contains("hunter42"));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("hunter42"));
+    }
+
+    #[tokio::test]
+    async fn validation_errors_are_listed_per_field() {
+        let mut errors = FieldErrors::new();
+        errors.add("email", "Email is invalid");
+        let response = AppError::Validation { errors }.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["errors"]["email"][0], "Email is invalid");
+        assert_eq!(json["status"], 422);
+    }
+
+    #[tokio::test]
+    async fn panics_are_internal_errors_without_their_message() {
+        let response = handle_panic(Box::new(String::from("index out of bounds: the len is 3")));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.extensions().get::<ProblemDetails>().is_some());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("index out of bounds"));
+        assert_eq!(handle_panic(Box::new("boom")).status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn rate_limited_sets_retry_after() {
+        let response = AppError::RateLimited { retry_after: Some(30) }.into_response();
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+    }
+
+    #[test]
+    fn browsers_do_not_want_json() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8".parse().unwrap());
+        assert!(!wants_json(&headers));
+
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        assert!(wants_json(&headers));
+
+        assert!(!wants_json(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn api_paths_are_recognised() {
+        assert!(is_api_path("/api/v1"));
+        assert!(is_api_path("/api/v1/users/me"));
+        assert!(!is_api_path("/api/v10"));
+        assert!(!is_api_path("/account"));
+    }
+}
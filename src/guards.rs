@@ -0,0 +1,105 @@
+//! src/guards.rs
+//! Route layers that guard routes behind a login or a role.
+//!
+//! ```ignore
+//! Router::new()
+//!     .route(...)
+//!     .route_layer(middleware::from_fn_with_state(roles::ADMIN, guards::require_role))
+//!     .route_layer(middleware::from_fn(guards::require_login))
+//! ```
+//! Layers added last run first, so `require_login` has to be the last one.
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::Uri,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_login::AuthzBackend;
+use crate::api_tokens::ApiTokenAuth;
+use crate::constants::route_paths;
+use crate::domain::SafeRedirect;
+use crate::error::{is_api_path, wants_json, AppError};
+use crate::user::AuthSession;
+use crate::utils::e500;
+
+/// Anonymous browser requests are redirected to the login page with a `next`
+/// parameter pointing back to where they were going. API requests and clients
+/// asking for JSON get a 401.
+pub async fn require_login(
+    auth_session: AuthSession,
+    OriginalUri(original_uri): OriginalUri,
+    request: Request,
+    next: Next,
+) -> Response {
+    if auth_session.user.is_some() {
+        return next.run(request).await;
+    }
+
+    if wants_json(request.headers()) || is_api_path(original_uri.path()) {
+        return AppError::Unauthorized.into_response();
+    }
+
+    Redirect::to(&login_url(&original_uri)).into_response()
+}
+
+/// Requires the logged in user to have `role`. Must run after `require_login`.
+pub async fn require_role(
+    State(role): State<&'static str>,
+    auth_session: AuthSession,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ref user) = auth_session.user else {
+        return AppError::Unauthorized.into_response();
+    };
+
+    match auth_session.backend.has_perm(user, role.into()).await {
+        Ok(true) => next.run(request).await,
+        Ok(false) => AppError::Forbidden.into_response(),
+        Err(err) => e500(err).into_response(),
+    }
+}
+
+/// Only lets in requests authenticated with the session cookie, so an API
+/// token can't be used to manage API tokens. Must run after `require_login`.
+pub async fn require_session(request: Request, next: Next) -> Response {
+    if request.extensions().get::<ApiTokenAuth>().is_some() {
+        return AppError::Forbidden.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Builds `/login?next=<path and query>`. Paths that would not be safe to
+/// redirect back to are left out.
+pub fn login_url(original_uri: &Uri) -> String {
+    original_uri
+        .path_and_query()
+        .and_then(|path_and_query| SafeRedirect::parse(path_and_query.as_str()).ok())
+        .map(|next| next.append_to(route_paths::LOGIN))
+        .unwrap_or_else(|| route_paths::LOGIN.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::login_url;
+    use axum::http::Uri;
+
+    #[test]
+    fn login_url_keeps_path_and_query() {
+        let uri: Uri = "/account/notifications?tab=email&x=1".parse().unwrap();
+        assert_eq!(login_url(&uri), "/login?next=%2Faccount%2Fnotifications%3Ftab%3Demail%26x%3D1");
+    }
+
+    #[test]
+    fn login_url_drops_unsafe_paths() {
+        let uri: Uri = "//evil.example/path".parse().unwrap();
+        assert_eq!(login_url(&uri), "/login");
+    }
+
+    #[test]
+    fn login_url_for_root() {
+        let uri: Uri = "/".parse().unwrap();
+        assert_eq!(login_url(&uri), "/login?next=%2F");
+    }
+}
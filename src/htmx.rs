@@ -0,0 +1,199 @@
+//! src/htmx.rs
+//! Support for [htmx](https://htmx.org), which swaps server rendered HTML into
+//! the page without a JavaScript build step. `HxRequest` tells handlers
+//! whether a request came from htmx; `Render` uses it to answer with just the
+//! page's `content` block plus an out-of-band swap of the flash messages.
+//! `HxRedirect` and `HxTrigger` set the response headers htmx acts on.
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, IntoResponseParts, Redirect, Response, ResponseParts},
+};
+use std::convert::Infallible;
+use crate::error::AppError;
+use crate::utils::e500;
+
+pub const HX_REQUEST: HeaderName = HeaderName::from_static("hx-request");
+pub const HX_BOOSTED: HeaderName = HeaderName::from_static("hx-boosted");
+pub const HX_HISTORY_RESTORE_REQUEST: HeaderName = HeaderName::from_static("hx-history-restore-request");
+pub const HX_TARGET: HeaderName = HeaderName::from_static("hx-target");
+pub const HX_TRIGGER_NAME: HeaderName = HeaderName::from_static("hx-trigger-name");
+pub const HX_REDIRECT: HeaderName = HeaderName::from_static("hx-redirect");
+pub const HX_TRIGGER: HeaderName = HeaderName::from_static("hx-trigger");
+
+/// The htmx request headers. Every request has one; for requests not sent by
+/// htmx all of it is empty.
+#[derive(Debug, Clone, Default)]
+pub struct HxRequest {
+    /// Sent by htmx at all
+    pub is_htmx: bool,
+    /// From a link or form boosted with `hx-boost`
+    pub boosted: bool,
+    /// htmx lost the page from its history cache and wants all of it
+    pub history_restore: bool,
+    /// `id` of the element the response will be swapped into
+    pub target: Option<String>,
+    /// `name` of the element that triggered the request
+    pub trigger_name: Option<String>,
+}
+
+impl HxRequest {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let is_true = |name: HeaderName| headers.get(name).is_some_and(|value| value == "true");
+        let string = |name: HeaderName| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        Self {
+            is_htmx: is_true(HX_REQUEST),
+            boosted: is_true(HX_BOOSTED),
+            history_restore: is_true(HX_HISTORY_RESTORE_REQUEST),
+            target: string(HX_TARGET),
+            trigger_name: string(HX_TRIGGER_NAME),
+        }
+    }
+
+    /// Whether to answer with part of a page. Boosted requests and history
+    /// restores replace the whole body, so they get the full page.
+    pub fn wants_partial(&self) -> bool {
+        self.is_htmx && !self.boosted && !self.history_restore
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for HxRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+/// Makes htmx load another page in full, like a redirect does for a plain
+/// form post. A real redirect would be followed by the XHR and its page
+/// swapped into the target instead.
+#[derive(Debug, Clone)]
+pub struct HxRedirect(pub String);
+
+impl IntoResponseParts for HxRedirect {
+    type Error = AppError;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let location = HeaderValue::try_from(self.0).map_err(e500)?;
+        res.headers_mut().insert(HX_REDIRECT, location);
+        Ok(res)
+    }
+}
+
+impl IntoResponse for HxRedirect {
+    fn into_response(self) -> Response {
+        (self, ()).into_response()
+    }
+}
+
+/// Client side events htmx triggers once the response arrives, each with an
+/// optional detail: `HxTrigger::new().event("notifications-read")`
+#[derive(Debug, Clone, Default)]
+pub struct HxTrigger(serde_json::Map<String, serde_json::Value>);
+
+impl HxTrigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event(self, name: impl Into<String>) -> Self {
+        self.event_with_detail(name, serde_json::Value::Null)
+    }
+
+    pub fn event_with_detail(mut self, name: impl Into<String>, detail: serde_json::Value) -> Self {
+        self.0.insert(name.into(), detail);
+        self
+    }
+
+    /// The header value. Events without details are sent as a plain list,
+    /// others as JSON mapping each event to its detail.
+    fn header_value(&self) -> String {
+        if self.0.values().all(serde_json::Value::is_null) {
+            self.0.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+        } else {
+            serde_json::Value::Object(self.0.clone()).to_string()
+        }
+    }
+}
+
+impl IntoResponseParts for HxTrigger {
+    type Error = AppError;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if self.0.is_empty() {
+            return Ok(res);
+        }
+        let events = HeaderValue::try_from(self.header_value()).map_err(e500)?;
+        res.headers_mut().insert(HX_TRIGGER, events);
+        Ok(res)
+    }
+}
+
+impl IntoResponse for HxTrigger {
+    fn into_response(self) -> Response {
+        (self, ()).into_response()
+    }
+}
+
+/// Sends the browser to `to`: with `HX-Redirect` for htmx requests and a
+/// `303 See Other` otherwise
+pub fn redirect(hx: &HxRequest, to: &str) -> Response {
+    if hx.is_htmx {
+        HxRedirect(to.to_string()).into_response()
+    } else {
+        Redirect::to(to).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redirect, HxRequest, HxTrigger};
+    use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+    use axum::response::IntoResponse;
+
+    fn htmx_headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn only_plain_htmx_requests_want_partials() {
+        assert!(!HxRequest::from_headers(&HeaderMap::new()).wants_partial());
+        let request = HxRequest::from_headers(&htmx_headers(&[("HX-Request", "true"), ("HX-Target", "main-content")]));
+        assert!(request.wants_partial());
+        assert_eq!(request.target.as_deref(), Some("main-content"));
+        assert!(!HxRequest::from_headers(&htmx_headers(&[("HX-Request", "true"), ("HX-Boosted", "true")])).wants_partial());
+        assert!(!HxRequest::from_headers(&htmx_headers(&[("HX-Request", "true"), ("HX-History-Restore-Request", "true")])).wants_partial());
+    }
+
+    #[test]
+    fn htmx_is_redirected_with_a_header() {
+        let response = redirect(&HxRequest::from_headers(&htmx_headers(&[("HX-Request", "true")])), "/");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["HX-Redirect"], "/");
+
+        let response = redirect(&HxRequest::default(), "/");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()["Location"], "/");
+    }
+
+    #[test]
+    fn triggers_only_use_json_for_details() {
+        let response = HxTrigger::new().event("closed").event("saved").into_response();
+        assert_eq!(response.headers()["HX-Trigger"], "closed, saved");
+
+        let trigger = HxTrigger::new().event_with_detail("count", serde_json::json!(3)).event("saved");
+        let response = trigger.into_response();
+        assert_eq!(response.headers()["HX-Trigger"], r#"{"count":3,"saved":null}"#);
+        assert!(HxTrigger::new().into_response().headers().get("HX-Trigger").is_none());
+    }
+}
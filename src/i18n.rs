@@ -0,0 +1,408 @@
+//! src/i18n.rs
+//! Translations of pages, flash messages, emails and validation errors. Every
+//! locale has a catalog in `locales/<locale>.ftl`, written in
+//! [Fluent](https://projectfluent.org), so messages can use plural forms and
+//! other selectors. Catalogs that don't parse stop the app from starting.
+//!
+//! `set_locale` picks the locale of each request from, in order: a `?lang=`
+//! query parameter, which is also remembered in a cookie, that cookie, the
+//! user's preference, `Accept-Language`, and finally English.
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::Arc;
+use unic_langid::LanguageIdentifier;
+use validator::ValidationErrors;
+use crate::configuration::Environment;
+use crate::error::FieldErrors;
+use crate::startup::AppState;
+use crate::user::AuthSession;
+
+pub const LOCALES_DIR: &str = "locales";
+/// Used for anything missing from the catalog of the request's locale
+pub const DEFAULT_LOCALE: &str = "en";
+/// Query parameter and cookie overriding the negotiated locale
+pub const LOCALE_PARAM: &str = "lang";
+/// Each catalog's name for its own language, shown in the language picker
+const LOCALE_NAME_KEY: &str = "locale-name";
+
+/// The catalogs shared by every request
+pub type SharedI18n = Arc<I18n>;
+
+/// The messages of one locale
+struct Catalog(FluentBundle<FluentResource>);
+
+impl std::fmt::Debug for Catalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Catalog").field(&self.0.locales).finish()
+    }
+}
+
+impl Catalog {
+    fn parse(locale: &str, source: String) -> Result<Self, String> {
+        let language: LanguageIdentifier = locale.parse().map_err(|err| format!("`{}` is not a language tag: {}", locale, err))?;
+        let resource = FluentResource::try_new(source).map_err(|(_, errors)| join_errors(&errors))?;
+        let mut bundle = FluentBundle::new_concurrent(vec![language]);
+        // The isolation marks Fluent puts around variables would end up in the HTML
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).map_err(|errors| join_errors(&errors))?;
+        Ok(Self(bundle))
+    }
+
+    /// `None` if there is no such message, or it only has attributes
+    fn format(&self, key: &str, args: Option<&FluentArgs>) -> Option<String> {
+        let pattern = self.0.get_message(key)?.value()?;
+        let mut errors = Vec::new();
+        let formatted = self.0.format_pattern(pattern, args, &mut errors).into_owned();
+        if !errors.is_empty() {
+            tracing::warn!(key, errors = %join_errors(&errors), "Failed to format a translation");
+        }
+        Some(formatted)
+    }
+}
+
+fn join_errors(errors: &[impl std::fmt::Display]) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// Fluent arguments from a JSON object. Numbers stay numbers, so they can
+/// pick plural forms.
+fn fluent_args(args: &serde_json::Value) -> Option<FluentArgs<'static>> {
+    let serde_json::Value::Object(args) = args else {
+        return None;
+    };
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        let value = match value {
+            serde_json::Value::Null => continue,
+            serde_json::Value::String(value) => FluentValue::from(value.clone()),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(number) => FluentValue::from(number),
+                None => FluentValue::from(number.as_f64().unwrap_or_default()),
+            },
+            value => FluentValue::from(value.to_string()),
+        };
+        fluent_args.set(name.clone(), value);
+    }
+    Some(fluent_args)
+}
+
+/// A locale there is a catalog for. Extracting it gives the locale `set_locale`
+/// picked for the request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Locale(String);
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self(DEFAULT_LOCALE.to_string())
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Locale
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned().unwrap_or_default())
+    }
+}
+
+/// A choice in the language picker
+#[derive(Debug, Clone, Serialize)]
+pub struct LocaleOption {
+    pub code: String,
+    /// The language's name in that language
+    pub name: String,
+}
+
+/// The catalog of every locale, by lowercase locale name
+#[derive(Debug)]
+pub struct I18n {
+    catalogs: BTreeMap<String, Catalog>,
+}
+
+impl I18n {
+    /// Loads every `.ftl` file in `dir`. The default locale has to be one of them.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let dir = dir.as_ref();
+        let mut sources = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "ftl") {
+                let locale = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                sources.push((locale, std::fs::read_to_string(&path)?));
+            }
+        }
+        Self::from_sources(sources).map_err(|err| anyhow::anyhow!("Failed to load the catalogs in {}/: {}", dir.display(), err))
+    }
+
+    fn from_sources(sources: impl IntoIterator<Item = (String, String)>) -> Result<Self, String> {
+        let mut catalogs = BTreeMap::new();
+        for (locale, source) in sources {
+            let locale = locale.to_ascii_lowercase();
+            let catalog = Catalog::parse(&locale, source).map_err(|err| format!("{}.ftl: {}", locale, err))?;
+            catalogs.insert(locale, catalog);
+        }
+        if !catalogs.contains_key(DEFAULT_LOCALE) {
+            return Err(format!("there is no catalog for the default locale, {}", DEFAULT_LOCALE));
+        }
+        Ok(Self { catalogs })
+    }
+
+    /// Every locale with the name of its language
+    pub fn locales(&self) -> Vec<LocaleOption> {
+        self.catalogs
+            .keys()
+            .map(|code| {
+                let locale = Locale(code.clone());
+                LocaleOption { code: code.clone(), name: self.message(&locale, LOCALE_NAME_KEY) }
+            })
+            .collect()
+    }
+
+    /// The supported locale for a language tag, like `de` for `de-AT`
+    pub fn supported(&self, tag: &str) -> Option<Locale> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+        let locale = [tag.as_str(), language]
+            .into_iter()
+            .find(|candidate| self.catalogs.contains_key(*candidate))?;
+        Some(Locale(locale.to_string()))
+    }
+
+    /// The supported locale the user prefers most, going by an
+    /// `Accept-Language` header like `de-CH, de;q=0.9, en;q=0.8`
+    pub fn negotiate(&self, accept_language: &str) -> Option<Locale> {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let tag = params.next()?.trim();
+                let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                    Some(quality) => quality.trim().parse().ok()?,
+                    None => 1.0,
+                };
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally preferred languages keep their order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.into_iter().find_map(|(tag, _)| self.supported(tag))
+    }
+
+    /// The message `key` in `locale`, with `args` (a JSON object) filling in
+    /// its variables. Falls back to English and then to the key itself.
+    pub fn translate(&self, locale: &Locale, key: &str, args: &serde_json::Value) -> String {
+        let args = fluent_args(args);
+        let found = [locale.as_ref(), DEFAULT_LOCALE]
+            .into_iter()
+            .find_map(|locale| self.catalogs.get(locale)?.format(key, args.as_ref()));
+        found.unwrap_or_else(|| {
+            tracing::warn!(key, locale = locale.as_ref(), "Missing translation");
+            key.to_string()
+        })
+    }
+
+    /// `translate` for messages without variables
+    pub fn message(&self, locale: &Locale, key: &str) -> String {
+        self.translate(locale, key, &serde_json::Value::Null)
+    }
+
+    /// The locale named by a template function's or filter's `lang` argument.
+    /// Unsupported or missing locales give English.
+    pub fn locale_arg(&self, args: &HashMap<String, tera::Value>) -> Locale {
+        args.get("lang")
+            .and_then(|lang| lang.as_str())
+            .and_then(|lang| self.supported(lang))
+            .unwrap_or_default()
+    }
+
+    /// Validation errors translated by their code, with their params as variables
+    pub fn field_errors(&self, locale: &Locale, errors: &ValidationErrors) -> FieldErrors {
+        let mut field_errors = FieldErrors::new();
+        for (field, errors) in errors.field_errors() {
+            for error in errors {
+                let args = serde_json::to_value(&error.params).unwrap_or_default();
+                field_errors.add(field, self.translate(locale, &error.code, &args));
+            }
+        }
+        field_errors
+    }
+}
+
+/// `{{ t(key="login-title", lang=locale) }}`. Other arguments are the
+/// message's variables. `lang` defaults to English, for pages not rendered
+/// with `Render`.
+pub struct Translate(pub SharedI18n);
+
+impl tera::Function for Translate {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let key = args.get("key").and_then(|key| key.as_str()).ok_or("t requires a `key` argument")?;
+        let locale = self.0.locale_arg(args);
+        let variables = args
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "key" | "lang"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Ok(tera::Value::String(self.0.translate(&locale, key, &tera::Value::Object(variables))))
+    }
+}
+
+/// Middleware picking the locale of the request. A supported `?lang=` is
+/// remembered for a year.
+pub async fn set_locale(
+    Extension(state): Extension<AppState>,
+    auth_session: AuthSession,
+    jar: CookieJar,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let i18n = &state.i18n;
+    let requested = serde_urlencoded::from_str::<Vec<(String, String)>>(request.uri().query().unwrap_or_default())
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(name, _)| name == LOCALE_PARAM)
+        .and_then(|(_, value)| i18n.supported(&value));
+    let remembered = jar.get(LOCALE_PARAM).and_then(|cookie| i18n.supported(cookie.value()));
+    let preferred = auth_session
+        .user
+        .as_ref()
+        .and_then(|user| user.locale.as_deref())
+        .and_then(|locale| i18n.supported(locale));
+    let negotiated = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|accept_language| i18n.negotiate(accept_language));
+
+    let locale = requested.clone().or(remembered).or(preferred).or(negotiated).unwrap_or_default();
+    request.extensions_mut().insert(locale);
+    let response = next.run(request).await;
+
+    match requested {
+        Some(locale) => {
+            let cookie = Cookie::build((LOCALE_PARAM, locale.0))
+                .path("/")
+                .max_age(time::Duration::days(365))
+                .same_site(SameSite::Lax)
+                .secure(state.environment == Environment::Production)
+                .http_only(true);
+            (jar.add(cookie), response).into_response()
+        },
+        None => response,
+    }
+}
+
+/// Forgets a `?lang=` override, so the user's preference applies again
+pub fn forget_override(jar: CookieJar) -> CookieJar {
+    jar.remove(Cookie::build(LOCALE_PARAM).path("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Catalog, I18n, Locale, LOCALES_DIR};
+    use fluent_bundle::FluentResource;
+    use fluent_syntax::ast::Entry;
+    use serde_json::json;
+    use std::collections::BTreeSet;
+
+    fn i18n() -> I18n {
+        I18n::from_sources([
+            ("en".to_string(), "-brand = Acme\nhello = Hello, { $name }!\nbye = Bye from { -brand }\nonly-english = English\n".to_string()),
+            ("de".to_string(), "hello = Hallo, { $name }!\nbye = Tschüss von { -brand }\n-brand = Acme GmbH\n".to_string()),
+        ]).unwrap()
+    }
+
+    #[test]
+    fn messages_fill_in_variables_and_terms() {
+        let i18n = i18n();
+        let german = i18n.supported("de").unwrap();
+        assert_eq!(i18n.translate(&german, "hello", &json!({ "name": "Ursula" })), "Hallo, Ursula!");
+        assert_eq!(i18n.message(&german, "bye"), "Tschüss von Acme GmbH");
+        assert_eq!(i18n.message(&Locale::default(), "hello"), "Hello, {$name}!");
+        assert_eq!(i18n.message(&german, "only-english"), "English");
+        assert_eq!(i18n.message(&german, "missing"), "missing");
+    }
+
+    #[test]
+    fn catalogs_support_comments_and_multiline_values() {
+        let catalog = Catalog::parse("en", "# A comment\n## A group\nlong =\n    First line\n    second line\n\nshort = { \"{\" }x\n".to_string()).unwrap();
+        assert_eq!(catalog.format("long", None).unwrap(), "First line\nsecond line");
+        assert_eq!(catalog.format("short", None).unwrap(), "{x");
+    }
+
+    #[test]
+    fn plurals_follow_the_rules_of_the_locale() {
+        let source = "items = { $count ->\n    [one] { $count } item\n   *[other] { $count } items\n}\n";
+        let i18n = I18n::from_sources([("en".to_string(), source.to_string())]).unwrap();
+        let english = Locale::default();
+        assert_eq!(i18n.translate(&english, "items", &json!({ "count": 1 })), "1 item");
+        assert_eq!(i18n.translate(&english, "items", &json!({ "count": 3 })), "3 items");
+        assert_eq!(i18n.translate(&english, "items", &json!({ "count": 0 })), "0 items");
+    }
+
+    #[test]
+    fn invalid_catalogs_are_rejected() {
+        assert!(Catalog::parse("en", "a = 1\na = 2".to_string()).is_err());
+        assert!(Catalog::parse("en", "no value here".to_string()).is_err());
+        assert!(Catalog::parse("not a language", "a = b".to_string()).is_err());
+        assert!(I18n::from_sources([("de".to_string(), "a = b".to_string())]).is_err());
+    }
+
+    #[test]
+    fn accept_language_picks_the_most_preferred_supported_locale() {
+        let i18n = i18n();
+        let negotiate = |header| i18n.negotiate(header).map(|locale| locale.0);
+        assert_eq!(negotiate("de-CH, en;q=0.8").as_deref(), Some("de"));
+        assert_eq!(negotiate("fr, en;q=0.5, de;q=0.7").as_deref(), Some("de"));
+        assert_eq!(negotiate("de;q=0, en-GB").as_deref(), Some("en"));
+        assert_eq!(negotiate("fr, *;q=0.1"), None);
+        assert_eq!(negotiate(""), None);
+        assert_eq!(i18n.supported("DE_at").map(|locale| locale.0).as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn shipped_catalogs_translate_every_english_message() {
+        let message_ids = |locale: &str| -> BTreeSet<String> {
+            let source = std::fs::read_to_string(format!("{}/{}.ftl", LOCALES_DIR, locale)).unwrap();
+            FluentResource::try_new(source)
+                .unwrap()
+                .entries()
+                .filter_map(|entry| match entry {
+                    Entry::Message(message) => Some(message.id.name.to_string()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let i18n = I18n::load(LOCALES_DIR).unwrap();
+        let english = message_ids("en");
+        assert!(i18n.catalogs.len() > 1);
+        for locale in i18n.catalogs.keys() {
+            let messages = message_ids(locale);
+            let missing: Vec<&String> = english.difference(&messages).collect();
+            assert!(missing.is_empty(), "{}.ftl is missing {:?}", locale, missing);
+            let extra: Vec<&String> = messages.difference(&english).collect();
+            assert!(extra.is_empty(), "{}.ftl has messages English doesn't: {:?}", locale, extra);
+        }
+    }
+}
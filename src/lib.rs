@@ -5,8 +5,33 @@ pub mod routes;
 pub mod handlers;
 pub mod telemetry;
 pub mod template_helpers;
+pub mod render;
+pub mod htmx;
+pub mod i18n;
 pub mod utils;
+pub mod error;
+pub mod guards;
+pub mod csrf;
+pub mod session;
+pub mod security_headers;
+pub mod rate_limit;
+pub mod proxy;
+pub mod api_tokens;
+pub mod realtime;
+pub mod scss;
+pub mod assets;
+pub mod compression;
+pub mod dev_reload;
+pub mod notifications;
+pub mod audit;
+pub mod account_exports;
+pub mod account_deletion;
 pub mod user;
 pub mod domain;
 pub mod emailer;
+pub mod notification_preferences;
+pub mod email_events;
+pub mod suppressions;
 pub mod constants;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::domain::EmailCategory;
+
+/// The email categories a user has opted in to. Users without a row in
+/// `notification_preferences` get the defaults (everything on).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct NotificationPreferences {
+    pub product_updates: bool,
+    pub digests: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            product_updates: true,
+            digests: true,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    /// Returns true if an email of `category` may be sent to the user
+    pub fn allows(&self, category: EmailCategory) -> bool {
+        match category {
+            EmailCategory::Transactional | EmailCategory::SecurityAlerts => true,
+            EmailCategory::ProductUpdates => self.product_updates,
+            EmailCategory::Digests => self.digests,
+        }
+    }
+
+    pub async fn fetch(db: &PgPool, user_id: Uuid) -> Result<Self, sqlx::Error> {
+        let preferences: Option<Self> = sqlx::query_as(
+            "SELECT product_updates, digests FROM notification_preferences WHERE user_id = $1"
+        )
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(preferences.unwrap_or_default())
+    }
+
+    pub async fn save(&self, db: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, product_updates, digests) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE SET product_updates = EXCLUDED.product_updates, digests = EXCLUDED.digests"
+        )
+            .bind(user_id)
+            .bind(self.product_updates)
+            .bind(self.digests)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Turns off a single category for the user. Categories that are not
+    /// optional are left untouched.
+    pub async fn unsubscribe(db: &PgPool, user_id: Uuid, category: EmailCategory) -> Result<(), sqlx::Error> {
+        let mut preferences = Self::fetch(db, user_id).await?;
+        match category {
+            EmailCategory::ProductUpdates => preferences.product_updates = false,
+            EmailCategory::Digests => preferences.digests = false,
+            EmailCategory::Transactional | EmailCategory::SecurityAlerts => return Ok(()),
+        }
+        preferences.save(db, user_id).await
+    }
+}
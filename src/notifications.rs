@@ -0,0 +1,204 @@
+//! src/notifications.rs
+//! In-app notifications. `notify` stores a notification, pushes it to the
+//! user's open tabs and, depending on its kind and the user's preferences,
+//! emails it too. It can be called from handlers and background jobs alike.
+//!
+//! ```ignore
+//! notifications::notify(&state, user.id(), NewNotification::new(NotificationKind::Info, "Export ready")
+//!     .with_url("/account/export")).await?;
+//! ```
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::time::Duration;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::configuration::NotificationSettings;
+use crate::constants::email_templates;
+use crate::domain::NotificationKind;
+use crate::emailer::{self, UserEmailParams};
+use crate::realtime::Event;
+use crate::startup::AppState;
+
+/// How often old notifications are pruned
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Notification {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub kind: String,
+    pub title: String,
+    pub body: Option<String>,
+    pub url: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub read_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewNotification {
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: Option<String>,
+    /// Where the notification links to, a path within the app
+    pub url: Option<String>,
+}
+
+impl NewNotification {
+    pub fn new(kind: NotificationKind, title: impl Into<String>) -> Self {
+        Self { kind, title: title.into(), body: None, url: None }
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+}
+
+/// Creates a notification for the user. Failing to push or email it is
+/// logged but does not fail the call; the notification is stored either way.
+pub async fn notify(state: &AppState, user_id: Uuid, new_notification: NewNotification) -> Result<Notification, sqlx::Error> {
+    let notification = create(&state.db, user_id, &new_notification).await?;
+    let unread_count = unread_count(&state.db, user_id).await?;
+
+    state.realtime.publish(user_id, Event::Notification {
+        id: notification.id,
+        title: notification.title.clone(),
+        body: notification.body.clone(),
+        url: notification.url.clone(),
+        unread_count,
+    }).await;
+
+    if let Some(category) = new_notification.kind.email_category() {
+        let email: Option<(String,)> = sqlx::query_as("SELECT email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await?;
+        if let Some((email,)) = email {
+            let url = notification.url.as_ref().map(|url| format!("{}{}", state.base_url, url)).unwrap_or_default();
+            let mut context = HashMap::new();
+            context.insert("title", notification.title.as_str());
+            context.insert("body", notification.body.as_deref().unwrap_or_default());
+            context.insert("url", url.as_str());
+            let params = UserEmailParams {
+                user_id,
+                to: &email,
+                category,
+                subject: &notification.title,
+                template_name: email_templates::NOTIFICATION,
+                context: &context,
+            };
+            if let Err(err) = emailer::send_user_email(state, params).await {
+                tracing::error!(error = %err, notification_id = %notification.id, "Failed to email a notification");
+            }
+        }
+    }
+
+    Ok(notification)
+}
+
+pub async fn create(db: &PgPool, user_id: Uuid, new_notification: &NewNotification) -> Result<Notification, sqlx::Error> {
+    sqlx::query_as(
+        "INSERT INTO notifications (id, user_id, kind, title, body, url) VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, user_id, kind, title, body, url, read_at, created_at"
+    )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(new_notification.kind.as_str())
+        .bind(&new_notification.title)
+        .bind(&new_notification.body)
+        .bind(&new_notification.url)
+        .fetch_one(db)
+        .await
+}
+
+/// The user's notifications, newest first
+pub async fn list(db: &PgPool, user_id: Uuid, limit: i64, offset: i64) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, user_id, kind, title, body, url, read_at, created_at FROM notifications
+        WHERE user_id = $1 ORDER BY created_at DESC, id LIMIT $2 OFFSET $3"
+    )
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count(db: &PgPool, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+    Ok(count as u64)
+}
+
+pub async fn unread_count(db: &PgPool, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read_at IS NULL")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+    Ok(count as u64)
+}
+
+/// Returns the notification, or `None` if the user has no such notification
+pub async fn mark_read(db: &PgPool, user_id: Uuid, notification_id: Uuid) -> Result<Option<Notification>, sqlx::Error> {
+    sqlx::query_as(
+        "UPDATE notifications SET read_at = COALESCE(read_at, NOW()) WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, kind, title, body, url, read_at, created_at"
+    )
+        .bind(notification_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Returns how many notifications were unread
+pub async fn mark_all_read(db: &PgPool, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes read notifications past the retention period, and unread ones
+/// past the longer unread retention period. Returns how many were deleted.
+pub async fn prune(db: &PgPool, settings: &NotificationSettings) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM notifications
+        WHERE (read_at IS NOT NULL AND created_at < NOW() - make_interval(days => $1))
+            OR created_at < NOW() - make_interval(days => $2)"
+    )
+        .bind(settings.retention_days)
+        .bind(settings.unread_retention_days)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Prunes old notifications every `PRUNE_INTERVAL`, until aborted
+pub async fn continuously_prune(db: PgPool, settings: NotificationSettings) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match prune(&db, &settings).await {
+            Ok(0) => {},
+            Ok(deleted) => tracing::info!(deleted, "Pruned old notifications"),
+            Err(err) => tracing::error!(error = %err, "Failed to prune old notifications"),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! src/proxy.rs
+//! The reverse proxies in front of the app. `TrustedProxies` is a request
+//! extension, so rate limits, the audit log and anything else that needs the
+//! client's address read it from the same place.
+use axum::{
+    extract::ConnectInfo,
+    http::{Extensions, HeaderMap, HeaderName},
+};
+use std::net::SocketAddr;
+use crate::configuration::ProxySettings;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// How many proxies' `X-Forwarded-For` entries are trusted, none by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrustedProxies {
+    hops: usize,
+}
+
+impl TrustedProxies {
+    pub fn new(hops: usize) -> Self {
+        Self { hops }
+    }
+
+    pub fn from_settings(settings: &ProxySettings) -> Self {
+        Self::new(if settings.trust_forwarded_for { settings.trusted_proxy_hops } else { 0 })
+    }
+
+    /// The address of the client. Behind trusted proxies it is the
+    /// `X-Forwarded-For` entry as many places from the right as there are
+    /// proxies, the one the outermost of them appended. Entries further left
+    /// are sent by the client and can be anything. With no trusted proxies,
+    /// or fewer entries than proxies, it is the peer address of the connection.
+    pub fn client_ip(&self, headers: &HeaderMap, extensions: &Extensions) -> Option<String> {
+        let entries: Vec<&str> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        let forwarded_for = entries
+            .len()
+            .checked_sub(self.hops)
+            .filter(|_| self.hops > 0)
+            .map(|index| entries[index].to_string())
+            .filter(|ip| !ip.is_empty());
+
+        forwarded_for.or_else(|| {
+            extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip().to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrustedProxies;
+    use crate::configuration::ProxySettings;
+    use axum::extract::ConnectInfo;
+    use axum::http::{Extensions, HeaderMap};
+    use std::net::SocketAddr;
+
+    fn peer() -> Extensions {
+        let mut extensions = Extensions::new();
+        let address: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        extensions.insert(ConnectInfo(address));
+        extensions
+    }
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("X-Forwarded-For", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_unless_trusted() {
+        let headers = forwarded_for(&["203.0.113.9"]);
+        assert_eq!(TrustedProxies::default().client_ip(&headers, &peer()).as_deref(), Some("10.0.0.1"));
+        let settings = ProxySettings { trust_forwarded_for: false, trusted_proxy_hops: 1 };
+        assert_eq!(TrustedProxies::from_settings(&settings), TrustedProxies::default());
+    }
+
+    #[test]
+    fn repeated_headers_are_read_as_one_list() {
+        let headers = forwarded_for(&["198.51.100.7", "203.0.113.9, 10.0.0.2"]);
+        assert_eq!(TrustedProxies::new(1).client_ip(&headers, &peer()).as_deref(), Some("10.0.0.2"));
+        assert_eq!(TrustedProxies::new(3).client_ip(&headers, &peer()).as_deref(), Some("198.51.100.7"));
+    }
+}
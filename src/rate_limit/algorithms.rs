@@ -0,0 +1,203 @@
+//! The two rate limiting algorithms. The in-memory store keeps these structs
+//! per key; the Redis scripts in `store.rs` mirror `take` and `hit` and reuse
+//! the `*_decision` functions so both stores answer the same way.
+use super::Decision;
+
+/// A bucket holding up to `limit` tokens, refilled evenly over the period.
+/// Allows bursts of `limit` requests and then one request per
+/// `period / limit`.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    pub updated_ms: u64,
+}
+
+impl TokenBucket {
+    pub fn full(limit: u32, now_ms: u64) -> Self {
+        Self { tokens: f64::from(limit), updated_ms: now_ms }
+    }
+
+    /// Refills the bucket and takes a token if there is one
+    pub fn take(&mut self, limit: u32, period_ms: u64, now_ms: u64) -> Decision {
+        let rate = f64::from(limit) / period_ms as f64;
+        let elapsed = now_ms.saturating_sub(self.updated_ms) as f64;
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limit));
+        self.updated_ms = now_ms;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        token_bucket_decision(limit, period_ms, self.tokens, allowed)
+    }
+}
+
+pub fn token_bucket_decision(limit: u32, period_ms: u64, tokens: f64, allowed: bool) -> Decision {
+    let rate = f64::from(limit) / period_ms as f64;
+    Decision {
+        allowed,
+        limit,
+        remaining: tokens.floor().max(0.0) as u32,
+        reset_after: ms_to_secs((f64::from(limit) - tokens) / rate),
+        retry_after: (!allowed).then(|| ms_to_secs((1.0 - tokens) / rate).max(1)),
+    }
+}
+
+/// Counts requests in fixed windows and weighs the previous window by how
+/// much of it still overlaps the sliding window ending now. Smoother than a
+/// fixed window without keeping a log of every request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlidingWindow {
+    pub window_start_ms: u64,
+    pub previous: u32,
+    pub current: u32,
+}
+
+impl SlidingWindow {
+    pub fn window_start(period_ms: u64, now_ms: u64) -> u64 {
+        now_ms - now_ms % period_ms
+    }
+
+    /// Counts the request if it is allowed
+    pub fn hit(&mut self, limit: u32, period_ms: u64, now_ms: u64) -> Decision {
+        let window_start_ms = Self::window_start(period_ms, now_ms);
+        if window_start_ms != self.window_start_ms {
+            self.previous = if window_start_ms.saturating_sub(self.window_start_ms) == period_ms {
+                self.current
+            } else {
+                0
+            };
+            self.current = 0;
+            self.window_start_ms = window_start_ms;
+        }
+
+        let elapsed_ms = now_ms - window_start_ms;
+        let allowed = estimate(period_ms, elapsed_ms, self.previous, self.current) + 1.0 <= f64::from(limit);
+        if allowed {
+            self.current += 1;
+        }
+        sliding_window_decision(limit, period_ms, elapsed_ms, self.previous, self.current, allowed)
+    }
+}
+
+fn estimate(period_ms: u64, elapsed_ms: u64, previous: u32, current: u32) -> f64 {
+    let overlap = 1.0 - elapsed_ms as f64 / period_ms as f64;
+    f64::from(previous) * overlap + f64::from(current)
+}
+
+/// `current` is the count after this request was (or was not) counted
+pub fn sliding_window_decision(
+    limit: u32,
+    period_ms: u64,
+    elapsed_ms: u64,
+    previous: u32,
+    current: u32,
+    allowed: bool,
+) -> Decision {
+    let estimated = estimate(period_ms, elapsed_ms, previous, current);
+    let retry_after = (!allowed).then(|| {
+        // When the estimate drops to `limit - 1` there is room for one more
+        let target = f64::from(limit) - 1.0;
+        let period = period_ms as f64;
+        let wait_ms = if f64::from(current) <= target && previous > 0 {
+            period * (1.0 - (target - f64::from(current)) / f64::from(previous)) - elapsed_ms as f64
+        } else {
+            // Only once this window is the previous one
+            (period - elapsed_ms as f64) + period * (1.0 - target / f64::from(current.max(1))).max(0.0)
+        };
+        ms_to_secs(wait_ms).max(1)
+    });
+
+    Decision {
+        allowed,
+        limit,
+        remaining: (f64::from(limit) - estimated).floor().max(0.0) as u32,
+        reset_after: ms_to_secs((period_ms - elapsed_ms) as f64),
+        retry_after,
+    }
+}
+
+fn ms_to_secs(ms: f64) -> u64 {
+    (ms / 1000.0).ceil().max(0.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SlidingWindow, TokenBucket};
+
+    const MINUTE: u64 = 60_000;
+
+    #[test]
+    fn token_bucket_allows_a_burst_then_refills() {
+        let start = 1_000_000;
+        let mut bucket = TokenBucket::full(3, start);
+        for remaining in [2, 1, 0] {
+            let decision = bucket.take(3, MINUTE, start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let denied = bucket.take(3, MINUTE, start);
+        assert!(!denied.allowed);
+        // One token every 20 seconds
+        assert_eq!(denied.retry_after, Some(20));
+        assert_eq!(denied.reset_after, 60);
+
+        assert!(!bucket.take(3, MINUTE, start + 19_000).allowed);
+        assert!(bucket.take(3, MINUTE, start + 21_000).allowed);
+    }
+
+    #[test]
+    fn token_bucket_never_holds_more_than_the_limit() {
+        let mut bucket = TokenBucket::full(2, 0);
+        bucket.take(2, MINUTE, 0);
+        let decision = bucket.take(2, MINUTE, 10 * MINUTE);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+    }
+
+    #[test]
+    fn sliding_window_denies_after_the_limit() {
+        let mut window = SlidingWindow::default();
+        let start = 10 * MINUTE;
+        for remaining in [2, 1, 0] {
+            let decision = window.hit(3, MINUTE, start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let denied = window.hit(3, MINUTE, start + 30_000);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Some(50));
+        assert_eq!(denied.reset_after, 30);
+        assert_eq!(window.current, 3);
+    }
+
+    #[test]
+    fn sliding_window_weighs_the_previous_window() {
+        let mut window = SlidingWindow::default();
+        let start = 10 * MINUTE;
+        for _ in 0..4 {
+            assert!(window.hit(4, MINUTE, start).allowed);
+        }
+
+        // Half way into the next window half of the previous count still applies
+        let half_way = start + MINUTE + 30_000;
+        assert!(window.hit(4, MINUTE, half_way).allowed);
+        assert!(window.hit(4, MINUTE, half_way).allowed);
+        assert!(!window.hit(4, MINUTE, half_way).allowed);
+        assert_eq!(window.previous, 4);
+        assert_eq!(window.current, 2);
+    }
+
+    #[test]
+    fn sliding_window_forgets_old_windows() {
+        let mut window = SlidingWindow::default();
+        for _ in 0..2 {
+            window.hit(2, MINUTE, MINUTE);
+        }
+        let decision = window.hit(2, MINUTE, 5 * MINUTE);
+        assert!(decision.allowed);
+        assert_eq!(window.previous, 0);
+    }
+}
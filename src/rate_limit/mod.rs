@@ -0,0 +1,256 @@
+//! src/rate_limit/mod.rs
+//! Rate limits for route groups. Each group declares its `RateLimit` in
+//! `routes::mod` and adds it as a route layer:
+//!
+//! ```ignore
+//! Router::new()
+//!     .nest(route_paths::ROOT, auth::routes())
+//!     .route_layer(middleware::from_fn_with_state(rate_limits::AUTH, rate_limit::enforce))
+//! ```
+//! Counters live in the `RateLimiter` request extension, in memory or in
+//! Redis depending on `application.rate_limit.store`.
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_login::AuthUser;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use crate::error::AppError;
+use crate::proxy::TrustedProxies;
+use crate::user::AuthSession;
+
+mod algorithms;
+mod store;
+
+pub use store::RateLimiter;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Allows bursts up to the limit, refilling evenly over the period
+    TokenBucket,
+    /// At most `limit` requests in any window of `period`
+    SlidingWindow,
+}
+
+/// What requests are counted together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyBy {
+    Ip,
+    /// The logged in user, or the IP for anonymous requests
+    User,
+    /// The bearer token, or the IP for requests without one
+    ApiKey,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Namespaces the counters, so every limit needs its own name
+    pub name: &'static str,
+    pub limit: u32,
+    pub period: Duration,
+    pub algorithm: Algorithm,
+    pub key_by: KeyBy,
+    /// Only count POST, PUT, PATCH and DELETE requests
+    pub writes_only: bool,
+}
+
+impl RateLimit {
+    pub const fn per_minute(name: &'static str, limit: u32) -> Self {
+        Self::new(name, limit, Duration::from_secs(60))
+    }
+
+    pub const fn per_hour(name: &'static str, limit: u32) -> Self {
+        Self::new(name, limit, Duration::from_secs(60 * 60))
+    }
+
+    pub const fn new(name: &'static str, limit: u32, period: Duration) -> Self {
+        Self {
+            name,
+            limit,
+            period,
+            algorithm: Algorithm::TokenBucket,
+            key_by: KeyBy::Ip,
+            writes_only: false,
+        }
+    }
+
+    pub const fn sliding_window(mut self) -> Self {
+        self.algorithm = Algorithm::SlidingWindow;
+        self
+    }
+
+    pub const fn keyed_by(mut self, key_by: KeyBy) -> Self {
+        self.key_by = key_by;
+        self
+    }
+
+    pub const fn writes_only(mut self) -> Self {
+        self.writes_only = true;
+        self
+    }
+
+    fn period_ms(&self) -> u64 {
+        self.period.as_millis().max(1) as u64
+    }
+
+    fn counts(&self, method: &Method) -> bool {
+        !self.writes_only || !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+    }
+}
+
+/// The outcome of counting a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the limit is fully available again
+    pub reset_after: u64,
+    /// Seconds until the next request will be allowed, when this one was not
+    pub retry_after: Option<u64>,
+}
+
+/// Middleware enforcing `limit`. Responses carry `RateLimit-*` headers and
+/// rejected requests get a 429 with `Retry-After`.
+pub async fn enforce(
+    State(limit): State<RateLimit>,
+    Extension(limiter): Extension<RateLimiter>,
+    Extension(trusted_proxies): Extension<TrustedProxies>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limit.counts(request.method()) {
+        return next.run(request).await;
+    }
+
+    let key = client_key(&request, limit.key_by, trusted_proxies);
+    let Some(decision) = limiter.check(&limit, &key).await else {
+        return next.run(request).await;
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        tracing::warn!(limit = limit.name, key = %key, "Rate limit exceeded");
+        AppError::RateLimited { retry_after: decision.retry_after }.into_response()
+    };
+    set_headers(response.headers_mut(), &limit, &decision);
+    response
+}
+
+fn set_headers(headers: &mut HeaderMap, limit: &RateLimit, decision: &Decision) {
+    let values = [
+        (RATELIMIT_LIMIT, decision.limit.to_string()),
+        (RATELIMIT_REMAINING, decision.remaining.to_string()),
+        (RATELIMIT_RESET, decision.reset_after.to_string()),
+        (RATELIMIT_POLICY, format!("{};w={}", limit.limit, limit.period.as_secs())),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+fn client_key(request: &Request, key_by: KeyBy, trusted_proxies: TrustedProxies) -> String {
+    let ip = || {
+        let ip = trusted_proxies.client_ip(request.headers(), request.extensions());
+        format!("ip:{}", ip.as_deref().unwrap_or("unknown"))
+    };
+    match key_by {
+        KeyBy::Ip => ip(),
+        KeyBy::User => request
+            .extensions()
+            .get::<AuthSession>()
+            .and_then(|auth_session| auth_session.user.as_ref())
+            .map(|user| format!("user:{}", user.id()))
+            .unwrap_or_else(ip),
+        // Hashed so tokens are never stored in the rate limit store
+        KeyBy::ApiKey => bearer_token(request.headers())
+            .map(|token| format!("api_key:{}", hex::encode(Sha256::digest(token.as_bytes()))))
+            .unwrap_or_else(ip),
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_key, KeyBy, RateLimit};
+    use crate::proxy::TrustedProxies;
+    use axum::body::Body;
+    use axum::extract::{ConnectInfo, Request};
+    use axum::http::Method;
+    use std::net::SocketAddr;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().uri("/login");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        let address: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(address));
+        request
+    }
+
+    #[test]
+    fn requests_are_keyed_by_peer_ip() {
+        let request = request(&[("X-Forwarded-For", "203.0.113.9")]);
+        assert_eq!(client_key(&request, KeyBy::Ip, TrustedProxies::new(0)), "ip:10.0.0.1");
+    }
+
+    #[test]
+    fn forwarded_for_is_used_when_trusted() {
+        // The client sent the first entry; the proxy appended the second
+        let request = request(&[("X-Forwarded-For", "198.51.100.7, 203.0.113.9")]);
+        assert_eq!(client_key(&request, KeyBy::Ip, TrustedProxies::new(1)), "ip:203.0.113.9");
+    }
+
+    #[test]
+    fn forwarded_for_entries_are_counted_from_the_right() {
+        let request = request(&[("X-Forwarded-For", "198.51.100.7, 203.0.113.9, 10.0.0.2")]);
+        assert_eq!(client_key(&request, KeyBy::Ip, TrustedProxies::new(2)), "ip:203.0.113.9");
+        // Fewer entries than proxies means the header didn't come through them
+        assert_eq!(client_key(&request, KeyBy::Ip, TrustedProxies::new(4)), "ip:10.0.0.1");
+    }
+
+    #[test]
+    fn api_keys_are_hashed() {
+        let request = request(&[("Authorization", "Bearer secret-token")]);
+        let key = client_key(&request, KeyBy::ApiKey, TrustedProxies::new(0));
+        assert!(key.starts_with("api_key:"));
+        assert!(!key.contains("secret-token"));
+    }
+
+    #[test]
+    fn anonymous_requests_fall_back_to_the_ip() {
+        let request = request(&[]);
+        assert_eq!(client_key(&request, KeyBy::User, TrustedProxies::new(0)), "ip:10.0.0.1");
+        assert_eq!(client_key(&request, KeyBy::ApiKey, TrustedProxies::new(0)), "ip:10.0.0.1");
+    }
+
+    #[test]
+    fn writes_only_limits_skip_reads() {
+        let limit = RateLimit::per_minute("test", 1).writes_only();
+        assert!(!limit.counts(&Method::GET));
+        assert!(limit.counts(&Method::POST));
+        assert!(RateLimit::per_minute("test", 1).counts(&Method::GET));
+    }
+}
@@ -0,0 +1,188 @@
+//! Rate limit counters, kept in memory or in Redis
+use redis::aio::ConnectionManager;
+use redis::Script;
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::algorithms::{self, SlidingWindow, TokenBucket};
+use super::{Algorithm, Decision, RateLimit};
+use crate::configuration::{RateLimitSettings, RateLimitStore};
+
+/// Idle entries are pruned from the in-memory store every this many checks
+const PRUNE_EVERY: u64 = 1024;
+
+/// Mirrors `TokenBucket::take`. Returns whether the request is allowed and
+/// the tokens left, as a string since Redis truncates Lua numbers.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local period = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(state[1]) or limit
+local updated = tonumber(state[2]) or now
+tokens = math.min(limit, tokens + math.max(0, now - updated) * limit / period)
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', now)
+redis.call('PEXPIRE', KEYS[1], period)
+return {allowed, tostring(tokens)}
+"#;
+
+/// Mirrors `SlidingWindow::hit` with one counter per window.
+/// KEYS[1] is the current window and KEYS[2] the previous one.
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local limit = tonumber(ARGV[1])
+local period = tonumber(ARGV[2])
+local elapsed = tonumber(ARGV[3])
+local current = tonumber(redis.call('GET', KEYS[1]) or '0')
+local previous = tonumber(redis.call('GET', KEYS[2]) or '0')
+local allowed = 0
+if previous * (1 - elapsed / period) + current + 1 <= limit then
+    current = redis.call('INCR', KEYS[1])
+    redis.call('PEXPIRE', KEYS[1], period * 2)
+    allowed = 1
+end
+return {allowed, current, previous}
+"#;
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    backend: Backend,
+}
+
+#[derive(Clone)]
+enum Backend {
+    Disabled,
+    Memory(Arc<Mutex<MemoryStore>>),
+    Redis(ConnectionManager),
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    entries: HashMap<String, MemoryEntry>,
+    checks: u64,
+}
+
+struct MemoryEntry {
+    state: State,
+    expires_ms: u64,
+}
+
+enum State {
+    TokenBucket(TokenBucket),
+    SlidingWindow(SlidingWindow),
+}
+
+impl RateLimiter {
+    /// Connects to Redis when it is the configured store
+    pub async fn from_settings(
+        settings: &RateLimitSettings,
+        redis_uri: &Secret<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let backend = match (settings.enabled, settings.store) {
+            (false, _) => Backend::Disabled,
+            (true, RateLimitStore::Memory) => Backend::Memory(Default::default()),
+            (true, RateLimitStore::Redis) => {
+                let client = redis::Client::open(redis_uri.expose_secret().as_str())?;
+                Backend::Redis(ConnectionManager::new(client).await?)
+            },
+        };
+
+        Ok(Self { backend })
+    }
+
+    /// Counts a request against `limit`. Returns `None` when rate limiting is
+    /// disabled or the store failed, in which case the request is let through.
+    pub(super) async fn check(&self, limit: &RateLimit, key: &str) -> Option<Decision> {
+        let key = format!("rate_limit:{}:{}", limit.name, key);
+        let now_ms = now_ms();
+        match &self.backend {
+            Backend::Disabled => None,
+            Backend::Memory(store) => {
+                let mut store = store.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                Some(store.check(limit, key, now_ms))
+            },
+            Backend::Redis(connection) => {
+                match check_redis(connection.clone(), limit, &key, now_ms).await {
+                    Ok(decision) => Some(decision),
+                    Err(err) => {
+                        tracing::error!(error = %err, limit = limit.name, "Rate limit check failed");
+                        None
+                    },
+                }
+            },
+        }
+    }
+}
+
+impl MemoryStore {
+    fn check(&mut self, limit: &RateLimit, key: String, now_ms: u64) -> Decision {
+        self.checks += 1;
+        if self.checks.is_multiple_of(PRUNE_EVERY) {
+            self.entries.retain(|_, entry| entry.expires_ms > now_ms);
+        }
+
+        let period_ms = limit.period_ms();
+        let entry = self.entries.entry(key).or_insert_with(|| MemoryEntry {
+            state: match limit.algorithm {
+                Algorithm::TokenBucket => State::TokenBucket(TokenBucket::full(limit.limit, now_ms)),
+                Algorithm::SlidingWindow => State::SlidingWindow(SlidingWindow::default()),
+            },
+            expires_ms: 0,
+        });
+        entry.expires_ms = now_ms + 2 * period_ms;
+        match &mut entry.state {
+            State::TokenBucket(bucket) => bucket.take(limit.limit, period_ms, now_ms),
+            State::SlidingWindow(window) => window.hit(limit.limit, period_ms, now_ms),
+        }
+    }
+}
+
+async fn check_redis(
+    mut connection: ConnectionManager,
+    limit: &RateLimit,
+    key: &str,
+    now_ms: u64,
+) -> Result<Decision, redis::RedisError> {
+    let period_ms = limit.period_ms();
+    match limit.algorithm {
+        Algorithm::TokenBucket => {
+            let (allowed, tokens): (u8, String) = Script::new(TOKEN_BUCKET_SCRIPT)
+                .key(key)
+                .arg(limit.limit)
+                .arg(period_ms)
+                .arg(now_ms)
+                .invoke_async(&mut connection)
+                .await?;
+            let tokens = tokens.parse().unwrap_or(0.0);
+            Ok(algorithms::token_bucket_decision(limit.limit, period_ms, tokens, allowed == 1))
+        },
+        Algorithm::SlidingWindow => {
+            let window_start_ms = SlidingWindow::window_start(period_ms, now_ms);
+            let elapsed_ms = now_ms - window_start_ms;
+            let (allowed, current, previous): (u8, u32, u32) = Script::new(SLIDING_WINDOW_SCRIPT)
+                .key(format!("{}:{}", key, window_start_ms))
+                .key(format!("{}:{}", key, window_start_ms.saturating_sub(period_ms)))
+                .arg(limit.limit)
+                .arg(period_ms)
+                .arg(elapsed_ms)
+                .invoke_async(&mut connection)
+                .await?;
+            Ok(algorithms::sliding_window_decision(
+                limit.limit, period_ms, elapsed_ms, previous, current, allowed == 1,
+            ))
+        },
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,214 @@
+//! src/realtime.rs
+//! Pushes events to the open browser tabs of a user over `/events` (SSE) or
+//! `/events/ws` (WebSocket). Events are published with Postgres `NOTIFY` and
+//! every app instance `LISTEN`s, so an event published by one instance
+//! reaches the user's connections on all of them.
+//!
+//! ```ignore
+//! state.realtime.publish(user.id(), Event::toast(ToastLevel::Success, "Export ready")).await;
+//! ```
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// The Postgres channel events are published on
+pub const CHANNEL: &str = "realtime";
+/// Events buffered per user before slow connections start missing some
+const BUFFER: usize = 64;
+/// How long to wait before listening again after the connection was lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToastLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// Something to show in the user's open tabs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A new in-app notification, with the user's unread count after it
+    Notification {
+        id: Uuid,
+        title: String,
+        body: Option<String>,
+        url: Option<String>,
+        unread_count: u64,
+    },
+    /// Progress of a background job, from 0 to 100
+    JobProgress { job_id: Uuid, progress: u8, message: Option<String> },
+    /// A short message like a flash message
+    Toast { level: ToastLevel, message: String },
+}
+
+impl Event {
+    pub fn toast(level: ToastLevel, message: impl Into<String>) -> Self {
+        Event::Toast { level, message: message.into() }
+    }
+
+    /// The SSE event name, which is the `type` of the JSON payload
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Notification { .. } => "notification",
+            Event::JobProgress { .. } => "job_progress",
+            Event::Toast { .. } => "toast",
+        }
+    }
+}
+
+/// What goes over the Postgres channel
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    user_id: Uuid,
+    event: Event,
+}
+
+/// Hands out per-user subscriptions and delivers the events received from
+/// Postgres to them
+#[derive(Clone)]
+pub struct Hub {
+    db: PgPool,
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    users: HashMap<Uuid, broadcast::Sender<Event>>,
+    closed: bool,
+}
+
+impl Hub {
+    pub fn new(db: PgPool) -> Self {
+        Self { db, inner: Default::default() }
+    }
+
+    /// Starts listening on `CHANNEL`. Returns once the listener is connected
+    /// so no event published after startup is missed.
+    pub async fn listen(&self) -> Result<tokio::task::JoinHandle<()>, sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.db).await?;
+        listener.listen(CHANNEL).await?;
+
+        let hub = self.clone();
+        Ok(tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => match serde_json::from_str::<Message>(notification.payload()) {
+                        Ok(message) => hub.deliver(message.user_id, message.event),
+                        Err(err) => tracing::error!(error = %err, "Invalid realtime message"),
+                    },
+                    Err(err) => {
+                        tracing::error!(error = %err, "Lost the realtime listener connection");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    },
+                }
+            }
+        }))
+    }
+
+    /// Sends `event` to every open connection of the user, on any instance
+    pub async fn publish(&self, user_id: Uuid, event: Event) {
+        if let Err(err) = notify(&self.db, user_id, &event).await {
+            tracing::error!(error = %err, "Failed to publish a realtime event, delivering it locally");
+            self.deliver(user_id, event);
+        }
+    }
+
+    /// Receives the events for the user. The receiver closes when the hub does.
+    pub fn subscribe(&self, user_id: Uuid) -> broadcast::Receiver<Event> {
+        let mut inner = self.inner.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if inner.closed {
+            // Dropping the sender right away closes the receiver
+            return broadcast::channel(1).1;
+        }
+        // Users whose connections have all gone away are dropped here
+        inner.users.retain(|_, sender| sender.receiver_count() > 0);
+        inner
+            .users
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(BUFFER).0)
+            .subscribe()
+    }
+
+    /// Ends every connection, for a graceful shutdown
+    pub fn close(&self) {
+        let mut inner = self.inner.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.closed = true;
+        inner.users.clear();
+    }
+
+    fn deliver(&self, user_id: Uuid, event: Event) {
+        let inner = self.inner.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(sender) = inner.users.get(&user_id) {
+            // Fails only when there are no receivers left, which is fine
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Publishes an event with `NOTIFY`. Prefer `Hub::publish`, which falls back
+/// to local delivery when Postgres is unavailable.
+pub async fn notify(db: &PgPool, user_id: Uuid, event: &Event) -> Result<(), anyhow::Error> {
+    let payload = serde_json::to_string(&Message { user_id, event: event.clone() })?;
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(CHANNEL)
+        .bind(payload)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Hub, ToastLevel};
+    use sqlx::postgres::PgPoolOptions;
+    use tokio::sync::broadcast::error::RecvError;
+    use uuid::Uuid;
+
+    fn hub() -> Hub {
+        let db = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        Hub::new(db)
+    }
+
+    #[tokio::test]
+    async fn events_only_reach_their_user() {
+        let hub = hub();
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut alice_events = hub.subscribe(alice);
+        let mut bob_events = hub.subscribe(bob);
+
+        let event = Event::toast(ToastLevel::Info, "Hello");
+        hub.deliver(alice, event.clone());
+
+        assert_eq!(alice_events.recv().await.unwrap(), event);
+        assert!(bob_events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn closing_the_hub_ends_subscriptions() {
+        let hub = hub();
+        let user_id = Uuid::new_v4();
+        let mut events = hub.subscribe(user_id);
+
+        hub.close();
+        assert!(matches!(events.recv().await, Err(RecvError::Closed)));
+        assert!(matches!(hub.subscribe(user_id).recv().await, Err(RecvError::Closed)));
+    }
+
+    #[test]
+    fn events_are_tagged_with_their_name() {
+        let event = Event::toast(ToastLevel::Success, "Saved");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.name());
+        assert_eq!(json["level"], "success");
+    }
+}
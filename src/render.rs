@@ -0,0 +1,230 @@
+//! src/render.rs
+//! The `Render` extractor renders pages with the context every page shares
+//! already filled in, so handlers only insert what is specific to them:
+//!
+//! - `current_user`: `id`, `email` and `timezone` of the logged in user, unset otherwise
+//! - `roles`: names of the current user's roles, sorted
+//! - `unread_notifications`: the current user's unread count, unset when logged out
+//! - `timezone`: the current user's timezone, `UTC` when logged out
+//! - `flashes`: the pending flash messages, each with a `level` and `message`
+//! - `csrf_token`, `csp_nonce`
+//! - `environment`: `local` or `production`
+//! - `request_path`: the path of the page, for highlighting the current link
+//! - `locale`: the language the page is shown in, for `t(key=.., lang=locale)`
+//! - `locales`: every language there is a catalog for, each with a `code` and `name`
+//!
+//! Values the handler inserts take precedence.
+//!
+//! For htmx requests only the template's `content` block is rendered,
+//! followed by the flash messages as an out-of-band swap.
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use axum_login::{AuthUser, AuthzBackend};
+use axum_messages::{Level, Messages};
+use serde::Serialize;
+use validator::ValidationErrors;
+use crate::csrf::CsrfToken;
+use crate::error::AppError;
+use crate::error::FieldErrors;
+use crate::htmx::HxRequest;
+use crate::i18n::{Locale, SharedI18n};
+use crate::notifications;
+use crate::security_headers::CspNonce;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams, SharedTera};
+use crate::user::AuthSession;
+use crate::utils::e500;
+use crate::constants::html_templates;
+
+/// The block htmx requests get instead of the whole page
+pub const CONTENT_BLOCK: &str = "content";
+
+#[derive(Debug, Serialize)]
+struct CurrentUser<'a> {
+    id: uuid::Uuid,
+    email: &'a str,
+    timezone: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Flash {
+    level: &'static str,
+    message: String,
+}
+
+impl Flash {
+    fn new(level: Level, message: String) -> Self {
+        let level = match level {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Success => "success",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        Self { level, message }
+    }
+}
+
+impl From<axum_messages::Message> for Flash {
+    fn from(message: axum_messages::Message) -> Self {
+        Self::new(message.level, message.message)
+    }
+}
+
+/// Renders templates with the shared page context. Handlers using it should
+/// push flash messages through `messages()` rather than also extracting
+/// `Messages`, which would load the session's messages a second time.
+pub struct Render {
+    tera: SharedTera,
+    globals: tera::Context,
+    messages: Messages,
+    hx: HxRequest,
+    i18n: SharedI18n,
+    locale: Locale,
+    /// Shown on this page rather than the next one
+    flashes_now: Vec<Flash>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Render
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(app_state) = Extension::<AppState>::from_request_parts(parts, state)
+            .await
+            .map_err(e500)?;
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, message)| e500(message))?;
+        let messages = Messages::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, message)| e500(message))?;
+        let csrf_token = CsrfToken::from_request_parts(parts, state).await?;
+        let csp_nonce = CspNonce::from_request_parts(parts, state).await?;
+        let hx = HxRequest::from_headers(&parts.headers);
+        let Ok(locale) = Locale::from_request_parts(parts, state).await;
+        // Nested routers see the path without their prefix
+        let request_path = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path().to_string(),
+            None => parts.uri.path().to_string(),
+        };
+
+        let mut globals = tera::Context::new();
+        let mut roles = Vec::new();
+        let mut timezone = "UTC";
+        if let Some(user) = &auth_session.user {
+            let (user_roles, unread_notifications) = tokio::try_join!(
+                async { auth_session.backend.get_group_permissions(user).await.map_err(e500) },
+                async { notifications::unread_count(&app_state.db, user.id()).await.map_err(e500) },
+            )?;
+            roles = user_roles.into_iter().map(|role| role.name).collect();
+            roles.sort();
+            timezone = &user.timezone;
+            globals.insert("current_user", &CurrentUser { id: user.id(), email: &user.email, timezone });
+            globals.insert("unread_notifications", &unread_notifications);
+        }
+        globals.insert("roles", &roles);
+        globals.insert("timezone", timezone);
+        globals.insert("csrf_token", csrf_token.as_ref());
+        globals.insert("csp_nonce", csp_nonce.as_ref());
+        globals.insert("environment", app_state.environment.as_str());
+        globals.insert("request_path", &request_path);
+        globals.insert("locale", &locale);
+        globals.insert("locales", &app_state.i18n.locales());
+
+        Ok(Self {
+            tera: app_state.tera,
+            globals,
+            messages,
+            hx,
+            i18n: app_state.i18n,
+            locale,
+            flashes_now: Vec::new(),
+        })
+    }
+}
+
+impl Render {
+    /// The session's flash messages, for pushing new ones
+    pub fn messages(&self) -> Messages {
+        self.messages.clone()
+    }
+
+    /// The language the page is shown in
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    /// The message `key` in the page's language, for flash messages
+    pub fn t(&self, key: &str) -> String {
+        self.i18n.message(&self.locale, key)
+    }
+
+    /// `t` for messages with variables: `render.t_with("login-succeeded", &json!({ "email": email }))`
+    pub fn t_with(&self, key: &str, args: &serde_json::Value) -> String {
+        self.i18n.translate(&self.locale, key, args)
+    }
+
+    /// Validation errors in the page's language, by field
+    pub fn field_errors(&self, errors: &ValidationErrors) -> FieldErrors {
+        self.i18n.field_errors(&self.locale, errors)
+    }
+
+    /// Adds a flash message to the page being rendered, for responses that
+    /// aren't followed by another page, like htmx form submissions
+    pub fn with_flash(mut self, level: Level, message: impl Into<String>) -> Self {
+        self.flashes_now.push(Flash::new(level, message.into()));
+        self
+    }
+
+    /// Renders a template with the shared context and `context` on top, or
+    /// only its `content` block for htmx requests. Flash messages are only
+    /// taken from the session here, so a handler that ends up redirecting
+    /// leaves them for the next page.
+    pub fn render(&self, template_path: &'static str, context: &tera::Context) -> Result<String, AppError> {
+        let mut flashes: Vec<Flash> = self.messages.clone().map(Flash::from).collect();
+        flashes.extend(self.flashes_now.iter().cloned());
+        let mut globals = self.globals.clone();
+        globals.insert("flashes", &flashes);
+        if !self.hx.wants_partial() {
+            return render_content(
+                &RenderTemplateParams::new(template_path, &self.tera)
+                .with_globals(&globals)
+                .with_context(context)
+            );
+        }
+
+        let content = render_content(
+            &RenderTemplateParams::new(template_path, &self.tera)
+            .with_globals(&globals)
+            .with_context(context)
+            .with_block(CONTENT_BLOCK)
+        )?;
+        // Sent even without flashes, so old ones are cleared
+        globals.insert("flashes_oob", &true);
+        let flashes = render_content(&RenderTemplateParams::new(html_templates::FLASHES, &self.tera).with_globals(&globals))?;
+        Ok(content + &flashes)
+    }
+
+    /// `render` as an HTML response, or the error page if rendering failed
+    pub fn page(&self, template_path: &'static str, context: &tera::Context) -> Response {
+        self.page_with_status(StatusCode::OK, template_path, context)
+    }
+
+    /// `page` with another status, like 422 for a form with invalid values
+    pub fn page_with_status(&self, status: StatusCode, template_path: &'static str, context: &tera::Context) -> Response {
+        match self.render(template_path, context) {
+            // Caches have to keep the full page, the htmx partial and each language apart
+            Ok(page) => (status, [(header::VARY, HeaderValue::from_static("HX-Request, Accept-Language"))], Html(page)).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::middleware;
+use axum_login::AuthUser;
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use time_tz::{timezones, TimeZone};
+use validator::{ValidationError, ValidationErrors};
+use crate::startup::AppState;
+use crate::render::Render;
+use crate::utils::e500;
+use crate::error::{AppError, FieldErrors};
+use crate::guards;
+use crate::htmx::{self, HxRequest};
+use crate::i18n::{self, I18n, Locale};
+
+use crate::user::AuthSession;
+use crate::notification_preferences::NotificationPreferences;
+use crate::api_tokens::{self, NewApiToken};
+use crate::notifications;
+use crate::audit::{self, AuditContext, NewAuditEvent};
+use crate::account_exports;
+use crate::account_deletion;
+use crate::notifications::NewNotification;
+use crate::domain::{AuditAction, NotificationKind};
+use crate::domain::ApiTokenScope;
+use crate::constants::{
+    html_templates,
+    route_paths,
+};
+
+// Unchecked checkboxes are not sent with the form, so a missing field means `false`.
+#[derive(Debug, Deserialize)]
+pub struct NotificationPreferencesForm {
+    #[serde(default)]
+    pub product_updates: bool,
+    #[serde(default)]
+    pub digests: bool,
+}
+
+impl From<NotificationPreferencesForm> for NotificationPreferences {
+    fn from(form: NotificationPreferencesForm) -> Self {
+        Self {
+            product_updates: form.product_updates,
+            digests: form.digests,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreferencesForm {
+    /// Empty to follow the browser
+    #[serde(default)]
+    pub locale: String,
+    /// IANA name, like `Europe/Berlin`
+    pub timezone: String,
+}
+
+impl PreferencesForm {
+    /// The chosen locale, `None` for the browser's, and the timezone's canonical name
+    fn parse(&self, i18n: &I18n) -> Result<(Option<Locale>, &'static str), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let locale = match self.locale.as_str() {
+            "" => Ok(None),
+            locale => i18n.supported(locale).map(Some).ok_or(()),
+        }
+            .map_err(|_| errors.add("locale", ValidationError::new("locale-invalid")));
+        let timezone = timezones::get_by_name(self.timezone.trim())
+            .map(|timezone| timezone.name())
+            .ok_or_else(|| errors.add("timezone", ValidationError::new("timezone-invalid")));
+        match (locale, timezone) {
+            (Ok(locale), Ok(timezone)) => Ok((locale, timezone)),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// The preferences form filled in with `locale` and `timezone`
+fn preferences_context(locale: &str, timezone: &str) -> tera::Context {
+    let mut timezones: Vec<&str> = timezones::iter().map(|timezone| timezone.name()).collect();
+    timezones.sort_unstable();
+    let mut context = tera::Context::new();
+    context.insert("preferred_locale", locale);
+    context.insert("preferred_timezone", timezone);
+    context.insert("timezones", &timezones);
+    context
+}
+
+/// Token lifetimes offered on the tokens page, in days. Empty means never.
+const TOKEN_EXPIRY_DAYS: &[i64] = &[7, 30, 90, 365];
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+/// Events shown on the security activity page
+const SECURITY_ACTIVITY_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountForm {
+    /// The current password, confirming the deletion
+    pub password: String,
+}
+
+fn format_time(time: time::OffsetDateTime) -> String {
+    time.format(&time::format_description::well_known::Rfc3339).unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewApiTokenForm {
+    pub name: String,
+    /// `read` or `write`, where `write` also allows reading
+    pub access: String,
+    #[serde(default)]
+    pub expires_in_days: String,
+}
+
+impl NewApiTokenForm {
+    /// Fails with the message key of the first invalid field
+    fn parse(self) -> Result<NewApiToken, &'static str> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+            return Err("api-token-name-required");
+        }
+
+        let scopes = match ApiTokenScope::try_from(self.access.as_str()) {
+            Ok(ApiTokenScope::Read) => vec![ApiTokenScope::Read],
+            Ok(ApiTokenScope::Write) => vec![ApiTokenScope::Read, ApiTokenScope::Write],
+            Err(_) => return Err("api-token-invalid-scope"),
+        };
+
+        let expires_at = match self.expires_in_days.as_str() {
+            "" => None,
+            days => match days.parse::<i64>() {
+                Ok(days) if TOKEN_EXPIRY_DAYS.contains(&days) => {
+                    Some(time::OffsetDateTime::now_utc() + time::Duration::days(days))
+                },
+                _ => return Err("api-token-invalid-expiry"),
+            },
+        };
+
+        Ok(NewApiToken { name, scopes, expires_at })
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(
+            route_paths::ACCOUNT_NOTIFICATIONS,
+            get(self::get::notifications).post(self::post::notifications),
+        )
+        .route(route_paths::ACCOUNT_SECURITY, get(self::get::security))
+        .route(
+            route_paths::ACCOUNT_PREFERENCES,
+            get(self::get::preferences).post(self::post::preferences),
+        )
+        .merge(session_routes())
+        .route_layer(middleware::from_fn(guards::require_login))
+}
+
+/// API tokens, data exports and account deletion can only be managed from a
+/// browser session
+fn session_routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ACCOUNT_TOKENS, get(self::get::tokens).post(self::post::create_token))
+        .route(route_paths::ACCOUNT_TOKEN_REVOKE, post(self::post::revoke_token))
+        .route(route_paths::ACCOUNT_EXPORT, get(self::get::export).post(self::post::request_export))
+        .route(route_paths::ACCOUNT_EXPORT_DOWNLOAD, get(self::get::download_export))
+        .route(route_paths::ACCOUNT_DELETE, get(self::get::delete).post(self::post::delete))
+        .route(route_paths::ACCOUNT_DELETE_CANCEL, post(self::post::cancel_delete))
+        .route_layer(middleware::from_fn(guards::require_session))
+}
+
+/// Renders the tokens page. `new_token` is the plain text of a token that
+/// was just created; it is only ever shown here.
+async fn render_tokens(
+    state: &AppState,
+    render: &Render,
+    user_id: uuid::Uuid,
+    new_token: Option<&str>,
+) -> Response {
+    let tokens = match api_tokens::list(&state.db, user_id).await.map_err(e500) {
+        Ok(tokens) => tokens,
+        Err(err) => return err.into_response(),
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("tokens", &tokens);
+    context.insert("new_token", &new_token);
+    context.insert("expiry_days", TOKEN_EXPIRY_DAYS);
+    render.page(html_templates::ACCOUNT_TOKENS, &context)
+}
+
+mod get {
+    use super::*;
+
+    pub async fn tokens(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        render_tokens(&state, &render, user.id(), None).await
+    }
+
+    pub async fn security(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let events = match audit::list_for_user(&state.db, user.id(), SECURITY_ACTIVITY_LIMIT).await.map_err(e500) {
+            Ok(events) => events,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("events", &events);
+        context.insert("user_id", &user.id());
+        render.page(html_templates::ACCOUNT_SECURITY, &context)
+    }
+
+    pub async fn notifications(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let preferences = match NotificationPreferences::fetch(&state.db, user.id()).await.map_err(e500) {
+            Ok(preferences) => preferences,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("preferences", &preferences);
+        context.insert("email_undeliverable", &user.email_undeliverable);
+        render.page(html_templates::ACCOUNT_NOTIFICATIONS, &context)
+    }
+
+    pub async fn preferences(
+        auth_session: AuthSession,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let mut context = preferences_context(user.locale.as_deref().unwrap_or_default(), &user.timezone);
+        context.insert("errors", &FieldErrors::new());
+        render.page(html_templates::ACCOUNT_PREFERENCES, &context)
+    }
+
+    pub async fn export(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let exports = match account_exports::list(&state.db, user.id()).await.map_err(e500) {
+            Ok(exports) => exports,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("exports", &exports);
+        context.insert("expiry_hours", &state.account_settings.export_expiry_hours);
+        render.page(html_templates::ACCOUNT_EXPORT, &context)
+    }
+
+    pub async fn download_export(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Path(export_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        match account_exports::find_archive(&state.db, user.id(), export_id).await.map_err(e500) {
+            Ok(Some(archive)) => {
+                let disposition = format!("attachment; filename=\"account-export-{}.zip\"", export_id);
+                (
+                    [
+                        (header::CONTENT_TYPE, "application/zip".to_string()),
+                        (header::CONTENT_DISPOSITION, disposition),
+                        (header::CACHE_CONTROL, "no-store".to_string()),
+                    ],
+                    archive,
+                ).into_response()
+            },
+            Ok(None) => {
+                render.messages().error(render.t("account-export-not-found"));
+                Redirect::to(route_paths::ACCOUNT_EXPORT).into_response()
+            },
+            Err(err) => err.into_response(),
+        }
+    }
+
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let scheduled_for = match account_deletion::scheduled_for(&state.db, user.id()).await.map_err(e500) {
+            Ok(scheduled_for) => scheduled_for,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("scheduled_for", &scheduled_for.map(format_time));
+        context.insert("grace_period_days", &state.account_settings.deletion_grace_period_days);
+        render.page(html_templates::ACCOUNT_DELETE, &context)
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn create_token(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Form(form): Form<NewApiTokenForm>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let new_token = match form.parse() {
+            Ok(new_token) => new_token,
+            Err(key) => {
+                render.messages().error(render.t_with(key, &serde_json::json!({ "max": MAX_TOKEN_NAME_LENGTH })));
+                return Redirect::to(route_paths::ACCOUNT_TOKENS).into_response();
+            },
+        };
+
+        match api_tokens::create(&state.db, user.id(), &new_token).await.map_err(e500) {
+            Ok(token) => render_tokens(&state, &render, user.id(), Some(token.expose())).await,
+            Err(err) => err.into_response(),
+        }
+    }
+
+    pub async fn revoke_token(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Path(token_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        match api_tokens::revoke(&state.db, user.id(), token_id).await.map_err(e500) {
+            Ok(true) => { render.messages().success(render.t("api-token-revoked")); },
+            Ok(false) => { render.messages().error(render.t("api-token-not-found")); },
+            Err(err) => return err.into_response(),
+        }
+
+        Redirect::to(route_paths::ACCOUNT_TOKENS).into_response()
+    }
+
+    pub async fn notifications(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Form(form): Form<NotificationPreferencesForm>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let preferences = NotificationPreferences::from(form);
+        if let Err(err) = preferences.save(&state.db, user.id()).await.map_err(e500) {
+            return err.into_response();
+        }
+        render.messages().success(render.t("notification-preferences-saved"));
+
+        Redirect::to(route_paths::ACCOUNT_NOTIFICATIONS).into_response()
+    }
+
+    pub async fn preferences(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        hx: HxRequest,
+        jar: CookieJar,
+        Form(form): Form<PreferencesForm>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let (locale, timezone) = match form.parse(&state.i18n) {
+            Ok(preferences) => preferences,
+            Err(errors) => {
+                let mut context = preferences_context(&form.locale, &form.timezone);
+                context.insert("errors", &render.field_errors(&errors));
+                return render.page_with_status(StatusCode::UNPROCESSABLE_ENTITY, html_templates::ACCOUNT_PREFERENCES, &context);
+            },
+        };
+        let saved = auth_session.backend.update_preferences(user.id(), locale.as_ref().map(AsRef::as_ref), timezone).await;
+        if let Err(err) = saved.map_err(e500) {
+            return err.into_response();
+        }
+        // Confirmed in the language just chosen
+        let message = match &locale {
+            Some(locale) => state.i18n.message(locale, "preferences-saved"),
+            None => render.t("preferences-saved"),
+        };
+        render.messages().success(message);
+
+        // A `?lang=` override would hide the new preference
+        (i18n::forget_override(jar), htmx::redirect(&hx, route_paths::ACCOUNT_PREFERENCES)).into_response()
+    }
+
+    pub async fn request_export(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        audit_context: AuditContext,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        match account_exports::request(&state.db, user.id()).await.map_err(e500) {
+            Ok(Some(_)) => {
+                let event = NewAuditEvent::new(AuditAction::DataExportRequested).actor(user.id()).target(user.id());
+                audit::record_or_log(&state.db, &event, &audit_context).await;
+                tokio::spawn(account_exports::process_pending(state.clone()));
+                render.messages().success(render.t("account-export-requested"));
+            },
+            Ok(None) => { render.messages().error(render.t("account-export-in-progress")); },
+            Err(err) => return err.into_response(),
+        }
+
+        Redirect::to(route_paths::ACCOUNT_EXPORT).into_response()
+    }
+
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        audit_context: AuditContext,
+        Form(form): Form<DeleteAccountForm>,
+    ) -> impl IntoResponse {
+        let Some(ref user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        match auth_session.backend.check_password(user, form.password).await.map_err(e500) {
+            Ok(true) => {},
+            Ok(false) => {
+                render.messages().error(render.t("incorrect-password"));
+                return Redirect::to(route_paths::ACCOUNT_DELETE).into_response();
+            },
+            Err(err) => return err.into_response(),
+        }
+
+        let grace_period_days = state.account_settings.deletion_grace_period_days;
+        let scheduled_for = match account_deletion::schedule(&state.db, user.id(), grace_period_days, &audit_context)
+            .await
+            .map_err(e500)
+        {
+            Ok(scheduled_for) => scheduled_for,
+            Err(err) => return err.into_response(),
+        };
+        let notification = NewNotification::new(NotificationKind::SecurityAlert, render.t("account-deletion-scheduled"))
+            .with_body(render.t_with("account-deletion-scheduled-body", &serde_json::json!({ "date": format_time(scheduled_for) })))
+            .with_url(route_paths::ACCOUNT_DELETE);
+        if let Err(err) = notifications::notify(&state, user.id(), notification).await.map_err(e500) {
+            return err.into_response();
+        }
+        render.messages().success(render.t("account-deletion-scheduled"));
+
+        Redirect::to(route_paths::ACCOUNT_DELETE).into_response()
+    }
+
+    pub async fn cancel_delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        audit_context: AuditContext,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        match account_deletion::cancel(&state.db, user.id(), &audit_context).await.map_err(e500) {
+            Ok(true) => { render.messages().success(render.t("account-deletion-cancelled")); },
+            Ok(false) => { render.messages().error(render.t("account-deletion-not-scheduled")); },
+            Err(err) => return err.into_response(),
+        }
+
+        Redirect::to(route_paths::ACCOUNT_DELETE).into_response()
+    }
+}
@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Json, Router,
+};
+use axum::Extension;
+use axum::middleware;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::render::Render;
+use crate::utils::e500;
+use crate::guards;
+
+use crate::suppressions;
+use crate::audit::{self, AuditFilter, AuditFilterForm};
+use crate::domain::AuditAction;
+use crate::error::FieldErrors;
+use crate::error::AppError;
+use crate::constants::{
+    html_templates,
+    roles,
+    route_paths,
+};
+
+const AUDIT_PER_PAGE: u32 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditPageQuery {
+    #[serde(default)]
+    pub page: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditExportQuery {
+    /// `csv` or `json`
+    #[serde(default)]
+    pub format: String,
+}
+
+fn parse_filter(form: &AuditFilterForm) -> Result<AuditFilter, AppError> {
+    AuditFilter::try_from(form).map_err(|errors| AppError::Validation { errors })
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ADMIN_SUPPRESSIONS, get(self::get::suppressions))
+        .route(route_paths::ADMIN_SUPPRESSION_CLEAR, post(self::post::clear_suppression))
+        .route(route_paths::ADMIN_AUDIT, get(self::get::audit))
+        .route(route_paths::ADMIN_AUDIT_EXPORT, get(self::get::audit_export))
+        .route_layer(middleware::from_fn_with_state(roles::ADMIN, guards::require_role))
+        .route_layer(middleware::from_fn(guards::require_login))
+}
+
+mod get {
+    use super::*;
+
+    pub async fn suppressions(
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let suppressions = match suppressions::list(&state.db).await.map_err(e500) {
+            Ok(suppressions) => suppressions,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("suppressions", &suppressions);
+        render.page(html_templates::ADMIN_SUPPRESSIONS, &context)
+    }
+
+    pub async fn audit(
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Query(form): Query<AuditFilterForm>,
+        Query(query): Query<AuditPageQuery>,
+    ) -> impl IntoResponse {
+        let filter = match parse_filter(&form) {
+            Ok(filter) => filter,
+            Err(err) => return err.into_response(),
+        };
+
+        let page = query.page.unwrap_or(1).max(1);
+        let offset = i64::from(page - 1) * i64::from(AUDIT_PER_PAGE);
+        let results = tokio::try_join!(
+            audit::list(&state.db, &filter, i64::from(AUDIT_PER_PAGE), offset),
+            audit::count(&state.db, &filter),
+        );
+        let (events, total) = match results.map_err(e500) {
+            Ok(results) => results,
+            Err(err) => return err.into_response(),
+        };
+        // Pagination and export links keep the current filters
+        let filter_query = match serde_urlencoded::to_string(&form).map_err(e500) {
+            Ok(filter_query) => filter_query,
+            Err(err) => return err.into_response(),
+        };
+        let actions: Vec<&str> = AuditAction::ALL.iter().map(|action| action.as_str()).collect();
+
+        let mut context = tera::Context::new();
+        context.insert("events", &events);
+        context.insert("filter", &form);
+        context.insert("filter_query", &filter_query);
+        context.insert("actions", &actions);
+        context.insert("total", &total);
+        context.insert("page", &page);
+        context.insert("has_next_page", &(u64::from(page) * u64::from(AUDIT_PER_PAGE) < total));
+        render.page(html_templates::ADMIN_AUDIT, &context)
+    }
+
+    /// Downloads the matching events, up to `audit::EXPORT_LIMIT`
+    pub async fn audit_export(
+        Extension(state): Extension<AppState>,
+        Query(form): Query<AuditFilterForm>,
+        Query(query): Query<AuditExportQuery>,
+    ) -> impl IntoResponse {
+        let filter = match parse_filter(&form) {
+            Ok(filter) => filter,
+            Err(err) => return err.into_response(),
+        };
+        if !matches!(query.format.as_str(), "csv" | "json") {
+            let mut errors = FieldErrors::new();
+            errors.add("format", "must be csv or json");
+            return AppError::Validation { errors }.into_response();
+        }
+
+        let events = match audit::list(&state.db, &filter, audit::EXPORT_LIMIT, 0).await.map_err(e500) {
+            Ok(events) => events,
+            Err(err) => return err.into_response(),
+        };
+        let disposition = format!("attachment; filename=\"audit-events.{}\"", query.format);
+        if query.format == "csv" {
+            (
+                [
+                    (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                audit::to_csv(&events),
+            ).into_response()
+        } else {
+            ([(header::CONTENT_DISPOSITION, disposition)], Json(events)).into_response()
+        }
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn clear_suppression(
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Path(suppression_id): Path<i32>,
+    ) -> impl IntoResponse {
+        match suppressions::clear(&state.db, suppression_id).await.map_err(e500) {
+            Ok(true) => { render.messages().success(render.t("suppression-cleared")); },
+            Ok(false) => { render.messages().error(render.t("suppression-not-found")); },
+            Err(err) => return err.into_response(),
+        }
+
+        Redirect::to(route_paths::ADMIN_SUPPRESSIONS).into_response()
+    }
+}
@@ -0,0 +1,178 @@
+use axum::{routing::get, Extension, Json, Router};
+use axum_login::AuthUser;
+use serde::{Deserialize, Serialize};
+use time_tz::{timezones, TimeZone};
+use utoipa::ToSchema;
+use crate::api_tokens::{self, ApiToken};
+use crate::constants::api_paths;
+use crate::error::{AppError, FieldErrors, ProblemDetails};
+use crate::i18n::I18n;
+use crate::notification_preferences::NotificationPreferences;
+use crate::startup::AppState;
+use crate::user::AuthSession;
+use crate::utils::e500;
+use super::envelope::{ApiJson, Envelope, Page, Pagination, PaginationParams};
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(
+            api_paths::NOTIFICATION_PREFERENCES,
+            get(notification_preferences).put(update_notification_preferences),
+        )
+        .route(api_paths::TOKENS, get(tokens))
+        .route(api_paths::PREFERENCES, get(preferences).put(update_preferences))
+}
+
+/// The language and timezone pages and emails are shown in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Preferences {
+    /// A supported locale like `de`, or `null` to follow the browser
+    pub locale: Option<String>,
+    /// IANA name, like `Europe/Berlin`
+    pub timezone: String,
+}
+
+impl Preferences {
+    /// The preferences with the locale and timezone in their canonical form
+    fn validate(&self, i18n: &I18n) -> Result<Self, AppError> {
+        let mut errors = FieldErrors::new();
+        let locale = match self.locale.as_deref() {
+            None => Ok(None),
+            Some(locale) => i18n.supported(locale).map(|locale| Some(locale.as_ref().to_string())).ok_or(()),
+        }
+            .map_err(|_| errors.add("locale", "is not a supported locale"));
+        let timezone = timezones::get_by_name(self.timezone.trim())
+            .map(|timezone| timezone.name().to_string())
+            .ok_or_else(|| errors.add("timezone", "is not a known timezone"));
+        match (locale, timezone) {
+            (Ok(locale), Ok(timezone)) => Ok(Self { locale, timezone }),
+            _ => Err(AppError::Validation { errors }),
+        }
+    }
+}
+
+/// Returns the email categories the user has opted in to
+#[utoipa::path(
+    get,
+    path = "/account/notification-preferences",
+    tag = "account",
+    responses(
+        (status = 200, description = "The user's notification preferences", body = Envelope<NotificationPreferences>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn notification_preferences(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+) -> Result<Json<Envelope<NotificationPreferences>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let preferences = NotificationPreferences::fetch(&state.db, user.id()).await.map_err(e500)?;
+    Ok(Envelope::new(preferences))
+}
+
+/// Replaces the user's notification preferences
+#[utoipa::path(
+    put,
+    path = "/account/notification-preferences",
+    tag = "account",
+    request_body = NotificationPreferences,
+    responses(
+        (status = 200, description = "The saved notification preferences", body = Envelope<NotificationPreferences>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The token does not have the write scope", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "The body is not valid", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_notification_preferences(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    ApiJson(preferences): ApiJson<NotificationPreferences>,
+) -> Result<Json<Envelope<NotificationPreferences>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    preferences.save(&state.db, user.id()).await.map_err(e500)?;
+    Ok(Envelope::new(preferences))
+}
+
+/// Lists the user's API tokens, newest first. Tokens themselves are never
+/// returned, only their prefixes.
+#[utoipa::path(
+    get,
+    path = "/account/tokens",
+    tag = "account",
+    params(PaginationParams),
+    responses(
+        (status = 200, description = "A page of the user's API tokens", body = Page<ApiToken>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid pagination parameters", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn tokens(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    pagination: Pagination,
+) -> Result<Json<Page<ApiToken>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let tokens = api_tokens::list_page(&state.db, user.id(), pagination.limit(), pagination.offset())
+        .await
+        .map_err(e500)?;
+    let total = api_tokens::count(&state.db, user.id()).await.map_err(e500)?;
+    Ok(Page::new(tokens, &pagination, total))
+}
+
+/// Returns the user's language and timezone
+#[utoipa::path(
+    get,
+    path = "/account/preferences",
+    tag = "account",
+    responses(
+        (status = 200, description = "The user's preferences", body = Envelope<Preferences>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn preferences(auth_session: AuthSession) -> Result<Json<Envelope<Preferences>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    Ok(Envelope::new(Preferences { locale: user.locale, timezone: user.timezone }))
+}
+
+/// Replaces the user's language and timezone
+#[utoipa::path(
+    put,
+    path = "/account/preferences",
+    tag = "account",
+    request_body = Preferences,
+    responses(
+        (status = 200, description = "The saved preferences", body = Envelope<Preferences>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The token does not have the write scope", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "The locale or timezone is not known", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_preferences(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    ApiJson(preferences): ApiJson<Preferences>,
+) -> Result<Json<Envelope<Preferences>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let preferences = preferences.validate(&state.i18n)?;
+    auth_session
+        .backend
+        .update_preferences(user.id(), preferences.locale.as_deref(), &preferences.timezone)
+        .await
+        .map_err(e500)?;
+    Ok(Envelope::new(preferences))
+}
@@ -0,0 +1,31 @@
+use axum::{
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+use crate::constants::{api_paths, html_templates, route_paths};
+use crate::render::Render;
+use super::ApiDoc;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(api_paths::OPENAPI, get(openapi))
+        .route(api_paths::DOCS, get(explorer))
+}
+
+/// The OpenAPI document, generated from the handlers and their types
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// A page listing the operations of the OpenAPI document. Requests made from
+/// it use the browser session, so it only works for logged in users.
+async fn explorer(
+    render: Render,
+) -> impl IntoResponse {
+    let mut context = tera::Context::new();
+    context.insert("api_base", route_paths::API_V1);
+    context.insert("openapi_url", &format!("{}{}", route_paths::API_V1, api_paths::OPENAPI));
+    render.page(html_templates::API_EXPLORER, &context)
+}
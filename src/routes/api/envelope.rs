@@ -0,0 +1,187 @@
+//! The shapes every API response shares. Single resources are wrapped in
+//! `{"data": ...}` and lists in `{"data": [...], "meta": ..., "links": ...}`.
+//! Errors are `application/problem+json` bodies (`ProblemDetails`).
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Query},
+    http::request::Parts,
+    Json,
+};
+use axum::extract::FromRequest;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use crate::error::{AppError, FieldErrors};
+
+pub const DEFAULT_PER_PAGE: u32 = 20;
+pub const MAX_PER_PAGE: u32 = 100;
+
+/// A single resource
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Envelope<T> {
+    pub data: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(data: T) -> Json<Self> {
+        Json(Self { data })
+    }
+}
+
+/// One page of a list
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub meta: PageMeta,
+    pub links: PageLinks,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PageMeta {
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+    pub total_pages: u32,
+}
+
+/// Links to this page and its neighbours. They carry only `page` and
+/// `per_page`; none of the lists take other query parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn new(data: Vec<T>, pagination: &Pagination, total: u64) -> Json<Self> {
+        let total_pages = total.div_ceil(u64::from(pagination.per_page)) as u32;
+        let link = |page: u32| format!("{}?page={}&per_page={}", pagination.path, page, pagination.per_page);
+        Json(Self {
+            data,
+            meta: PageMeta {
+                page: pagination.page,
+                per_page: pagination.per_page,
+                total,
+                total_pages,
+            },
+            links: PageLinks {
+                current: link(pagination.page),
+                next: (pagination.page < total_pages).then(|| link(pagination.page + 1)),
+                prev: (pagination.page > 1).then(|| link((pagination.page - 1).min(total_pages.max(1)))),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationParams {
+    /// Page number, starting at 1
+    #[param(minimum = 1, default = 1)]
+    pub page: Option<u32>,
+    /// Items per page
+    #[param(minimum = 1, maximum = 100, default = 20)]
+    pub per_page: Option<u32>,
+}
+
+/// The `page` and `per_page` query parameters, validated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+    /// Path of the request, for the page links
+    path: String,
+}
+
+impl Pagination {
+    fn new(params: PaginationParams, path: String) -> Result<Self, AppError> {
+        let mut errors = FieldErrors::new();
+        let page = params.page.unwrap_or(1);
+        if page < 1 {
+            errors.add("page", "must be at least 1");
+        }
+        let per_page = params.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            errors.add("per_page", format!("must be between 1 and {}", MAX_PER_PAGE));
+        }
+        if !errors.is_empty() {
+            return Err(AppError::Validation { errors });
+        }
+
+        Ok(Self { page, per_page, path })
+    }
+
+    pub fn limit(&self) -> i64 {
+        i64::from(self.per_page)
+    }
+
+    pub fn offset(&self) -> i64 {
+        i64::from(self.page - 1) * i64::from(self.per_page)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<PaginationParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| {
+                let mut errors = FieldErrors::new();
+                errors.add("query", rejection.body_text());
+                AppError::Validation { errors }
+            })?;
+        let path = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path().to_string(),
+            None => parts.uri.path().to_string(),
+        };
+
+        Self::new(params, path)
+    }
+}
+
+/// `axum::Json` with its rejections turned into problem+json validation errors
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct ApiJson<T>(pub T);
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, Pagination, PaginationParams};
+
+    fn pagination(page: u32, per_page: u32) -> Pagination {
+        let params = PaginationParams { page: Some(page), per_page: Some(per_page) };
+        Pagination::new(params, "/api/v1/things".to_string()).unwrap()
+    }
+
+    #[test]
+    fn pagination_defaults() {
+        let params = PaginationParams { page: None, per_page: None };
+        let pagination = Pagination::new(params, "/".to_string()).unwrap();
+        assert_eq!((pagination.page, pagination.per_page), (1, 20));
+        assert_eq!(pagination.offset(), 0);
+    }
+
+    #[test]
+    fn pagination_rejects_out_of_range_values() {
+        let params = PaginationParams { page: Some(0), per_page: Some(101) };
+        assert!(Pagination::new(params, "/".to_string()).is_err());
+    }
+
+    #[test]
+    fn page_links_point_at_neighbours() {
+        let page = Page::new(vec![(); 10], &pagination(2, 10), 25).0;
+        assert_eq!(page.meta.total_pages, 3);
+        assert_eq!(page.links.current, "/api/v1/things?page=2&per_page=10");
+        assert_eq!(page.links.next.as_deref(), Some("/api/v1/things?page=3&per_page=10"));
+        assert_eq!(page.links.prev.as_deref(), Some("/api/v1/things?page=1&per_page=10"));
+
+        let last = Page::<()>::new(vec![], &pagination(3, 10), 25).0;
+        assert_eq!(last.links.next, None);
+    }
+}
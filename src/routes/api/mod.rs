@@ -0,0 +1,126 @@
+//! src/routes/api/mod.rs
+//! The JSON API, nested under `/api/v1`. Requests are authenticated with the
+//! session cookie or an API token. The OpenAPI document is generated from the
+//! `#[utoipa::path]` attributes of the handlers and the `ToSchema` derives of
+//! their types, so adding an endpoint means adding it to `ApiDoc` as well.
+use axum::{middleware, Router};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use crate::api_tokens::ApiToken;
+use crate::error::{FieldErrors, ProblemDetails};
+use crate::guards;
+use crate::notification_preferences::NotificationPreferences;
+use crate::notifications::Notification;
+
+mod account;
+mod docs;
+mod envelope;
+mod notifications;
+mod users;
+
+use envelope::{PageLinks, PageMeta};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Axum Sass Template API", version = "1"),
+    servers((url = "/api/v1")),
+    paths(
+        users::me,
+        account::notification_preferences,
+        account::update_notification_preferences,
+        account::tokens,
+        account::preferences,
+        account::update_preferences,
+        notifications::list,
+        notifications::mark_read,
+        notifications::mark_all_read,
+    ),
+    components(schemas(
+        ProblemDetails,
+        FieldErrors,
+        PageMeta,
+        PageLinks,
+        users::CurrentUser,
+        NotificationPreferences,
+        ApiToken,
+        account::Preferences,
+        Notification,
+    )),
+    modifiers(&SecuritySchemes),
+    security(("bearer" = []), ("session" = [])),
+    tags(
+        (name = "users", description = "The logged in user"),
+        (name = "account", description = "Account settings"),
+        (name = "notifications", description = "In-app notifications"),
+    ),
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("A personal API token from /account/tokens"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "id",
+                "The browser session. Requests other than GET also need an X-CSRF-Token header.",
+            ))),
+        );
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .merge(docs::routes())
+        .merge(
+            Router::new()
+                .merge(users::routes())
+                .merge(account::routes())
+                .merge(notifications::routes())
+                .route_layer(middleware::from_fn(guards::require_login)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiDoc;
+    use crate::constants::api_paths;
+    use utoipa::OpenApi;
+
+    #[test]
+    fn spec_paths_match_the_routes() {
+        let spec = ApiDoc::openapi();
+        let mut documented: Vec<&str> = spec.paths.paths.keys().map(String::as_str).collect();
+        documented.sort();
+        // The spec writes path parameters as `{id}`, the router as `:id`
+        let routed = [
+            api_paths::USERS_ME,
+            api_paths::NOTIFICATION_PREFERENCES,
+            api_paths::TOKENS,
+            api_paths::PREFERENCES,
+            api_paths::NOTIFICATIONS,
+            api_paths::NOTIFICATION_READ,
+            api_paths::NOTIFICATIONS_READ_ALL,
+        ];
+        let mut routed: Vec<String> = routed.iter().map(|path| path.replace(":id", "{id}")).collect();
+        routed.sort();
+        assert_eq!(documented, routed);
+    }
+
+    #[test]
+    fn spec_is_openapi_3_1() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+    }
+}
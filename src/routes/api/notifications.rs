@@ -0,0 +1,103 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use axum_login::AuthUser;
+use uuid::Uuid;
+use crate::constants::api_paths;
+use crate::error::{AppError, ProblemDetails};
+use crate::notifications::{self, Notification};
+use crate::startup::AppState;
+use crate::user::AuthSession;
+use crate::utils::e500;
+use super::envelope::{Envelope, Page, Pagination, PaginationParams};
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(api_paths::NOTIFICATIONS, get(list))
+        .route(api_paths::NOTIFICATION_READ, post(mark_read))
+        .route(api_paths::NOTIFICATIONS_READ_ALL, post(mark_all_read))
+}
+
+/// Lists the user's notifications, newest first
+#[utoipa::path(
+    get,
+    path = "/notifications",
+    tag = "notifications",
+    params(PaginationParams),
+    responses(
+        (status = 200, description = "A page of the user's notifications", body = Page<Notification>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid pagination parameters", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn list(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    pagination: Pagination,
+) -> Result<Json<Page<Notification>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let (notifications, total) = tokio::try_join!(
+        notifications::list(&state.db, user.id(), pagination.limit(), pagination.offset()),
+        notifications::count(&state.db, user.id()),
+    )
+        .map_err(e500)?;
+    Ok(Page::new(notifications, &pagination, total))
+}
+
+/// Marks a notification read. Marking it again keeps the first read time.
+#[utoipa::path(
+    post,
+    path = "/notifications/{id}/read",
+    tag = "notifications",
+    params(("id" = Uuid, Path, description = "The notification's id")),
+    responses(
+        (status = 200, description = "The notification", body = Envelope<Notification>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The token does not have the write scope", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "The user has no such notification", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn mark_read(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    Path(notification_id): Path<Uuid>,
+) -> Result<Json<Envelope<Notification>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let notification = notifications::mark_read(&state.db, user.id(), notification_id)
+        .await
+        .map_err(e500)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Envelope::new(notification))
+}
+
+/// Marks all of the user's notifications read
+#[utoipa::path(
+    post,
+    path = "/notifications/read-all",
+    tag = "notifications",
+    responses(
+        (status = 204, description = "All notifications are read"),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The token does not have the write scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn mark_all_read(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+) -> Result<StatusCode, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    notifications::mark_all_read(&state.db, user.id()).await.map_err(e500)?;
+    Ok(StatusCode::NO_CONTENT)
+}
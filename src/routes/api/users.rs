@@ -0,0 +1,57 @@
+use axum::{routing::get, Json, Router};
+use axum_login::{AuthUser, AuthzBackend};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::constants::api_paths;
+use crate::error::{AppError, ProblemDetails};
+use crate::user::AuthSession;
+use crate::utils::e500;
+use super::envelope::Envelope;
+
+/// The logged in user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CurrentUser {
+    pub id: Uuid,
+    pub email: String,
+    /// Set when email to this address bounced or was marked as spam
+    pub email_undeliverable: bool,
+    pub roles: Vec<String>,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new().route(api_paths::USERS_ME, get(me))
+}
+
+/// Returns the user the request is authenticated as
+#[utoipa::path(
+    get,
+    path = "/users/me",
+    tag = "users",
+    responses(
+        (status = 200, description = "The logged in user", body = Envelope<CurrentUser>),
+        (status = 401, description = "Not logged in", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn me(auth_session: AuthSession) -> Result<Json<Envelope<CurrentUser>>, AppError> {
+    let Some(user) = auth_session.user else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let mut roles: Vec<String> = auth_session
+        .backend
+        .get_group_permissions(&user)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|role| role.name)
+        .collect();
+    roles.sort();
+
+    Ok(Envelope::new(CurrentUser {
+        id: user.id(),
+        email: user.email,
+        email_undeliverable: user.email_undeliverable,
+        roles,
+    }))
+}
@@ -1,36 +1,41 @@
 use axum::{
     extract::Query,
     http::StatusCode,
-    response::{IntoResponse, Redirect},
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Router,
 };
 use axum::Extension;
-use axum::response::Html;
-use axum_messages::Messages;
+use axum_login::{tower_sessions::Session, AuthUser};
+use axum_messages::Level;
+use crate::error::FieldErrors;
+use validator::{ValidationError, ValidationErrors};
+use crate::htmx::{self, HxRequest};
 use serde::Deserialize;
 use crate::startup::AppState;
-use crate::template_helpers::{render_content, RenderTemplateParams};
+use crate::render::Render;
 use secrecy::Secret;
 use crate::utils::e500;
 use crate::telemetry;
+use crate::audit::{self, AuditContext, NewAuditEvent};
 use password_auth::generate_hash;
 
 use crate::user::{AuthSession, Credentials};
-use crate::domain::{NewUser, UserEmail, UserPassword};
+use crate::domain::{AuditAction, NewUser, SafeRedirect, UserEmail, UserPassword};
 use crate::emailer;
+use crate::session;
 use crate::constants::{
     html_templates,
     route_paths,
     email_templates,
-    strings,
 };
 
 // This allows us to extract the "next" field from the query string. We use this
 // to redirect after log in.
 #[derive(Debug, Deserialize)]
 pub struct NextUrl {
-    next: Option<String>,
+    #[serde(default, deserialize_with = "SafeRedirect::deserialize_lenient")]
+    next: Option<SafeRedirect>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,14 +46,18 @@ pub struct RegistrationForm {
 
 /// This runs validations on RegistrationForm. It tries to create the NewUser
 /// struct with the values passed in from RegistrationForm.
-/// Validations are inside of the NewUser file
+/// Every field is checked, so each invalid one gets its own message.
 impl TryFrom<RegistrationForm> for NewUser {
-    type Error = String;
+    type Error = ValidationErrors;
 
     fn try_from(value: RegistrationForm) -> Result<Self, Self::Error> {
-        let email = UserEmail::parse(value.email)?;
-        let password = UserPassword::parse(value.password)?;
-        Ok(Self { email, password })
+        let mut errors = ValidationErrors::new();
+        let email = UserEmail::parse(value.email).map_err(|error| errors.add("email", error));
+        let password = UserPassword::parse(value.password).map_err(|error| errors.add("password", error));
+        match (email, password) {
+            (Ok(email), Ok(password)) => Ok(Self { email, password }),
+            _ => Err(errors),
+        }
     }
 }
 
@@ -58,101 +67,158 @@ pub fn routes() -> Router<()> {
         .route(route_paths::REGISTER, get(self::get::register))
         .route(route_paths::REGISTER, post(self::post::register))
         .route(route_paths::LOGIN, get(self::get::login))
-        .route(route_paths::LOGOUT, get(self::get::logout))
+        .route(route_paths::LOGOUT, post(self::post::logout))
 }
 
 mod post {
     use super::*;
 
+    /// Shows the form again with the email they typed, but never the password
+    fn invalid_registration(render: &Render, email: &str, errors: &ValidationErrors) -> Response {
+        let mut context = tera::Context::new();
+        context.insert("email", email);
+        context.insert("errors", &render.field_errors(errors));
+        render.page_with_status(StatusCode::UNPROCESSABLE_ENTITY, html_templates::REGISTER, &context)
+    }
+
     pub async fn register(
         Extension(state): Extension<AppState>,
-        messages: Messages,
+        render: Render,
+        hx: HxRequest,
+        audit_context: AuditContext,
         Form(creds): Form<RegistrationForm>,
     ) -> impl IntoResponse {
+        let messages = render.messages();
+        let email = creds.email.clone();
         let new_user = match NewUser::try_from(creds) {
             Ok(new_user) => new_user,
-            Err(err) => {
-                messages.error(err.to_string());
-                return Redirect::to(route_paths::REGISTER).into_response();
-            },
+            Err(errors) => return invalid_registration(&render, &email, &errors),
         };
         let user_id = uuid::Uuid::new_v4();
         let password_hash = match telemetry::spawn_blocking_with_tracing(move || generate_hash(new_user.password)).await {
             Ok(hash) => hash,
-            Err(err) => {
-                messages.error(err.to_string());
-                return Redirect::to(route_paths::REGISTER).into_response();
-            },
+            Err(err) => return e500(err).into_response(),
         };
 
-        match sqlx::query(
-            "INSERT INTO users (id, email, password_hash) VALUES ($1, $2, $3) RETURNING id, email, password_hash, created_at, updated_at"
-        )
-            .bind(&user_id)
+        match sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, $3)")
+            .bind(user_id)
             .bind(&new_user.email.email)
             .bind(&password_hash)
-            .fetch_one(&state.db)
+            .execute(&state.db)
             .await
-            .map_err(e500) {
-                Ok(user) => user,
-                Err(err) => return err.into_response()
-            };
-        messages.success(strings::REGISTER_ACCOUNT_SUCCESS);
+        {
+            Ok(_) => {},
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                let mut errors = ValidationErrors::new();
+                errors.add("email", ValidationError::new("email-taken"));
+                return invalid_registration(&render, &email, &errors);
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+        let event = NewAuditEvent::new(AuditAction::Registered).actor(user_id).target(user_id);
+        audit::record_or_log(&state.db, &event, &audit_context).await;
+        messages.success(render.t("register-succeeded"));
 
+        let login_url = format!("{}{}", state.base_url, route_paths::LOGIN);
         let mut context = std::collections::HashMap::new();
-        context.insert("email", "jinius@g.com");
-        context.insert("confirmation_link", "http://example.com/confirm");
+        context.insert("email", new_user.email.as_ref());
+        context.insert("login_url", login_url.as_str());
+        context.insert("locale", render.locale().as_ref());
         match emailer::send_email(
+            &state,
             &new_user.email.email,
-            strings::WELCOME_EMAIL_SUBJECT,
+            &render.t("welcome-email-subject"),
             email_templates::EMAIL_VERIFICATION,
             &context,
-            &state.tera,
-            &state.email_settings,
         ).await.map_err(e500) {
-            Ok(_) => {
-                println!("Email was successfully sent");
+            Ok(outcome) => {
+                tracing::info!(?outcome, "Sent the welcome email");
             },
             Err(err) => {
                 return err.into_response();
             }
         }
 
-        Redirect::to(route_paths::ROOT).into_response()
+        htmx::redirect(&hx, route_paths::ROOT)
     }
 
     pub async fn login(
         mut auth_session: AuthSession,
-        messages: Messages,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        hx: HxRequest,
+        audit_context: AuditContext,
         Form(creds): Form<Credentials>,
     ) -> impl IntoResponse {
         let user = match auth_session.authenticate(creds.clone()).await {
             Ok(Some(user)) => user,
             Ok(None) => {
-                messages.error(strings::INVALID_CREDENTIALS);
-
-                let mut login_url = route_paths::LOGIN.to_string();
-                if let Some(next) = creds.next {
-                    login_url = format!("{}?next={}", login_url, next);
+                // Attempts on an existing account show up in its security activity.
+                // Only attempts on unknown emails keep the email they were made with.
+                let event = match auth_session.backend.find_user_id(&creds.email).await {
+                    Ok(Some(user_id)) => NewAuditEvent::new(AuditAction::LoginFailed).target(user_id),
+                    Ok(None) => NewAuditEvent::new(AuditAction::LoginFailed)
+                        .metadata(serde_json::json!({ "email": creds.email })),
+                    Err(err) => return e500(err).into_response(),
+                };
+                audit::record_or_log(&state.db, &event, &audit_context).await;
+
+                // htmx swaps the form again instead of following a redirect
+                if hx.is_htmx {
+                    let mut context = tera::Context::new();
+                    context.insert("next", &creds.next);
+                    let message = render.t("login-failed");
+                    return render
+                        .with_flash(Level::Error, message)
+                        .page_with_status(StatusCode::UNPROCESSABLE_ENTITY, html_templates::LOGIN, &context);
+                }
+                render.messages().error(render.t("login-failed"));
+
+                let login_url = match creds.next {
+                    Some(next) => next.append_to(route_paths::LOGIN),
+                    None => route_paths::LOGIN.to_string(),
                 };
 
                 return Redirect::to(&login_url).into_response();
             }
-            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            Err(err) => return e500(err).into_response(),
         };
 
-        if auth_session.login(&user).await.is_err() {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        if let Err(err) = auth_session.login(&user).await {
+            return e500(err).into_response();
+        }
+        if let Err(err) = session::record_for_user(&state.db, &session, user.id()).await {
+            return e500(err).into_response();
+        }
+        let event = NewAuditEvent::new(AuditAction::Login).actor(user.id()).target(user.id());
+        audit::record_or_log(&state.db, &event, &audit_context).await;
+
+        let messages = render.messages().success(render.t_with("login-succeeded", &serde_json::json!({ "email": user.email })));
+        if user.email_undeliverable {
+            messages.warning(render.t("email-undeliverable"));
         }
 
-        messages.success(format!("Successfully logged in as {}", user.email));
+        match creds.next {
+            Some(ref next) => htmx::redirect(&hx, next.as_ref()),
+            None => htmx::redirect(&hx, route_paths::ROOT),
+        }
+    }
 
-        if let Some(ref next) = creds.next {
-            Redirect::to(next)
-        } else {
-            Redirect::to(route_paths::ROOT)
+    pub async fn logout(
+        mut auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        audit_context: AuditContext,
+    ) -> impl IntoResponse {
+        match auth_session.logout().await {
+            Ok(Some(user)) => {
+                let event = NewAuditEvent::new(AuditAction::Logout).actor(user.id()).target(user.id());
+                audit::record_or_log(&state.db, &event, &audit_context).await;
+                Redirect::to(route_paths::ROOT).into_response()
+            },
+            Ok(None) => Redirect::to(route_paths::ROOT).into_response(),
+            Err(err) => e500(err).into_response(),
         }
-        .into_response()
     }
 }
 
@@ -160,43 +226,23 @@ mod get {
     use super::*;
 
     pub async fn register(
-        Extension(state): Extension<AppState>,
-        _messages: Messages,
+        render: Render,
         Query(NextUrl { next }): Query<NextUrl>,
     ) -> impl IntoResponse {
         let mut context = tera::Context::new();
         context.insert("next", &next);
-        match render_content(
-            &RenderTemplateParams::new(html_templates::REGISTER, &state.tera)
-            .with_context(&context)
-        ) {
-            Ok(register_template) => Html(register_template).into_response(),
-            Err(e) => e.into_response()
-        }
+        context.insert("email", "");
+        context.insert("errors", &FieldErrors::new());
+        render.page(html_templates::REGISTER, &context)
     }
 
     pub async fn login(
-        Extension(state): Extension<AppState>,
-        _messages: Messages,
+        render: Render,
         Query(NextUrl { next }): Query<NextUrl>,
     ) -> impl IntoResponse {
         let mut context = tera::Context::new();
-        let boo = "FROM THE LOGIN ROUTE";
-        context.insert("boo", &boo);
         context.insert("next", &next);
-        match render_content(
-            &RenderTemplateParams::new(html_templates::LOGIN, &state.tera)
-            .with_context(&context)
-        ) {
-            Ok(homepage_template) => Html(homepage_template).into_response(),
-            Err(e) => e.into_response()
-        }
+        render.page(html_templates::LOGIN, &context)
     }
 
-    pub async fn logout(mut auth_session: AuthSession) -> impl IntoResponse {
-        match auth_session.logout().await {
-            Ok(_) => Redirect::to(route_paths::ROOT).into_response(),
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-        }
-    }
 }
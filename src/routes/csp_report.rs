@@ -0,0 +1,55 @@
+use axum::{
+    body::Bytes,
+    extract::DefaultBodyLimit,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::post,
+    Router,
+};
+use serde_json::Value;
+use crate::error::{AppError, FieldErrors};
+
+use crate::constants::route_paths;
+
+/// Reports are small; anything bigger is not a browser report
+const MAX_REPORT_BYTES: usize = 64 * 1024;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::CSP_REPORT, post(self::post::csp_report))
+        .layer(DefaultBodyLimit::max(MAX_REPORT_BYTES))
+}
+
+mod post {
+    use super::*;
+
+    /// Collects Content-Security-Policy violations. Browsers send either a
+    /// single `{"csp-report": {...}}` object (`application/csp-report`) or an
+    /// array of reports (`application/reports+json`); both are logged as is.
+    pub async fn csp_report(body: Bytes) -> impl IntoResponse {
+        let reports = match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(reports)) => reports,
+            Ok(report) => vec![report],
+            Err(err) => {
+                let mut errors = FieldErrors::new();
+                errors.add("body", err.to_string());
+                return AppError::Validation { errors }.into_response();
+            },
+        };
+
+        for report in reports {
+            let report = report.get("csp-report").or_else(|| report.get("body")).unwrap_or(&report);
+            let field = |legacy: &str, current: &str| {
+                report.get(legacy).or_else(|| report.get(current)).cloned().unwrap_or(Value::Null)
+            };
+            tracing::warn!(
+                document_uri = %field("document-uri", "documentURL"),
+                violated_directive = %field("violated-directive", "effectiveDirective"),
+                report = %report,
+                "Content-Security-Policy violation"
+            );
+        }
+
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
@@ -0,0 +1,135 @@
+//! Streams realtime events to the logged in user. There is no login redirect
+//! here: `EventSource` and `WebSocket` clients can't follow one, so anonymous
+//! requests just get a 401. WebSocket handshakes aren't covered by CORS, so
+//! upgrades from pages on other origins are rejected; clients that send no
+//! `Origin`, like scripts using an API token, aren't browsers and are let in.
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    http::{header, HeaderMap, Uri},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Extension, Router,
+};
+use axum_login::AuthUser;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use crate::constants::route_paths;
+use crate::error::AppError;
+use crate::realtime::Event;
+use crate::startup::AppState;
+use crate::user::AuthSession;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::EVENTS, get(self::get::events))
+        .route(route_paths::EVENTS_WS, get(self::get::websocket))
+}
+
+/// Whether `origin` is the origin of `base_url`. Schemes and hosts are compared
+/// case-insensitively and default ports are filled in.
+fn is_same_origin(origin: &str, base_url: &str) -> bool {
+    fn parts(url: &str) -> Option<(String, String, u16)> {
+        let uri: Uri = url.parse().ok()?;
+        let scheme = uri.scheme_str()?.to_ascii_lowercase();
+        let default_port = match scheme.as_str() {
+            "https" => 443,
+            "http" => 80,
+            _ => return None,
+        };
+        let port = uri.port_u16().unwrap_or(default_port);
+        Some((scheme, uri.host()?.to_ascii_lowercase(), port))
+    }
+    matches!((parts(origin), parts(base_url)), (Some(origin), Some(base_url)) if origin == base_url)
+}
+
+/// Forwards events to the socket until either side closes it
+async fn forward(mut socket: WebSocket, mut events: broadcast::Receiver<Event>) {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let Ok(text) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        return;
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "WebSocket client is too slow, events were dropped");
+                },
+                Err(RecvError::Closed) => {
+                    let close = CloseFrame { code: close_code::AWAY, reason: "Server is shutting down".into() };
+                    let _ = socket.send(Message::Close(Some(close))).await;
+                    return;
+                },
+            },
+            message = socket.recv() => match message {
+                // Clients have nothing to say. Pings are answered by axum.
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {},
+            },
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    /// Server-Sent Events. Each event is named after its type and carries the
+    /// event as JSON.
+    pub async fn events(auth_session: AuthSession, Extension(state): Extension<AppState>) -> Response {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        // Events a slow client missed are skipped
+        let stream = BroadcastStream::new(state.realtime.subscribe(user.id()))
+            .filter_map(Result::ok)
+            .map(|event| SseEvent::default().event(event.name()).json_data(&event));
+
+        Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+    }
+
+    /// The same events as `events`, as JSON text messages
+    pub async fn websocket(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        headers: HeaderMap,
+        upgrade: WebSocketUpgrade,
+    ) -> Response {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+        if let Some(origin) = headers.get(header::ORIGIN) {
+            let origin = origin.to_str().unwrap_or_default();
+            if !is_same_origin(origin, &state.base_url) {
+                tracing::warn!(origin, "Rejected a WebSocket upgrade from another origin");
+                return AppError::Forbidden.into_response();
+            }
+        }
+
+        // Subscribing before the upgrade so no event is missed in between
+        let events = state.realtime.subscribe(user.id());
+        upgrade.on_upgrade(move |socket| forward(socket, events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_same_origin;
+
+    #[test]
+    fn origins_match_the_base_url() {
+        assert!(is_same_origin("https://app.example.com", "https://app.example.com/"));
+        assert!(is_same_origin("HTTPS://App.Example.com:443", "https://app.example.com"));
+        assert!(is_same_origin("http://127.0.0.1", "http://127.0.0.1"));
+        assert!(!is_same_origin("https://evil.example", "https://app.example.com"));
+        assert!(!is_same_origin("http://app.example.com", "https://app.example.com"));
+        assert!(!is_same_origin("https://app.example.com:8443", "https://app.example.com"));
+        assert!(!is_same_origin("null", "https://app.example.com"));
+    }
+}
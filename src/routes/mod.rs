@@ -1,17 +1,48 @@
-use axum::Router;
+use axum::{middleware, Router};
 use crate::constants::route_paths;
+use crate::rate_limit;
 
 mod health_check;
 mod homepage;
 mod auth;
 mod protected;
+mod account;
+mod unsubscribe;
+mod webhooks;
+mod admin;
+mod csp_report;
+mod api;
+mod events;
+mod notifications;
+
+pub use api::ApiDoc;
+
+/// Rate limits of each route group
+pub mod rate_limits {
+    use crate::rate_limit::{KeyBy, RateLimit};
+
+    /// Login and registration attempts, per IP
+    pub const AUTH: RateLimit = RateLimit::per_minute("auth", 10).sliding_window().writes_only();
+    /// Changes to account settings, per user
+    pub const ACCOUNT: RateLimit = RateLimit::per_minute("account", 30).writes_only().keyed_by(KeyBy::User);
+    /// JSON API calls, per API key
+    pub const API: RateLimit = RateLimit::per_minute("api", 120).keyed_by(KeyBy::ApiKey);
+    /// Email provider webhooks, per IP
+    pub const WEBHOOKS: RateLimit = RateLimit::per_minute("webhooks", 300);
+    /// Unsubscribe links, per IP
+    pub const UNSUBSCRIBE: RateLimit = RateLimit::per_minute("unsubscribe", 30).writes_only();
+    /// Content-Security-Policy violation reports, per IP
+    pub const CSP_REPORT: RateLimit = RateLimit::per_minute("csp_report", 60);
+}
 
 pub fn homepage_routes() -> Router {
     Router::new().nest(route_paths::ROOT, homepage::routes())
 }
 
 pub fn auth_routes() -> Router {
-    Router::new().nest(route_paths::ROOT, auth::routes())
+    Router::new()
+        .nest(route_paths::ROOT, auth::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::AUTH, rate_limit::enforce))
 }
 
 pub fn health_check_routes() -> Router {
@@ -21,3 +52,47 @@ pub fn health_check_routes() -> Router {
 pub fn protected_routes() -> Router {
     Router::new().nest(route_paths::PROTECTED, protected::routes())
 }
+
+pub fn account_routes() -> Router {
+    Router::new()
+        .nest(route_paths::ROOT, account::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::ACCOUNT, rate_limit::enforce))
+}
+
+pub fn unsubscribe_routes() -> Router {
+    Router::new()
+        .nest(route_paths::UNSUBSCRIBE, unsubscribe::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::UNSUBSCRIBE, rate_limit::enforce))
+}
+
+pub fn webhook_routes() -> Router {
+    Router::new()
+        .nest(route_paths::ROOT, webhooks::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::WEBHOOKS, rate_limit::enforce))
+}
+
+pub fn admin_routes() -> Router {
+    Router::new().nest(route_paths::ROOT, admin::routes())
+}
+
+pub fn csp_report_routes() -> Router {
+    Router::new()
+        .nest(route_paths::ROOT, csp_report::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::CSP_REPORT, rate_limit::enforce))
+}
+
+pub fn notifications_routes() -> Router {
+    Router::new()
+        .nest(route_paths::ROOT, notifications::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::ACCOUNT, rate_limit::enforce))
+}
+
+pub fn events_routes() -> Router {
+    Router::new().nest(route_paths::ROOT, events::routes())
+}
+
+pub fn api_routes() -> Router {
+    Router::new()
+        .nest(route_paths::API_V1, api::routes())
+        .route_layer(middleware::from_fn_with_state(rate_limits::API, rate_limit::enforce))
+}
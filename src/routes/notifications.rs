@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, Query},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::middleware;
+use axum_login::AuthUser;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::render::Render;
+use crate::utils::e500;
+use crate::error::AppError;
+use crate::guards;
+use crate::domain::SafeRedirect;
+use crate::notifications;
+
+use crate::user::AuthSession;
+use crate::constants::{
+    html_templates,
+    route_paths,
+};
+
+const PER_PAGE: u32 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationsQuery {
+    #[serde(default)]
+    pub page: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarkReadForm {
+    /// Go to the notification's link after marking it read
+    #[serde(default)]
+    pub open: bool,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::NOTIFICATIONS, get(self::get::notifications))
+        .route(route_paths::NOTIFICATION_READ, post(self::post::mark_read))
+        .route(route_paths::NOTIFICATIONS_READ_ALL, post(self::post::mark_all_read))
+        .route_layer(middleware::from_fn(guards::require_login))
+}
+
+mod get {
+    use super::*;
+
+    pub async fn notifications(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Query(query): Query<NotificationsQuery>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let page = query.page.unwrap_or(1).max(1);
+        let offset = i64::from(page - 1) * i64::from(PER_PAGE);
+        let counts = tokio::try_join!(
+            notifications::list(&state.db, user.id(), i64::from(PER_PAGE), offset),
+            notifications::count(&state.db, user.id()),
+        );
+        let (notifications, total) = match counts.map_err(e500) {
+            Ok(counts) => counts,
+            Err(err) => return err.into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("notifications", &notifications);
+        context.insert("page", &page);
+        context.insert("has_next_page", &(u64::from(page) * u64::from(PER_PAGE) < total));
+        render.page(html_templates::NOTIFICATIONS, &context)
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn mark_read(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Path(notification_id): Path<uuid::Uuid>,
+        Form(form): Form<MarkReadForm>,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        let notification = match notifications::mark_read(&state.db, user.id(), notification_id).await.map_err(e500) {
+            Ok(Some(notification)) => notification,
+            Ok(None) => {
+                render.messages().error(render.t("notification-not-found"));
+                return Redirect::to(route_paths::NOTIFICATIONS).into_response();
+            },
+            Err(err) => return err.into_response(),
+        };
+
+        // Links are set by the app, but are still only followed within it
+        let link = notification.url.as_deref().and_then(|url| SafeRedirect::parse(url).ok());
+        match link {
+            Some(link) if form.open => Redirect::to(link.as_ref()).into_response(),
+            _ => Redirect::to(route_paths::NOTIFICATIONS).into_response(),
+        }
+    }
+
+    pub async fn mark_all_read(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        render: Render,
+    ) -> impl IntoResponse {
+        let Some(user) = auth_session.user else {
+            return AppError::Unauthorized.into_response();
+        };
+
+        if let Err(err) = notifications::mark_all_read(&state.db, user.id()).await.map_err(e500) {
+            return err.into_response();
+        }
+        render.messages().success(render.t("notifications-marked-read"));
+
+        Redirect::to(route_paths::NOTIFICATIONS).into_response()
+    }
+}
@@ -0,0 +1,93 @@
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::render::Render;
+use crate::utils::e500;
+
+use crate::domain::UnsubscribeToken;
+use crate::notification_preferences::NotificationPreferences;
+use crate::constants::{
+    html_templates,
+    route_paths,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeQuery {
+    token: String,
+}
+
+/// The unsubscribe link has to work without the user being logged in, so the
+/// signed token in the query string is the only thing that identifies them.
+/// `POST` is the RFC 8058 one-click endpoint mail clients hit directly; it is
+/// also what the confirmation form on the `GET` page submits to.
+pub fn routes() -> Router<()> {
+    Router::new().route(
+        route_paths::ROOT,
+        get(self::get::unsubscribe).post(self::post::unsubscribe),
+    )
+}
+
+fn render_unsubscribe(render: &Render, status: StatusCode, context: &tera::Context) -> axum::response::Response {
+    render.page_with_status(status, html_templates::UNSUBSCRIBE, context)
+}
+
+mod get {
+    use super::*;
+
+    pub async fn unsubscribe(
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Query(UnsubscribeQuery { token }): Query<UnsubscribeQuery>,
+    ) -> impl IntoResponse {
+        let mut context = tera::Context::new();
+        match UnsubscribeToken::parse(&token, &state.hmac_secret) {
+            Ok(unsubscribe_token) => {
+                context.insert("token", &token);
+                context.insert("category", unsubscribe_token.category.as_str());
+                render_unsubscribe(&render, StatusCode::OK, &context)
+            },
+            Err(_) => {
+                context.insert("error", &render.t("unsubscribe-link-invalid"));
+                render_unsubscribe(&render, StatusCode::BAD_REQUEST, &context)
+            },
+        }
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn unsubscribe(
+        Extension(state): Extension<AppState>,
+        render: Render,
+        Query(UnsubscribeQuery { token }): Query<UnsubscribeQuery>,
+    ) -> impl IntoResponse {
+        let mut context = tera::Context::new();
+        let unsubscribe_token = match UnsubscribeToken::parse(&token, &state.hmac_secret) {
+            Ok(unsubscribe_token) => unsubscribe_token,
+            Err(_) => {
+                context.insert("error", &render.t("unsubscribe-link-invalid"));
+                return render_unsubscribe(&render, StatusCode::BAD_REQUEST, &context);
+            },
+        };
+
+        if let Err(err) = NotificationPreferences::unsubscribe(
+            &state.db,
+            unsubscribe_token.user_id,
+            unsubscribe_token.category,
+        ).await.map_err(e500) {
+            return err.into_response();
+        }
+
+        context.insert("category", unsubscribe_token.category.as_str());
+        context.insert("unsubscribed", &true);
+        render_unsubscribe(&render, StatusCode::OK, &context)
+    }
+}
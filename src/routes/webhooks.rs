@@ -0,0 +1,86 @@
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json},
+    routing::post,
+    Router,
+};
+use axum::Extension;
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use serde_json::json;
+use sha2::Sha256;
+use crate::startup::AppState;
+use crate::utils::e500;
+use crate::error::{AppError, FieldErrors};
+
+use crate::email_events::{self, EmailEvent, EmailEventBatch};
+use crate::suppressions;
+use crate::constants::route_paths;
+
+/// Header holding the hex encoded HMAC-SHA256 of the raw request body
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+pub fn routes() -> Router<()> {
+    Router::new().route(route_paths::WEBHOOKS_EMAIL_EVENTS, post(self::post::email_events))
+}
+
+fn has_valid_signature(state: &AppState, headers: &HeaderMap, body: &[u8]) -> bool {
+    let Some(signature) = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| hex::decode(value).ok())
+    else {
+        return false;
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(state.email_settings.webhook_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+mod post {
+    use super::*;
+
+    /// Receives bounce and complaint notifications. JSON bodies are batches of
+    /// events from the email provider; anything else is treated as an mbox file
+    /// or a single raw bounce message.
+    pub async fn email_events(
+        Extension(state): Extension<AppState>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> impl IntoResponse {
+        if !has_valid_signature(&state, &headers, &body) {
+            return AppError::Unauthorized.into_response();
+        }
+
+        let is_json = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+        let events: Vec<EmailEvent> = if is_json {
+            match serde_json::from_slice::<EmailEventBatch>(&body) {
+                Ok(batch) => batch.events,
+                Err(err) => {
+                    let mut errors = FieldErrors::new();
+                    errors.add("events", err.to_string());
+                    return AppError::Validation { errors }.into_response();
+                },
+            }
+        } else {
+            email_events::parse_mbox(&String::from_utf8_lossy(&body))
+        };
+
+        let mut suppressed = 0;
+        for event in &events {
+            match suppressions::record_event(&state.db, event).await.map_err(e500) {
+                Ok(true) => suppressed += 1,
+                Ok(false) => {},
+                Err(err) => return err.into_response(),
+            }
+        }
+
+        Json(json!({ "received": events.len(), "suppressed": suppressed })).into_response()
+    }
+}
@@ -0,0 +1,221 @@
+//! src/scss.rs
+//! Compiles the SCSS in `scss/` to CSS in `public/css/`, so styles are built
+//! by the app itself rather than a separate frontend toolchain.
+//!
+//! Partials (`_name.scss`) are only compiled through the files that use them,
+//! and subdirectories are mirrored in the output. In the local environment a
+//! compile error doesn't stop the app: it's shown in place of every page until
+//! the SCSS is fixed.
+use anyhow::Context;
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use crate::assets;
+use crate::configuration::{Environment, ScssSettings, ScssStyle};
+use crate::constants::{html_templates, route_paths, strings};
+use crate::startup::AppState;
+use crate::template_helpers;
+
+pub const SCSS_DIR: &str = "scss";
+pub const CSS_DIR: &str = "public/css";
+
+/// The last compile error, if the last compile failed. Shared between every
+/// clone of a compiler.
+#[derive(Clone, Default)]
+pub struct CompileStatus(Arc<RwLock<Option<String>>>);
+
+impl CompileStatus {
+    pub fn error(&self) -> Option<String> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    fn set(&self, error: Option<String>) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = error;
+    }
+}
+
+#[derive(Clone)]
+pub struct ScssCompiler {
+    scss_dir: PathBuf,
+    css_dir: PathBuf,
+    load_paths: Vec<PathBuf>,
+    style: grass::OutputStyle,
+    status: CompileStatus,
+}
+
+impl ScssCompiler {
+    /// Output is compressed in production unless configured otherwise
+    pub fn new(settings: &ScssSettings, environment: Environment) -> Self {
+        let style = match settings.style {
+            Some(ScssStyle::Expanded) => grass::OutputStyle::Expanded,
+            Some(ScssStyle::Compressed) => grass::OutputStyle::Compressed,
+            None if environment == Environment::Production => grass::OutputStyle::Compressed,
+            None => grass::OutputStyle::Expanded,
+        };
+        Self {
+            scss_dir: SCSS_DIR.into(),
+            css_dir: CSS_DIR.into(),
+            load_paths: settings.load_paths.clone(),
+            style,
+            status: CompileStatus::default(),
+        }
+    }
+
+    pub fn with_dirs(mut self, scss_dir: impl Into<PathBuf>, css_dir: impl Into<PathBuf>) -> Self {
+        self.scss_dir = scss_dir.into();
+        self.css_dir = css_dir.into();
+        self
+    }
+
+    pub fn scss_dir(&self) -> &Path {
+        &self.scss_dir
+    }
+
+    pub fn status(&self) -> CompileStatus {
+        self.status.clone()
+    }
+
+    /// Compiles every `.scss` file under the SCSS directory, except partials,
+    /// to a `.css` file at the same relative path in the CSS directory. The
+    /// outcome is kept in `status`. Returns how many files were compiled.
+    pub fn compile(&self) -> Result<usize, anyhow::Error> {
+        let result = self.compile_all();
+        self.status.set(result.as_ref().err().map(|err| format!("{:#}", err)));
+        result
+    }
+
+    fn compile_all(&self) -> Result<usize, anyhow::Error> {
+        // Create the CSS directory if it doesn't exist
+        fs::create_dir_all(&self.css_dir).context(strings::FAILED_TO_WRITE_SCSS)?;
+
+        let options = grass::Options::default()
+            .style(self.style)
+            .load_path(&self.scss_dir)
+            .load_paths(&self.load_paths);
+        let mut compiled = 0;
+        let mut dirs = vec![self.scss_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).context(strings::FAILED_TO_COMPILE_SCSS)? {
+                let path = entry.context(strings::FAILED_TO_COMPILE_SCSS)?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if path.extension().and_then(|s| s.to_str()) != Some("scss") || is_partial(&path) {
+                    continue;
+                }
+
+                let css = grass::from_path(&path, &options)
+                    .with_context(|| format!("{}: {}", strings::FAILED_TO_COMPILE_SCSS, path.display()))?;
+                let Ok(relative) = path.strip_prefix(&self.scss_dir) else {
+                    continue;
+                };
+                let css_path = self.css_dir.join(relative).with_extension("css");
+                if let Some(parent) = css_path.parent() {
+                    fs::create_dir_all(parent).context(strings::FAILED_TO_WRITE_SCSS)?;
+                }
+                fs::write(&css_path, css).context(strings::FAILED_TO_WRITE_SCSS)?;
+                compiled += 1;
+            }
+        }
+
+        Ok(compiled)
+    }
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('_'))
+}
+
+/// Development only. While the SCSS doesn't compile, pages are replaced with
+/// the compile error. Assets and the live reload stream are left alone so the
+/// page reloads itself once the error is fixed.
+pub async fn show_compile_errors(
+    State(status): State<CompileStatus>,
+    Extension(state): Extension<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let wants_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let is_page = request.method() == Method::GET
+        && wants_html
+        && !path.starts_with(assets::URL_PREFIX)
+        && path != route_paths::DEV_RELOAD;
+    let Some(error) = status.error().filter(|_| is_page) else {
+        return next.run(request).await;
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("error", &error);
+    let page = template_helpers::render(&state.tera, html_templates::SCSS_ERROR, &context).unwrap_or(error);
+    (StatusCode::INTERNAL_SERVER_ERROR, Html(page)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScssCompiler;
+    use crate::configuration::{Environment, ScssSettings, ScssStyle};
+    use crate::test_support::TempDir;
+
+    fn compiler(dirs: &TempDir, settings: &ScssSettings, environment: Environment) -> ScssCompiler {
+        ScssCompiler::new(settings, environment).with_dirs(dirs.join("scss"), dirs.join("css"))
+    }
+
+    #[test]
+    fn partials_are_only_compiled_through_their_users() {
+        let dirs = TempDir::new("scss");
+        dirs.write("scss/_colors.scss", "$primary: red;");
+        dirs.write("scss/main.scss", "@use 'colors'; body { color: colors.$primary; }");
+        dirs.write("scss/admin/admin.scss", "@use 'colors'; nav { color: colors.$primary; }");
+
+        let compiler = compiler(&dirs, &ScssSettings::default(), Environment::Production);
+        assert_eq!(compiler.compile().unwrap(), 2);
+        assert!(dirs.read("css/main.css").contains("color:red"));
+        assert!(dirs.read("css/admin/admin.css").contains("color:red"));
+        assert!(!dirs.join("css/_colors.css").exists());
+    }
+
+    #[test]
+    fn load_paths_are_searched() {
+        let dirs = TempDir::new("scss");
+        dirs.write("vendor/_reset.scss", "* { margin: 0; }");
+        dirs.write("scss/main.scss", "@use 'reset';");
+        let settings = ScssSettings {
+            load_paths: vec![dirs.join("vendor")],
+            style: Some(ScssStyle::Expanded),
+        };
+
+        compiler(&dirs, &settings, Environment::Production).compile().unwrap();
+        assert!(dirs.read("css/main.css").contains("margin: 0;"));
+    }
+
+    #[test]
+    fn compile_errors_are_kept_until_fixed() {
+        let dirs = TempDir::new("scss");
+        dirs.write("scss/main.scss", "body { color: $missing; }");
+        let compiler = compiler(&dirs, &ScssSettings::default(), Environment::Local);
+
+        assert!(compiler.compile().is_err());
+        let error = compiler.status().error().unwrap();
+        assert!(error.contains("main.scss"));
+        assert!(error.contains("Undefined variable"));
+
+        dirs.write("scss/main.scss", "body { color: red; }");
+        compiler.compile().unwrap();
+        assert_eq!(compiler.status().error(), None);
+    }
+}
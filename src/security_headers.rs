@@ -0,0 +1,184 @@
+//! src/security_headers.rs
+//! Sets Content-Security-Policy and the other security headers on every
+//! response. Each request gets a fresh CSP nonce; `Render` passes it to Tera
+//! as `csp_nonce` so inline scripts can be allowed with `nonce="{{ csp_nonce }}"`.
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+use crate::configuration::{Environment, SecurityHeadersSettings};
+use crate::constants::route_paths;
+use crate::error::AppError;
+use crate::utils::e500;
+
+const REPORTING_ENDPOINTS: HeaderName = HeaderName::from_static("reporting-endpoints");
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+/// Name of the endpoint in `Reporting-Endpoints` used by `report-to`
+const REPORT_GROUP: &str = "csp-endpoint";
+
+/// The nonce of the current request's Content-Security-Policy
+#[derive(Debug, Clone)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        Self(hex::encode(rand::random::<[u8; 16]>()))
+    }
+}
+
+impl AsRef<str> for CspNonce {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CspNonce
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CspNonce>()
+            .cloned()
+            .ok_or_else(|| e500("The security headers layer is missing"))
+    }
+}
+
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    settings: Arc<SecurityHeadersSettings>,
+    hsts: bool,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: SecurityHeadersSettings, environment: Environment) -> Self {
+        Self {
+            settings: Arc::new(settings),
+            hsts: environment == Environment::Production,
+        }
+    }
+
+    fn content_security_policy(&self, nonce: &CspNonce) -> String {
+        let sources = |base: &str, extra: &[String]| {
+            std::iter::once(base.to_string())
+                .chain(extra.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let settings = &self.settings;
+
+        [
+            "default-src 'self'".to_string(),
+            format!("script-src {}", sources(&format!("'self' 'nonce-{}'", nonce.as_ref()), &settings.script_src)),
+            format!("style-src {}", sources("'self'", &settings.style_src)),
+            format!("img-src {}", sources("'self' data:", &settings.img_src)),
+            format!("connect-src {}", sources("'self'", &settings.connect_src)),
+            "object-src 'none'".to_string(),
+            "base-uri 'self'".to_string(),
+            "form-action 'self'".to_string(),
+            format!("frame-ancestors {}", settings.frame_ancestors),
+            format!("report-uri {}", route_paths::CSP_REPORT),
+            format!("report-to {}", REPORT_GROUP),
+        ]
+        .join("; ")
+    }
+
+    fn csp_header(&self) -> HeaderName {
+        if self.settings.csp_report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        }
+    }
+}
+
+/// Middleware adding the security headers. Headers already set by a handler
+/// are left alone so a route can loosen or tighten its own policy.
+pub async fn set_security_headers(
+    State(security_headers): State<SecurityHeaders>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let nonce = CspNonce::generate();
+    request.extensions_mut().insert(nonce.clone());
+
+    let mut response = next.run(request).await;
+    let settings = &security_headers.settings;
+    let mut headers = vec![
+        (security_headers.csp_header(), security_headers.content_security_policy(&nonce)),
+        (REPORTING_ENDPOINTS, format!(r#"{}="{}""#, REPORT_GROUP, route_paths::CSP_REPORT)),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        (header::REFERRER_POLICY, settings.referrer_policy.clone()),
+        (PERMISSIONS_POLICY, settings.permissions_policy.clone()),
+    ];
+    if security_headers.hsts {
+        headers.push((
+            header::STRICT_TRANSPORT_SECURITY,
+            format!("max-age={}; includeSubDomains", settings.hsts_max_age),
+        ));
+    }
+
+    for (name, value) in headers {
+        match HeaderValue::from_str(&value) {
+            Ok(value) => {
+                response.headers_mut().entry(name).or_insert(value);
+            },
+            Err(err) => tracing::error!(header = %name, error = %err, "Invalid security header value"),
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CspNonce, SecurityHeaders};
+    use crate::configuration::{Environment, SecurityHeadersSettings};
+    use axum::http::header;
+
+    #[test]
+    fn nonces_are_unique() {
+        assert_ne!(CspNonce::generate().as_ref(), CspNonce::generate().as_ref());
+        assert_eq!(CspNonce::generate().as_ref().len(), 32);
+    }
+
+    #[test]
+    fn policy_includes_nonce_and_extra_sources() {
+        let settings = SecurityHeadersSettings {
+            script_src: vec!["https://cdn.example".to_string()],
+            ..Default::default()
+        };
+        let security_headers = SecurityHeaders::new(settings, Environment::Local);
+        let nonce = CspNonce("abc".to_string());
+        let policy = security_headers.content_security_policy(&nonce);
+
+        assert!(policy.contains("script-src 'self' 'nonce-abc' https://cdn.example;"));
+        assert!(policy.contains("frame-ancestors 'none';"));
+        assert!(policy.contains("report-uri /csp-report;"));
+        assert!(policy.contains("object-src 'none';"));
+    }
+
+    #[test]
+    fn report_only_mode_changes_the_header() {
+        let settings = SecurityHeadersSettings { csp_report_only: true, ..Default::default() };
+        let security_headers = SecurityHeaders::new(settings, Environment::Local);
+        assert_eq!(security_headers.csp_header(), header::CONTENT_SECURITY_POLICY_REPORT_ONLY);
+
+        let security_headers = SecurityHeaders::new(Default::default(), Environment::Local);
+        assert_eq!(security_headers.csp_header(), header::CONTENT_SECURITY_POLICY);
+    }
+
+    #[test]
+    fn hsts_only_in_production() {
+        assert!(!SecurityHeaders::new(Default::default(), Environment::Local).hsts);
+        assert!(SecurityHeaders::new(Default::default(), Environment::Production).hsts);
+    }
+}
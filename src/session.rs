@@ -0,0 +1,350 @@
+//! src/session.rs
+//! Signed session cookies. The signing key is derived from
+//! `application.session.secret`; cookies signed with one of the
+//! `previous_secrets` are re-signed with the current key before the session
+//! layer sees them, so secrets can be rotated without logging users out. The
+//! session is then marked modified, so the browser gets the re-signed cookie
+//! back and the old secret can be dropped once sessions have been used.
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use axum_login::tower_sessions::{
+    cookie::{self, Cookie, CookieJar, Key},
+    service::SignedCookie,
+    Expiry, Session, SessionManagerLayer, SessionStore,
+};
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::configuration::{Environment, SameSite, SessionSettings};
+
+/// Shortest secret `Key::derive_from` accepts
+const MIN_SECRET_BYTES: usize = 32;
+/// Where `PostgresStore` keeps sessions by default
+const SESSION_TABLE: &str = r#""tower_sessions"."session""#;
+/// How often sessions that no longer exist are forgotten
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The current signing key followed by the keys it replaced
+#[derive(Clone)]
+pub struct SessionKeys {
+    cookie_name: Arc<str>,
+    current: Key,
+    previous: Arc<[Key]>,
+}
+
+impl SessionKeys {
+    pub fn from_settings(settings: &SessionSettings) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            cookie_name: settings.cookie_name.as_str().into(),
+            current: derive_key(&settings.secret)?,
+            previous: settings
+                .previous_secrets
+                .iter()
+                .map(derive_key)
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+        })
+    }
+
+    /// Returns the `Cookie` header with the session cookie re-signed by the
+    /// current key, or `None` when there is nothing to re-sign.
+    fn resign(&self, cookie_header: &str) -> Option<String> {
+        let cookies: Vec<Cookie> = Cookie::split_parse(cookie_header)
+            .filter_map(Result::ok)
+            .collect();
+        let session_cookie = cookies.iter().find(|c| c.name() == &*self.cookie_name)?;
+
+        let mut jar = CookieJar::new();
+        jar.add_original(session_cookie.clone().into_owned());
+        if jar.signed(&self.current).get(&self.cookie_name).is_some() {
+            return None;
+        }
+        let verified = self
+            .previous
+            .iter()
+            .find_map(|key| jar.signed(key).get(&self.cookie_name))?;
+
+        let mut resigned = CookieJar::new();
+        resigned.signed_mut(&self.current).add(verified);
+        let resigned = resigned.get(&self.cookie_name)?;
+
+        Some(
+            cookies
+                .iter()
+                .map(|c| if c.name() == &*self.cookie_name { resigned } else { c })
+                .map(|c| c.stripped().to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+}
+
+fn derive_key(secret: &Secret<String>) -> Result<Key, anyhow::Error> {
+    let secret = secret.expose_secret().as_bytes();
+    if secret.len() < MIN_SECRET_BYTES {
+        anyhow::bail!("Session secrets must be at least {} bytes long", MIN_SECRET_BYTES);
+    }
+    Ok(Key::derive_from(secret))
+}
+
+/// Builds the session layer from the configured cookie settings. Cookies are
+/// always `Secure` in production, whatever the configuration says.
+pub fn session_layer<Store: SessionStore>(
+    store: Store,
+    settings: &SessionSettings,
+    keys: &SessionKeys,
+    environment: Environment,
+) -> SessionManagerLayer<Store, SignedCookie> {
+    let secure = settings.secure || environment == Environment::Production;
+    let layer = SessionManagerLayer::new(store)
+        .with_name(settings.cookie_name.clone())
+        .with_secure(secure)
+        .with_http_only(settings.http_only)
+        .with_same_site(same_site(settings.same_site))
+        .with_expiry(Expiry::OnInactivity(time::Duration::days(1)));
+    let layer = match &settings.cookie_domain {
+        Some(domain) => layer.with_domain(domain.clone()),
+        None => layer,
+    };
+    layer.with_signed(keys.current.clone())
+}
+
+fn same_site(same_site: SameSite) -> cookie::SameSite {
+    match same_site {
+        SameSite::Strict => cookie::SameSite::Strict,
+        SameSite::Lax => cookie::SameSite::Lax,
+        SameSite::None => cookie::SameSite::None,
+    }
+}
+
+/// Left on requests whose session cookie `resign_session_cookie` re-signed
+#[derive(Debug, Clone, Copy)]
+struct ResignedSessionCookie;
+
+/// Middleware re-signing session cookies signed with a previous secret. Must
+/// run before the session layer.
+pub async fn resign_session_cookie(
+    State(keys): State<SessionKeys>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let resigned = request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join("; ");
+    if let Some(value) = keys.resign(&resigned).and_then(|v| HeaderValue::from_str(&v).ok()) {
+        request.headers_mut().insert(header::COOKIE, value);
+        request.extensions_mut().insert(ResignedSessionCookie);
+    }
+
+    next.run(request).await
+}
+
+/// Middleware marking sessions whose cookie was re-signed as modified, so the
+/// session layer sets the cookie signed with the current key. Must run inside
+/// the session layer.
+pub async fn save_resigned_session(request: Request, next: Next) -> Response {
+    if request.extensions().get::<ResignedSessionCookie>().is_some() {
+        if let Some(session) = request.extensions().get::<Session>() {
+            // Setting the expiry it already has is enough to mark it modified
+            session.set_expiry(session.expiry());
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Remembers that the session belongs to the user. The session is saved first,
+/// as a new session has no id until it is.
+pub async fn record_for_user(db: &PgPool, session: &Session, user_id: Uuid) -> Result<(), anyhow::Error> {
+    session.save().await?;
+    let session_id = session.id().context("A saved session has an id")?;
+    sqlx::query(
+        "INSERT INTO user_sessions (session_id, user_id) VALUES ($1, $2)
+        ON CONFLICT (session_id) DO UPDATE SET user_id = EXCLUDED.user_id"
+    )
+        .bind(session_id.to_string())
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// When each of the user's unexpired sessions expires
+pub async fn list_for_user(db: &PgPool, user_id: Uuid) -> Result<Vec<OffsetDateTime>, sqlx::Error> {
+    let expiry_dates: Vec<(OffsetDateTime,)> = sqlx::query_as(&format!(
+        "SELECT session.expiry_date FROM user_sessions
+        INNER JOIN {} AS session ON session.id = user_sessions.session_id
+        WHERE user_sessions.user_id = $1 AND session.expiry_date > NOW()
+        ORDER BY session.expiry_date",
+        SESSION_TABLE
+    ))
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+
+    Ok(expiry_dates.into_iter().map(|(expiry_date,)| expiry_date).collect())
+}
+
+/// Logs the user out everywhere. Returns how many sessions were deleted.
+pub async fn delete_for_user(connection: &mut PgConnection, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&format!(
+        "DELETE FROM {} WHERE id IN (SELECT session_id FROM user_sessions WHERE user_id = $1)",
+        SESSION_TABLE
+    ))
+        .bind(user_id)
+        .execute(&mut *connection)
+        .await?;
+    sqlx::query("DELETE FROM user_sessions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *connection)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Forgets sessions that expired or were logged out. Returns how many were
+/// forgotten.
+pub async fn prune(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(&format!(
+        "DELETE FROM user_sessions WHERE NOT EXISTS (SELECT 1 FROM {} AS session WHERE session.id = user_sessions.session_id)",
+        SESSION_TABLE
+    ))
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Prunes forgotten sessions every `PRUNE_INTERVAL`, until aborted
+pub async fn continuously_prune(db: PgPool) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match prune(&db).await {
+            Ok(0) => {},
+            Ok(pruned) => tracing::info!(pruned, "Pruned sessions that no longer exist"),
+            Err(err) => tracing::error!(error = %err, "Failed to prune sessions that no longer exist"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resign_session_cookie, save_resigned_session, session_layer, SessionKeys};
+    use crate::configuration::{Environment, SameSite, SessionSettings};
+    use axum::{body::Body, http::{header, Request}, middleware, routing::get, Router};
+    use axum_login::tower_sessions::cookie::{Cookie, CookieJar};
+    use axum_login::tower_sessions::{MemoryStore, Session};
+    use secrecy::Secret;
+    use tower::ServiceExt;
+
+    const OLD_SECRET: &str = "an-old-secret-that-is-at-least-32-bytes-long";
+    const NEW_SECRET: &str = "a-new-secret-that-is-also-at-least-32-bytes-long";
+
+    fn settings(secret: &str, previous: &[&str]) -> SessionSettings {
+        SessionSettings {
+            secret: Secret::new(secret.to_string()),
+            previous_secrets: previous.iter().map(|s| Secret::new(s.to_string())).collect(),
+            cookie_name: "id".to_string(),
+            cookie_domain: None,
+            secure: false,
+            http_only: true,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    fn signed_cookie(keys: &SessionKeys, value: &str) -> String {
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&keys.current).add(Cookie::new("id", value.to_string()));
+        jar.get("id").unwrap().stripped().to_string()
+    }
+
+    /// `/login` stores a user in the session and `/` returns it
+    fn app(settings: &SessionSettings, store: MemoryStore) -> Router {
+        let keys = SessionKeys::from_settings(settings).unwrap();
+        Router::new()
+            .route("/login", get(|session: Session| async move { session.insert("user", "someone").await.unwrap() }))
+            .route("/", get(|session: Session| async move { session.get::<String>("user").await.unwrap().unwrap_or_default() }))
+            .layer(middleware::from_fn(save_resigned_session))
+            .layer(session_layer(store, settings, &keys, Environment::Local))
+            .layer(middleware::from_fn_with_state(keys, resign_session_cookie))
+    }
+
+    async fn send(app: Router, path: &str, cookie: Option<&str>) -> (Option<String>, String) {
+        let mut request = Request::get(path);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let set_cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .map(|value| value.to_str().unwrap().split(';').next().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (set_cookie, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn short_secrets_are_rejected() {
+        assert!(SessionKeys::from_settings(&settings("too-short", &[])).is_err());
+        assert!(SessionKeys::from_settings(&settings(NEW_SECRET, &["too-short"])).is_err());
+    }
+
+    #[test]
+    fn cookies_signed_with_the_current_key_are_left_alone() {
+        let keys = SessionKeys::from_settings(&settings(NEW_SECRET, &[OLD_SECRET])).unwrap();
+        let header = format!("theme=dark; {}", signed_cookie(&keys, "session-id"));
+        assert_eq!(keys.resign(&header), None);
+    }
+
+    #[test]
+    fn cookies_signed_with_a_previous_key_are_resigned() {
+        let old_keys = SessionKeys::from_settings(&settings(OLD_SECRET, &[])).unwrap();
+        let keys = SessionKeys::from_settings(&settings(NEW_SECRET, &[OLD_SECRET])).unwrap();
+        let header = format!("theme=dark; {}", signed_cookie(&old_keys, "session-id"));
+
+        let resigned = keys.resign(&header).unwrap();
+        assert!(resigned.starts_with("theme=dark; "));
+        assert!(resigned.ends_with(&signed_cookie(&keys, "session-id")));
+    }
+
+    #[test]
+    fn cookies_signed_with_an_unknown_key_are_not_resigned() {
+        let other_keys = SessionKeys::from_settings(
+            &settings("some-other-secret-that-is-32-bytes-long-too", &[])
+        ).unwrap();
+        let keys = SessionKeys::from_settings(&settings(NEW_SECRET, &[OLD_SECRET])).unwrap();
+        assert_eq!(keys.resign(&signed_cookie(&other_keys, "session-id")), None);
+    }
+
+    #[tokio::test]
+    async fn resigned_cookies_are_sent_back_signed_with_the_current_key() {
+        let store = MemoryStore::default();
+        let (old_cookie, _) = send(app(&settings(OLD_SECRET, &[]), store.clone()), "/login", None).await;
+        let old_cookie = old_cookie.unwrap();
+
+        let settings = settings(NEW_SECRET, &[OLD_SECRET]);
+        let (new_cookie, user) = send(app(&settings, store), "/", Some(&old_cookie)).await;
+        assert_eq!(user, "someone");
+
+        let new_cookie = Cookie::parse(new_cookie.unwrap()).unwrap();
+        let mut jar = CookieJar::new();
+        jar.add_original(new_cookie);
+        let keys = SessionKeys::from_settings(&settings).unwrap();
+        assert!(jar.signed(&keys.current).get("id").is_some());
+    }
+}
@@ -4,17 +4,18 @@
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use secrecy::Secret;
-use axum::{Extension,Router};
+use axum::{middleware, Extension, Router};
 use tokio::net::TcpListener;
+use tower::Layer;
+use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::trace::TraceLayer;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
 use tera::Tera;
-use tower_http::services::{ServeDir, ServeFile};
-use std::fs;
-use std::path::Path;
+use tower_http::services::ServeDir;
+use axum::handler::HandlerWithoutStateExt;
 use axum_login::{
-    login_required,
-    tower_sessions::{ExpiredDeletion, Expiry, SessionManagerLayer, cookie::Key},
+    tower_sessions::ExpiredDeletion,
     AuthManagerLayerBuilder,
 };
 use axum_messages::MessagesManagerLayer;
@@ -22,38 +23,90 @@ use tokio::{signal, task::AbortHandle};
 use tower_sessions_sqlx_store::PostgresStore;
 
 use crate::configuration::Settings;
+use crate::configuration::ApplicationSettings;
 use crate::configuration::DatabaseSettings;
 use crate::configuration::EmailSettings;
+use crate::configuration::Environment;
+use crate::configuration::AccountSettings;
+use crate::dev_reload::{self, DevReload};
+use crate::scss::{self, ScssCompiler};
+use crate::assets::{self, AssetManifest, SharedAssets};
+use crate::compression;
+use crate::template_helpers::{self, SharedTera};
+use crate::i18n::{self, I18n, SharedI18n};
 use crate::routes::health_check_routes;
 use crate::routes::homepage_routes;
 use crate::routes::auth_routes;
 use crate::routes::protected_routes;
+use crate::routes::account_routes;
+use crate::routes::unsubscribe_routes;
+use crate::routes::webhook_routes;
+use crate::routes::admin_routes;
+use crate::routes::csp_report_routes;
+use crate::routes::api_routes;
+use crate::routes::events_routes;
+use crate::routes::notifications_routes;
 use crate::user::Backend;
-use crate::constants::strings;
+use crate::error::{self, render_errors};
+use crate::csrf;
+use crate::api_tokens;
+use crate::session::{self, SessionKeys};
+use crate::security_headers::{self, SecurityHeaders};
+use crate::rate_limit::RateLimiter;
+use crate::proxy::TrustedProxies;
+use crate::realtime;
+use crate::notifications;
+use crate::account_exports;
+use crate::account_deletion;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub hmac_secret: Secret<String>,
-    pub tera: Arc<Tera>,
+    pub tera: SharedTera,
     pub email_settings: EmailSettings,
+    pub base_url: String,
+    pub realtime: realtime::Hub,
+    pub account_settings: AccountSettings,
+    pub environment: Environment,
+    pub i18n: SharedI18n,
+}
+
+/// What `run` serves the app with, besides the configuration. Built once by
+/// `Application::build`.
+pub struct Resources {
+    pub db_pool: PgPool,
+    pub tera: SharedTera,
+    pub i18n: SharedI18n,
+    pub assets: SharedAssets,
+    pub scss_compiler: ScssCompiler,
+    /// Served alongside the app's own routes
+    pub routes: Router,
 }
 
 pub struct Application {
     port: u16,
-    db_pool: PgPool,
-    tera: Arc<Tera>,
     listener: TcpListener,
-    base_url: String,
-    redis_uri: Secret<String>,
-    hmac_secret: Secret<String>,
-    email_settings: EmailSettings,
+    resources: Resources,
+    configuration: Settings,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
-        // Compile SCSS files to CSS at runtime
-        compile_scss_to_css("scss", "public/css");
+        // Compile SCSS files to CSS at runtime. Locally a compile error is shown
+        // in place of every page rather than stopping the app.
+        let scss_compiler = ScssCompiler::new(&configuration.application.scss, configuration.environment);
+        if let Err(err) = scss_compiler.compile() {
+            if configuration.environment != Environment::Local {
+                return Err(err);
+            }
+            tracing::error!(error = ?err, "Failed to compile SCSS");
+        }
+        if configuration.application.compression.precompress_assets {
+            compression::precompress_dir(assets::PUBLIC_DIR)?;
+        }
+        // Fingerprint everything in public/, including the CSS just compiled
+        let assets = Arc::new(RwLock::new(AssetManifest::build(assets::PUBLIC_DIR)?));
         let connection_pool = get_connection_pool(&configuration.database);
 
         let address = format!(
@@ -62,18 +115,27 @@ impl Application {
         );
         let listener = TcpListener::bind(address).await?;
         let port = listener.local_addr().unwrap().port();
-        let tera = Tera::new("templates/**/*html")?;
-        let tera = Arc::new(tera);
+        let i18n = Arc::new(I18n::load(i18n::LOCALES_DIR)?);
+        let mut tera = Tera::new("templates/**/*html")?;
+        let dev_reload_settings = &configuration.application.dev_reload;
+        let live_reload = configuration.environment == Environment::Local
+            && dev_reload_settings.enabled
+            && dev_reload_settings.live_reload;
+        template_helpers::register(&mut tera, assets.clone(), i18n.clone(), live_reload);
+        let tera = Arc::new(RwLock::new(tera));
 
         Ok(Self {
             port,
-            tera,
             listener,
-            db_pool: connection_pool,
-            base_url: configuration.application.base_url,
-            redis_uri: configuration.redis_uri,
-            hmac_secret: configuration.application.hmac_secret,
-            email_settings: configuration.email,
+            resources: Resources {
+                db_pool: connection_pool,
+                tera,
+                i18n,
+                assets,
+                scss_compiler,
+                routes: Router::new(),
+            },
+            configuration,
         })
     }
 
@@ -81,10 +143,15 @@ impl Application {
         self.port
     }
 
+    /// Adds routes behind the same layers as the app's own, such as the error
+    /// pages. Tests use it for handlers that fail on purpose.
+    pub fn with_routes(mut self, routes: Router) -> Self {
+        self.resources.routes = self.resources.routes.merge(routes);
+        self
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
-        run(
-            self.db_pool, self.listener, self.base_url, self.redis_uri, self.hmac_secret, self.tera, self.email_settings
-            ).await
+        run(self.listener, self.resources, self.configuration).await
     }
 }
 
@@ -98,7 +165,27 @@ pub fn get_connection_pool(
 
 pub struct ApplicationBaseUrl(pub String);
 
-pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _redis_uri: Secret<String>, hmac_secret: Secret<String>, tera: Arc<Tera>, email_settings: EmailSettings) -> Result<(), anyhow::Error> {
+pub async fn run(
+    listener: TcpListener,
+    resources: Resources,
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let Resources { db_pool, tera, i18n, assets, scss_compiler, routes } = resources;
+    let Settings { application, email: email_settings, redis_uri, environment, .. } = configuration;
+    let ApplicationSettings {
+        base_url,
+        hmac_secret,
+        session: session_settings,
+        security_headers: security_headers_settings,
+        rate_limit: rate_limit_settings,
+        proxy: proxy_settings,
+        notifications: notification_settings,
+        account: account_settings,
+        dev_reload: dev_reload_settings,
+        compression: compression_settings,
+        ..
+    } = application;
+
     // Session layer.
     //
     // This uses `tower-sessions` to establish a layer that will provide the session
@@ -111,12 +198,10 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
         .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
     );
 
-    // Generate a cryptographic key to sign the session cookie.
-    // let key = Key::generate();
-    // TODO: Need to secure cookie
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(time::Duration::days(1)));
+    // The session cookie is signed with a key derived from the configured
+    // secret. Cookies signed with a previous secret are re-signed on the way in.
+    let session_keys = SessionKeys::from_settings(&session_settings)?;
+    let session_layer = session::session_layer(session_store, &session_settings, &session_keys, environment);
 
     // Auth service.
     //
@@ -125,61 +210,117 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
     let backend = Backend::new(db_pool.clone());
     let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
-    let app = api_router()
+    // Rate limit counters shared by every route group
+    let rate_limiter = RateLimiter::from_settings(&rate_limit_settings, &redis_uri).await?;
+
+    // Realtime events, fanned out to every instance with Postgres LISTEN/NOTIFY
+    let realtime = realtime::Hub::new(db_pool.clone());
+    let realtime_task = realtime.listen().await?;
+
+    // Old notifications, and the users of sessions that are gone, are pruned in the background
+    let pruning_task = tokio::task::spawn(notifications::continuously_prune(db_pool.clone(), notification_settings));
+    let session_pruning_task = tokio::task::spawn(session::continuously_prune(db_pool.clone()));
+
+    // Templates, SCSS and the asset manifest are reloaded when they change during development
+    let dev_reload = (environment == Environment::Local && dev_reload_settings.enabled)
+        .then(|| {
+            DevReload::new(tera.clone(), assets.clone(), scss_compiler.clone(), &dev_reload_settings)
+                .precompress_assets(compression_settings.precompress_assets)
+        });
+    let dev_reload_task = dev_reload.clone().map(|dev_reload| tokio::task::spawn(dev_reload.watch()));
+
+    let state = AppState {
+        db: db_pool.clone(),
+        hmac_secret,
+        tera,
+        email_settings,
+        base_url,
+        realtime: realtime.clone(),
+        account_settings,
+        environment,
+        i18n,
+    };
+
+    // Data exports are built and accounts past their grace period deleted in the background
+    let export_task = tokio::task::spawn(account_exports::continuously_process(state.clone()));
+    let account_deletion_task = tokio::task::spawn(account_deletion::continuously_delete_due(db_pool));
+
+    let mut router = api_router(assets, compression_settings.precompress_assets).merge(routes);
+    if let Some(dev_reload) = dev_reload {
+        router = router.merge(dev_reload::routes(dev_reload));
+    }
+    if environment == Environment::Local {
+        router = router.layer(middleware::from_fn_with_state(scss_compiler.status(), scss::show_compile_errors));
+    }
+    let app = router
+        // Inside `render_errors`, so panics get the same error pages
+        .layer(CatchPanicLayer::custom(error::handle_panic))
+        .layer(middleware::from_fn(render_errors))
+        .layer(middleware::from_fn_with_state(
+            SecurityHeaders::new(security_headers_settings, environment),
+            security_headers::set_security_headers,
+        ))
+        .layer(compression::layer(&compression_settings))
         .layer(TraceLayer::new_for_http())
-        .layer(
-            Extension(
-                AppState {
-                    db: db_pool,
-                    hmac_secret,
-                    tera,
-                    email_settings,
-                }
-            )
-        )
+        .layer(middleware::from_fn(i18n::set_locale))
+        .layer(Extension(state))
+        .layer(Extension(rate_limiter))
+        .layer(Extension(TrustedProxies::from_settings(&proxy_settings)))
         .layer(MessagesManagerLayer)
-        .layer(auth_layer);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
+        .layer(middleware::from_fn(session::save_resigned_session))
+        .layer(auth_layer)
+        .layer(middleware::from_fn_with_state(session_keys, session::resign_session_cookie));
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle(), realtime))
         .await?;
 
+    realtime_task.abort();
+    pruning_task.abort();
+    session_pruning_task.abort();
+    export_task.abort();
+    account_deletion_task.abort();
+    if let Some(dev_reload_task) = dev_reload_task {
+        dev_reload_task.abort();
+    }
+
     deletion_task.await??;
     Ok(())
 }
 
-fn api_router() -> Router {
+fn api_router(assets: SharedAssets, precompressed: bool) -> Router {
     // The ServeDir directory will allow the application to access these files and its
-    // subdirectories
-    let service = ServeDir::new("public")
-        .fallback(ServeFile::new("public/file_not_found.html"));
+    // subdirectories. Fingerprinted paths from the `asset` template function are
+    // served from the file they name and cached for good. Missing files get
+    // the same 404 page as unknown routes.
+    let mut service = ServeDir::new(assets::PUBLIC_DIR)
+        .fallback(error::not_found.into_service());
+    if precompressed {
+        service = service.precompressed_br().precompressed_gzip();
+    }
+    let service = middleware::from_fn_with_state(assets, assets::serve_fingerprinted).layer(service);
 
     Router::new()
-        .nest_service("/public", service)
+        .nest_service(assets::URL_PREFIX, service)
         .merge(health_check_routes())
         .merge(homepage_routes())
         .merge(protected_routes())
         .merge(auth_routes())
+        .merge(account_routes())
+        .merge(unsubscribe_routes())
+        .merge(webhook_routes())
+        .merge(admin_routes())
+        .merge(csp_report_routes())
+        .merge(api_routes())
+        .merge(events_routes())
+        .merge(notifications_routes())
+        .fallback(error::not_found)
+        .layer(middleware::from_fn(csrf::verify_csrf_token))
+        .layer(middleware::from_fn(api_tokens::authenticate_bearer))
 }
 
-fn compile_scss_to_css(scss_dir: &str, css_dir: &str) {
-    // Create the CSS directory if it doesn't exist
-    fs::create_dir_all(css_dir).unwrap();
-
-    // Compile SCSS files to CSS
-    for entry in fs::read_dir(scss_dir).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
-
-        if path.extension().and_then(|s| s.to_str()) == Some("scss") {
-            let css = grass::from_path(&path, &grass::Options::default()).expect(strings::FAILED_TO_COMPILE_SCSS);
-
-            let css_path = Path::new(css_dir).join(path.with_extension("css").file_name().unwrap());
-            fs::write(css_path, css).expect(strings::FAILED_TO_WRITE_SCSS);
-        }
-    }
-}
-
-async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
+/// Waits for Ctrl+C or SIGTERM. Open realtime connections are closed so the
+/// graceful shutdown doesn't wait on them forever.
+async fn shutdown_signal(deletion_task_abort_handle: AbortHandle, realtime: realtime::Hub) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -201,5 +342,6 @@ async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
         _ = ctrl_c => { deletion_task_abort_handle.abort() },
         _ = terminate => { deletion_task_abort_handle.abort() },
     }
+    realtime.close();
 }
 
@@ -0,0 +1,80 @@
+//! src/suppressions.rs
+//! Addresses we must not email anymore because they hard bounced or the
+//! recipient marked us as spam.
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use crate::email_events::EmailEvent;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct EmailSuppression {
+    pub id: i32,
+    pub email: String,
+    pub reason: String,
+    pub detail: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+pub async fn is_suppressed(db: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let suppressed: Option<(i32,)> = sqlx::query_as("SELECT id FROM email_suppressions WHERE email = lower($1)")
+        .bind(email)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(suppressed.is_some())
+}
+
+/// Adds the address of the event to the suppression list and flags the account
+/// using that address so the user is asked to fix it. Returns false for events
+/// that do not suppress the address (soft bounces).
+pub async fn record_event(db: &PgPool, event: &EmailEvent) -> Result<bool, sqlx::Error> {
+    if !event.kind.suppresses() {
+        tracing::info!(email = %event.email, kind = event.kind.as_str(), "Ignoring email event");
+        return Ok(false);
+    }
+
+    let mut transaction = db.begin().await?;
+    sqlx::query(
+        "INSERT INTO email_suppressions (email, reason, detail) VALUES (lower($1), $2, $3)
+        ON CONFLICT (email) DO UPDATE SET reason = EXCLUDED.reason, detail = EXCLUDED.detail"
+    )
+        .bind(&event.email)
+        .bind(event.kind.as_str())
+        .bind(&event.detail)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query("UPDATE users SET email_undeliverable = TRUE WHERE lower(email) = lower($1)")
+        .bind(&event.email)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+pub async fn list(db: &PgPool) -> Result<Vec<EmailSuppression>, sqlx::Error> {
+    sqlx::query_as("SELECT id, email, reason, detail, created_at FROM email_suppressions ORDER BY created_at DESC")
+        .fetch_all(db)
+        .await
+}
+
+/// Removes an address from the suppression list and clears the flag on the
+/// matching account. Returns false if there was no such suppression.
+pub async fn clear(db: &PgPool, suppression_id: i32) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let email: Option<(String,)> = sqlx::query_as("DELETE FROM email_suppressions WHERE id = $1 RETURNING email")
+        .bind(suppression_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let Some((email,)) = email else {
+        return Ok(false);
+    };
+    sqlx::query("UPDATE users SET email_undeliverable = FALSE WHERE lower(email) = $1")
+        .bind(&email)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
@@ -1,22 +1,51 @@
-use std::sync::Arc;
-use crate::utils::{e500, ErrorResponse};
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use time_tz::{timezones, OffsetDateTimeExt, Tz};
+use crate::assets::{AssetUrl, SharedAssets};
+use crate::csrf;
+use crate::dev_reload::LiveReload;
+use crate::i18n::{I18n, Locale, SharedI18n, Translate};
+use crate::utils::e500;
+use crate::error::{AppError, ProblemDetails};
+use crate::security_headers::CspNonce;
 use crate::constants::{
     strings,
     html_templates,
+    route_paths,
 };
 
+/// Locale used by the formatting filters when none is given
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// The templates shared by every request. Behind a lock so they can be
+/// reloaded in place during development.
+pub type SharedTera = Arc<RwLock<tera::Tera>>;
+
+/// Renders a template, even if a reload panicked while holding the lock
+pub fn render(tera: &SharedTera, template_name: &str, context: &tera::Context) -> tera::Result<String> {
+    tera.read().unwrap_or_else(PoisonError::into_inner).render(template_name, context)
+}
+
 pub struct RenderTemplateParams<'a> {
     pub template_path: &'static str,
-    pub tera_store: &'a Arc<tera::Tera>,
+    pub tera_store: &'a SharedTera,
     pub template_context: Option<&'a tera::Context>,
+    /// Context shared by every page, overridden by `template_context`
+    pub globals: Option<&'a tera::Context>,
+    /// Render only this block of the template, leaving out its layout
+    pub block: Option<&'a str>,
 }
 
 impl<'a> RenderTemplateParams<'a> {
-    pub fn new(template_path: &'static str, tera_store: &'a Arc<tera::Tera>) -> Self {
+    pub fn new(template_path: &'static str, tera_store: &'a SharedTera) -> Self {
         Self {
             template_path,
             tera_store,
             template_context: None,
+            globals: None,
+            block: None,
         }
     }
 
@@ -24,28 +53,99 @@ impl<'a> RenderTemplateParams<'a> {
         self.template_context = Some(data);
         self
     }
+
+    pub fn with_globals(mut self, globals: &'a tera::Context) -> Self {
+        self.globals = Some(globals);
+        self
+    }
+
+    pub fn with_block(mut self, block: &'a str) -> Self {
+        self.block = Some(block);
+        self
+    }
 }
 
-pub fn render_content(render_template_params: &RenderTemplateParams<'_>) -> Result<String, ErrorResponse> {
-    // First set the context data
-    let context: tera::Context;
+pub fn render_content(render_template_params: &RenderTemplateParams<'_>) -> Result<String, AppError> {
+    // First set the context data, with the handler's own values taking precedence
+    let mut context = render_template_params.globals.cloned().unwrap_or_default();
     if let Some(data) = render_template_params.template_context {
-        context = data.clone(); // assuming `tera::Context` implements the Clone trait
-    } else {
-        context = tera::Context::new();
+        context.extend(data.clone());
+    }
+
+    match render_template_params.block {
+        Some(block) => render_block(render_template_params.tera_store, render_template_params.template_path, block, &context),
+        None => render(render_template_params.tera_store, render_template_params.template_path, &context),
+    }.map_err(e500)
+}
+
+/// Renders a single block of a template, as it would appear in the whole
+/// page: blocks inside it and `super()` resolve against the templates it
+/// extends. Tera can only render whole templates, so this renders a copy of
+/// the template, `<template>#<block>`, that extends a layout made of just
+/// that block, `#<block>`. Both are added the first time they are needed;
+/// reloading the templates drops them.
+pub fn render_block(tera: &SharedTera, template_name: &str, block_name: &str, context: &tera::Context) -> tera::Result<String> {
+    let partial_name = format!("{}#{}", template_name, block_name);
+    {
+        let tera = tera.read().unwrap_or_else(PoisonError::into_inner);
+        if tera.templates.contains_key(&partial_name) {
+            return tera.render(&partial_name, context);
+        }
     }
 
-    Ok(render_template_params.tera_store.render(&render_template_params.template_path, &context).map_err(e500)?)
+    let mut tera = tera.write().unwrap_or_else(PoisonError::into_inner);
+    let template = tera.get_template(template_name)?;
+    let has_block = template.blocks.contains_key(block_name)
+        || template.parents.iter().any(|parent| {
+            tera.templates.get(parent).is_some_and(|parent| parent.blocks.contains_key(block_name))
+        });
+    if !has_block {
+        return Err(tera::Error::msg(format!("{} has no block named {}", template_name, block_name)));
+    }
+
+    let layout_name = format!("#{}", block_name);
+    let mut partial = template.clone();
+    partial.name.clone_from(&partial_name);
+    partial.parents.push(layout_name.clone());
+    if let std::collections::hash_map::Entry::Vacant(entry) = tera.templates.entry(layout_name) {
+        let source = format!("{{% block {0} %}}{{% endblock {0} %}}", block_name);
+        let layout = tera::Template::new(entry.key(), None, &source)?;
+        entry.insert(layout);
+    }
+    tera.templates.insert(partial_name.clone(), partial);
+    tera.render(&partial_name, context)
 }
 
-pub fn err_500_template<E: std::fmt::Display>(tr: &Arc<tera::Tera>, error: E) -> String {
-    let error_description = format!("{}", error);
+/// The context of every error page. There is no `Render` for them, so
+/// only the problem, the nonce and the language are known.
+fn error_page_context(problem: &ProblemDetails, csp_nonce: Option<&CspNonce>, locale: Option<&Locale>) -> tera::Context {
     let mut context = tera::Context::new();
-    context.insert("error_description", &error_description);
-    tr.render(html_templates::E500, &context).unwrap_or_else(|_| String::from(strings::INTERNAL_SERVER_ERROR))
+    context.insert("problem", problem);
+    context.insert("csp_nonce", csp_nonce.map(AsRef::as_ref).unwrap_or_default());
+    context.insert("locale", &locale.cloned().unwrap_or_default());
+    context
 }
 
-pub fn currency_format(value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+/// Renders the 500 page. Whatever broke may also break the template, so
+/// this falls back to plain text.
+pub fn err_500_template(tr: &SharedTera, problem: &ProblemDetails, csp_nonce: Option<&CspNonce>, locale: Option<&Locale>) -> String {
+    let context = error_page_context(problem, csp_nonce, locale);
+    render(tr, html_templates::E500, &context).unwrap_or_else(|_| String::from(strings::INTERNAL_SERVER_ERROR))
+}
+
+/// Renders the branded page for an error: `404.html`, `500.html`, or
+/// `error.html` for every other status. Falls back to the bare title if the
+/// template itself fails to render.
+pub fn error_page_template(tr: &SharedTera, problem: &ProblemDetails, csp_nonce: Option<&CspNonce>, locale: Option<&Locale>) -> String {
+    let template = match problem.status {
+        404 => html_templates::E404,
+        500 => return err_500_template(tr, problem, csp_nonce, locale),
+        _ => html_templates::ERROR,
+    };
+    render(tr, template, &error_page_context(problem, csp_nonce, locale)).unwrap_or_else(|_| problem.title.clone())
+}
+
+pub fn currency_format(value: &tera::Value, _: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
     match value.as_f64() {
         Some(num) => {
             // Format the number as currency here. This is a simple example.
@@ -62,7 +162,7 @@ pub fn currency_format(value: &tera::Value, _: &std::collections::HashMap<String
     }
 }
 
-pub fn round_hundreths(value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+pub fn round_hundreths(value: &tera::Value, _: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
     match value.as_f64() {
         Some(num) => {
             // Format the number as currency here. This is a simple example.
@@ -78,3 +178,389 @@ pub fn round_hundreths(value: &tera::Value, _: &std::collections::HashMap<String
         None => Err("Failed to format value as f64 in round_hundreths".into()),
     }
 }
+
+/// Registers every filter and function templates can use. Called once on the
+/// `Tera` instance in `Application::build`; reloaded copies keep them.
+pub fn register(tera: &mut tera::Tera, assets: SharedAssets, i18n: SharedI18n, live_reload: bool) {
+    tera.register_filter("currency_format", currency_format);
+    tera.register_filter("round_hundreths", round_hundreths);
+    tera.register_filter("currency", currency);
+    tera.register_filter("number", number);
+    tera.register_filter("relative_time", RelativeTime(i18n.clone()));
+    tera.register_filter("pluralize", Pluralize(i18n.clone()));
+    tera.register_filter("markdown", Markdown);
+    tera.register_function("url_for", url_for);
+    tera.register_function("csrf_field", csrf::CsrfField);
+    tera.register_function("asset", AssetUrl(assets));
+    tera.register_function("t", Translate(i18n));
+    tera.register_function("live_reload", LiveReload(live_reload));
+}
+
+/// Separators and currency placement of a locale. Locales other than the
+/// ones below are formatted as `en-US`.
+struct NumberFormat {
+    decimal: char,
+    group: &'static str,
+    symbol_first: bool,
+}
+
+impl NumberFormat {
+    fn for_locale(locale: &str) -> Self {
+        let language = locale.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "de" | "es" | "it" | "nl" | "pt" => Self { decimal: ',', group: ".", symbol_first: false },
+            // A narrow no-break space, so amounts don't wrap
+            "fr" => Self { decimal: ',', group: "\u{202f}", symbol_first: false },
+            _ => Self { decimal: '.', group: ",", symbol_first: true },
+        }
+    }
+
+    /// The absolute value with `decimals` digits and grouped thousands
+    fn digits(&self, value: f64, decimals: usize) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (whole, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let mut grouped = String::new();
+        for (index, digit) in whole.chars().enumerate() {
+            if index > 0 && (whole.len() - index) % 3 == 0 {
+                grouped.push_str(self.group);
+            }
+            grouped.push(digit);
+        }
+        if !fraction.is_empty() {
+            grouped.push(self.decimal);
+            grouped.push_str(fraction);
+        }
+        grouped
+    }
+}
+
+fn str_arg<'a>(args: &'a HashMap<String, tera::Value>, name: &str, default: &'a str) -> &'a str {
+    args.get(name).and_then(|value| value.as_str()).unwrap_or(default)
+}
+
+fn is_negative(value: f64, decimals: usize) -> bool {
+    // Rounds to zero without a sign, like "-0.001" to "0.00"
+    value < 0.0 && format!("{:.*}", decimals, value.abs()).bytes().any(|b| matches!(b, b'1'..=b'9'))
+}
+
+/// `{{ 1234.5 | number(decimals=2, locale="de-DE") }}` renders `1.234,50`
+pub fn number(value: &tera::Value, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    let num = value.as_f64().ok_or("number can only format numbers")?;
+    let decimals = args.get("decimals").and_then(|decimals| decimals.as_u64()).unwrap_or(0) as usize;
+    let format = NumberFormat::for_locale(str_arg(args, "locale", DEFAULT_LOCALE));
+    let sign = if is_negative(num, decimals) { "-" } else { "" };
+    Ok(tera::Value::String(format!("{}{}", sign, format.digits(num, decimals))))
+}
+
+/// `{{ 1234.5 | currency(code="EUR", locale="de-DE") }}` renders `1.234,50 €`.
+/// `code` defaults to USD.
+pub fn currency(value: &tera::Value, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    let num = value.as_f64().ok_or("currency can only format numbers")?;
+    let code = str_arg(args, "code", "USD").to_ascii_uppercase();
+    let (symbol, decimals) = match code.as_str() {
+        "USD" => ("$", 2),
+        "EUR" => ("€", 2),
+        "GBP" => ("£", 2),
+        "JPY" => ("¥", 0),
+        _ => (code.as_str(), 2),
+    };
+    let format = NumberFormat::for_locale(str_arg(args, "locale", DEFAULT_LOCALE));
+    let sign = if is_negative(num, decimals) { "-" } else { "" };
+    let digits = format.digits(num, decimals);
+    let formatted = if format.symbol_first {
+        format!("{}{}{}", sign, symbol, digits)
+    } else {
+        format!("{}{}\u{a0}{}", sign, digits, symbol)
+    };
+    Ok(tera::Value::String(formatted))
+}
+
+/// `{{ notification.created_at | relative_time(timezone="Europe/Berlin", lang=locale) }}`
+/// renders `3 minutes ago`, `yesterday at 14:05` or `on 4 March 2024` in the
+/// language of `lang`, with days counted in the timezone. Takes RFC 3339
+/// timestamps, as `OffsetDateTime` fields are serialized. Unknown timezones
+/// fall back to UTC.
+pub struct RelativeTime(pub SharedI18n);
+
+impl tera::Filter for RelativeTime {
+    fn filter(&self, value: &tera::Value, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let timestamp = value.as_str().ok_or("relative_time can only format RFC 3339 timestamps")?;
+        let time = OffsetDateTime::parse(timestamp, &Rfc3339)
+            .map_err(|err| format!("relative_time can't parse {}: {}", timestamp, err))?;
+        let timezone = timezones::get_by_name(str_arg(args, "timezone", "UTC")).unwrap_or(timezones::db::UTC);
+        let locale = self.0.locale_arg(args);
+        let formatted = format_relative_time(time, OffsetDateTime::now_utc(), timezone, &self.0, &locale);
+        Ok(tera::Value::String(formatted))
+    }
+}
+
+fn format_relative_time(time: OffsetDateTime, now: OffsetDateTime, timezone: &Tz, i18n: &I18n, locale: &Locale) -> String {
+    let (time, now) = (time.to_timezone(timezone), now.to_timezone(timezone));
+    let elapsed = now - time;
+    // The catalogs pick the plural form of the unit
+    let ago = |count: i64, unit: &str| {
+        let key = if elapsed.is_negative() { format!("relative-in-{}", unit) } else { format!("relative-{}-ago", unit) };
+        i18n.translate(locale, &key, &serde_json::json!({ "count": count }))
+    };
+
+    let seconds = elapsed.whole_seconds().abs();
+    if seconds < 45 {
+        return i18n.message(locale, "relative-just-now");
+    }
+    if seconds < 60 * 60 {
+        return ago((seconds / 60).max(1), "minutes");
+    }
+    if seconds < 24 * 60 * 60 {
+        return ago(seconds / (60 * 60), "hours");
+    }
+    let days = (now.date() - time.date()).whole_days();
+    if days == 1 {
+        let clock = format!("{:02}:{:02}", time.hour(), time.minute());
+        return i18n.translate(locale, "relative-yesterday", &serde_json::json!({ "time": clock }));
+    }
+    if (2..7).contains(&days) {
+        return ago(days, "days");
+    }
+    let date = serde_json::json!({
+        "day": time.day().to_string(),
+        "month": u8::from(time.month()),
+        "year": time.year().to_string(),
+    });
+    i18n.translate(locale, "relative-date", &date)
+}
+
+/// `{{ total | pluralize(key="audit-event-count", lang=locale) }}` renders the
+/// message `key` with the number as `$count`, so the catalog picks the plural
+/// form of the language.
+pub struct Pluralize(pub SharedI18n);
+
+impl tera::Filter for Pluralize {
+    fn filter(&self, value: &tera::Value, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let count = value.as_f64().ok_or("pluralize needs a number")?;
+        let key = args.get("key").and_then(|key| key.as_str()).ok_or("pluralize requires a `key` argument")?;
+        let locale = self.0.locale_arg(args);
+        Ok(tera::Value::String(self.0.translate(&locale, key, &serde_json::json!({ "count": count }))))
+    }
+}
+
+/// `{{ url_for(name="account_export_download", id=export.id) }}` renders
+/// `/account/export/<id>/download`. Names are the `route_paths` constants in
+/// lowercase. Arguments that aren't path parameters become the query string.
+pub fn url_for(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    let name = args.get("name").and_then(|name| name.as_str()).ok_or("url_for requires a `name` argument")?;
+    let (_, path) = route_paths::ALL
+        .iter()
+        .find(|(route, _)| route.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("url_for: there is no route named {}", name))?;
+
+    let param = |value: &tera::Value| match value {
+        tera::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    let mut used = vec!["name"];
+    let mut url = String::new();
+    for segment in path.split('/').skip(1) {
+        url.push('/');
+        match segment.strip_prefix(':') {
+            Some(key) => {
+                let value = args.get(key).ok_or_else(|| format!("url_for: {} needs a `{}` argument", name, key))?;
+                url.push_str(&encode_path_segment(&param(value)));
+                used.push(key);
+            },
+            None => url.push_str(segment),
+        }
+    }
+
+    let mut query: Vec<(&String, String)> = args
+        .iter()
+        .filter(|(key, _)| !used.contains(&key.as_str()))
+        .map(|(key, value)| (key, param(value)))
+        .collect();
+    if !query.is_empty() {
+        // Sorted so the same arguments always give the same URL
+        query.sort();
+        url.push('?');
+        url.push_str(&serde_urlencoded::to_string(&query).map_err(|err| err.to_string())?);
+    }
+
+    Ok(tera::Value::String(url))
+}
+
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `{{ notification.body | markdown }}` renders markdown as HTML. The HTML is
+/// sanitized, so it is safe for user supplied text.
+pub struct Markdown;
+
+impl tera::Filter for Markdown {
+    fn filter(&self, value: &tera::Value, _: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let text = value.as_str().ok_or("markdown can only render strings")?;
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new_ext(text, pulldown_cmark::Options::all()));
+        Ok(tera::Value::String(ammonia::clean(&html)))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::LOCALES_DIR;
+    use tera::Filter;
+    use time::macros::datetime;
+
+    fn args(pairs: &[(&str, tera::Value)]) -> HashMap<String, tera::Value> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.clone())).collect()
+    }
+
+    fn string(result: tera::Result<tera::Value>) -> String {
+        result.unwrap().as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn numbers_follow_the_locale() {
+        let value = tera::Value::from(1234567.891);
+        assert_eq!(string(number(&value, &args(&[("decimals", 2.into())]))), "1,234,567.89");
+        assert_eq!(string(number(&value, &args(&[("locale", "de-DE".into())]))), "1.234.568");
+        assert_eq!(string(number(&tera::Value::from(-0.001), &args(&[("decimals", 2.into())]))), "0.00");
+        assert_eq!(string(number(&tera::Value::from(-12), &args(&[]))), "-12");
+    }
+
+    #[test]
+    fn currency_follows_the_locale() {
+        let value = tera::Value::from(-1234.5);
+        assert_eq!(string(currency(&value, &args(&[]))), "-$1,234.50");
+        assert_eq!(
+            string(currency(&value, &args(&[("code", "EUR".into()), ("locale", "de-DE".into())]))),
+            "-1.234,50\u{a0}€",
+        );
+        assert_eq!(string(currency(&tera::Value::from(1500), &args(&[("code", "JPY".into())]))), "¥1,500");
+        assert_eq!(string(currency(&tera::Value::from(3), &args(&[("code", "CHF".into())]))), "CHF3.00");
+    }
+
+    #[test]
+    fn relative_times_count_days_in_the_timezone() {
+        let i18n = I18n::load(LOCALES_DIR).unwrap();
+        let english = Locale::default();
+        let now = datetime!(2024-03-10 00:30 UTC);
+        let relative = |time, timezone| format_relative_time(time, now, timezone, &i18n, &english);
+        let utc = timezones::db::UTC;
+        let berlin = timezones::get_by_name("Europe/Berlin").unwrap();
+
+        assert_eq!(relative(now - time::Duration::seconds(10), utc), "just now");
+        assert_eq!(relative(now - time::Duration::minutes(3), utc), "3 minutes ago");
+        assert_eq!(relative(now - time::Duration::hours(1), utc), "1 hour ago");
+        assert_eq!(relative(now + time::Duration::hours(2), utc), "in 2 hours");
+        assert_eq!(relative(datetime!(2024-03-07 12:00 UTC), utc), "3 days ago");
+        assert_eq!(relative(datetime!(2024-02-01 12:00 UTC), utc), "on 1 February 2024");
+        // 23:30 UTC on the 8th is already 00:30 on the 9th in Berlin
+        let time = datetime!(2024-03-08 23:30 UTC);
+        assert_eq!(relative(time, utc), "2 days ago");
+        assert_eq!(relative(time, berlin), "yesterday at 00:30");
+    }
+
+    #[test]
+    fn relative_times_are_translated() {
+        let i18n = I18n::load(LOCALES_DIR).unwrap();
+        let german = i18n.supported("de").unwrap();
+        let now = datetime!(2024-03-10 12:00 UTC);
+        let utc = timezones::db::UTC;
+
+        assert_eq!(format_relative_time(now - time::Duration::minutes(1), now, utc, &i18n, &german), "vor 1 Minute");
+        assert_eq!(format_relative_time(now + time::Duration::hours(5), now, utc, &i18n, &german), "in 5 Stunden");
+        assert_eq!(format_relative_time(datetime!(2024-03-09 08:05 UTC), now, utc, &i18n, &german), "gestern um 08:05");
+        assert_eq!(format_relative_time(datetime!(2023-12-24 12:00 UTC), now, utc, &i18n, &german), "am 24. Dezember 2023");
+    }
+
+    #[test]
+    fn pluralize_picks_the_form_of_the_locale() {
+        let pluralize = Pluralize(Arc::new(I18n::load(LOCALES_DIR).unwrap()));
+        let english = args(&[("key", "audit-event-count".into())]);
+        assert_eq!(string(pluralize.filter(&tera::Value::from(1), &english)), "1 event");
+        assert_eq!(string(pluralize.filter(&tera::Value::from(0), &english)), "0 events");
+        let german = args(&[("key", "audit-event-count".into()), ("lang", "de".into())]);
+        assert_eq!(string(pluralize.filter(&tera::Value::from(2), &german)), "2 Ereignisse");
+        assert!(pluralize.filter(&tera::Value::from(2), &args(&[])).is_err());
+    }
+
+    #[test]
+    fn url_for_fills_in_route_parameters() {
+        assert_eq!(string(url_for(&args(&[("name", "login".into())]))), "/login");
+        assert_eq!(
+            string(url_for(&args(&[("name", "ACCOUNT_EXPORT_DOWNLOAD".into()), ("id", "a b".into())]))),
+            "/account/export/a%20b/download",
+        );
+        assert_eq!(
+            string(url_for(&args(&[("name", "admin_audit".into()), ("page", 2.into()), ("action", "login".into())]))),
+            "/admin/audit?action=login&page=2",
+        );
+        assert!(url_for(&args(&[("name", "account_export_download".into())])).is_err());
+        assert!(url_for(&args(&[("name", "nowhere".into())])).is_err());
+    }
+
+    #[test]
+    fn markdown_is_sanitized() {
+        let value = tera::Value::from("**bold** <script>alert(1)</script> [link](javascript:alert(1))");
+        let html = string(tera::Filter::filter(&Markdown, &value, &HashMap::new()));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn blocks_render_without_their_layout() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates([
+            ("base.html", "<html>{% block content %}base{% endblock content %}{% block footer %}footer{% endblock footer %}</html>"),
+            ("page.html", "{% extends \"base.html\" %}{% block content %}{{ super() }} {% block form %}{{ name }}{% endblock form %}{% endblock content %}"),
+        ]).unwrap();
+        let tera = Arc::new(RwLock::new(tera));
+        let mut context = tera::Context::new();
+        context.insert("name", "form");
+
+        assert_eq!(render_block(&tera, "page.html", "content", &context).unwrap(), "base form");
+        assert_eq!(render_block(&tera, "page.html", "form", &context).unwrap(), "form");
+        assert_eq!(render_block(&tera, "page.html", "footer", &context).unwrap(), "footer");
+        assert!(render_block(&tera, "page.html", "missing", &context).is_err());
+        assert_eq!(render(&tera, "page.html", &context).unwrap(), "<html>base formfooter</html>");
+    }
+
+    #[test]
+    fn error_pages_are_picked_by_status() {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates([
+            (html_templates::E404, "404 {{ locale }}"),
+            (html_templates::E500, "500 {{ problem.correlation_id }}"),
+            (html_templates::ERROR, "{{ problem.status }} {{ problem.title }}"),
+        ]).unwrap();
+        let tera = Arc::new(RwLock::new(tera));
+        let problem = |status: u16, title: &str| ProblemDetails {
+            problem_type: "about:blank",
+            title: title.to_string(),
+            status,
+            detail: String::new(),
+            instance: None,
+            correlation_id: uuid::Uuid::nil(),
+            errors: crate::error::FieldErrors::new(),
+            message_keys: None,
+        };
+
+        assert_eq!(error_page_template(&tera, &problem(404, "Not Found"), None, None), "404 en");
+        assert_eq!(error_page_template(&tera, &problem(500, "Internal Server Error"), None, None), format!("500 {}", uuid::Uuid::nil()));
+        assert_eq!(error_page_template(&tera, &problem(403, "Forbidden"), None, None), "403 Forbidden");
+        // A broken 500 page still says what happened
+        let broken = Arc::new(RwLock::new(tera::Tera::default()));
+        assert_eq!(err_500_template(&broken, &problem(500, "Internal Server Error"), None, None), strings::INTERNAL_SERVER_ERROR);
+    }
+}
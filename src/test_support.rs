@@ -0,0 +1,50 @@
+//! src/test_support.rs
+//! Fixtures shared by the unit tests.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A fresh directory under the system temp dir, removed when dropped
+pub struct TempDir {
+    root: PathBuf,
+}
+
+impl TempDir {
+    /// `prefix` names the directory after the tests using it
+    pub fn new(prefix: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("{}-{}", prefix, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Writes a file, creating its directories. It's dated a little in the
+    /// future, so watchers see the change even on filesystems with coarse
+    /// timestamps.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(2);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.root.join(path)).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
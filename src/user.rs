@@ -1,15 +1,25 @@
 use async_trait::async_trait;
-use axum_login::{AuthUser, AuthnBackend, UserId};
+use axum_login::{AuthUser, AuthnBackend, AuthzBackend, UserId};
+use std::collections::HashSet;
 use password_auth::verify_password;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 use tokio::task;
+use uuid::Uuid;
+use crate::audit::{self, AuditContext, NewAuditEvent};
+use crate::domain::{AuditAction, SafeRedirect, UserEmail, UserPassword};
 
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     id: uuid::Uuid,
     pub email: String,
     password_hash: String,
+    /// Set when email to this address bounced or was marked as spam
+    pub email_undeliverable: bool,
+    /// IANA name of the timezone dates and times are shown in
+    pub timezone: String,
+    /// Language pages are shown in. Negotiated with the browser when unset.
+    pub locale: Option<String>,
 }
 
 // Here we've implemented `Debug` manually to avoid accidentally logging the
@@ -20,6 +30,9 @@ impl std::fmt::Debug for User {
             .field("id", &self.id)
             .field("email", &self.email)
             .field("password_hash", &"[redacted]")
+            .field("email_undeliverable", &self.email_undeliverable)
+            .field("timezone", &self.timezone)
+            .field("locale", &self.locale)
             .finish()
     }
 }
@@ -45,7 +58,8 @@ impl AuthUser for User {
 pub struct Credentials {
     pub email: String,
     pub password: String,
-    pub next: Option<String>,
+    #[serde(default, deserialize_with = "SafeRedirect::deserialize_lenient")]
+    pub next: Option<SafeRedirect>,
 }
 
 #[derive(Debug, Clone)]
@@ -57,6 +71,128 @@ impl Backend {
     pub fn new(db: PgPool) -> Self {
         Self { db }
     }
+
+    /// Changes the user's password, which also ends their other sessions.
+    /// `actor_id` is whoever made the change, which is not always the user.
+    pub async fn change_password(
+        &self,
+        user_id: Uuid,
+        password: UserPassword,
+        actor_id: Uuid,
+        context: &AuditContext,
+    ) -> Result<(), Error> {
+        let password_hash = task::spawn_blocking(move || password_auth::generate_hash(password)).await?;
+
+        let mut transaction = self.db.begin().await?;
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+        let event = NewAuditEvent::new(AuditAction::PasswordChanged).actor(actor_id).target(user_id);
+        audit::record(&mut *transaction, &event, context).await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Changes the user's email. The new address has not bounced yet, so the
+    /// undeliverable flag is cleared.
+    pub async fn change_email(
+        &self,
+        user_id: Uuid,
+        email: UserEmail,
+        actor_id: Uuid,
+        context: &AuditContext,
+    ) -> Result<(), Error> {
+        let mut transaction = self.db.begin().await?;
+        sqlx::query("UPDATE users SET email = $1, email_undeliverable = FALSE WHERE id = $2")
+            .bind(email.as_ref())
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+        let event = NewAuditEvent::new(AuditAction::EmailChanged).actor(actor_id).target(user_id);
+        audit::record(&mut *transaction, &event, context).await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Sets the language and timezone pages are shown in. A `None` locale
+    /// follows the browser.
+    pub async fn update_preferences(&self, user_id: Uuid, locale: Option<&str>, timezone: &str) -> Result<(), Error> {
+        sqlx::query("UPDATE users SET locale = $1, timezone = $2 WHERE id = $3")
+            .bind(locale)
+            .bind(timezone)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gives the user a role. Returns false if they already had it.
+    pub async fn grant_role(&self, user_id: Uuid, role: &str, actor_id: Uuid, context: &AuditContext) -> Result<bool, Error> {
+        let mut transaction = self.db.begin().await?;
+        let granted = sqlx::query(
+            "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = $2
+            ON CONFLICT DO NOTHING"
+        )
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected() > 0;
+        if granted {
+            let event = NewAuditEvent::new(AuditAction::RoleGranted)
+                .actor(actor_id)
+                .target(user_id)
+                .metadata(serde_json::json!({ "role": role }));
+            audit::record(&mut *transaction, &event, context).await?;
+        }
+        transaction.commit().await?;
+
+        Ok(granted)
+    }
+
+    /// Takes a role away from the user. Returns false if they didn't have it.
+    pub async fn revoke_role(&self, user_id: Uuid, role: &str, actor_id: Uuid, context: &AuditContext) -> Result<bool, Error> {
+        let mut transaction = self.db.begin().await?;
+        let revoked = sqlx::query(
+            "DELETE FROM user_roles WHERE user_id = $1 AND role_id = (SELECT id FROM roles WHERE name = $2)"
+        )
+            .bind(user_id)
+            .bind(role)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected() > 0;
+        if revoked {
+            let event = NewAuditEvent::new(AuditAction::RoleRevoked)
+                .actor(actor_id)
+                .target(user_id)
+                .metadata(serde_json::json!({ "role": role }));
+            audit::record(&mut *transaction, &event, context).await?;
+        }
+        transaction.commit().await?;
+
+        Ok(revoked)
+    }
+
+    /// Checks the user's current password, to confirm a sensitive action
+    pub async fn check_password(&self, user: &User, password: String) -> Result<bool, Error> {
+        let password_hash = user.password_hash.clone();
+        Ok(task::spawn_blocking(move || verify_password(password, &password_hash).is_ok()).await?)
+    }
+
+    /// The id of the account using `email`, if any
+    pub async fn find_user_id(&self, email: &str) -> Result<Option<Uuid>, Error> {
+        let user_id: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(user_id.map(|(id,)| id))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -95,7 +231,7 @@ impl AuthnBackend for Backend {
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
         let user = sqlx::query_as("SELECT * FROM users WHERE id = $1")
-            .bind(&user_id)
+            .bind(user_id)
             .fetch_optional(&self.db)
             .await?;
 
@@ -103,6 +239,40 @@ impl AuthnBackend for Backend {
     }
 }
 
+/// A role from the `roles` table. Roles are used as axum-login group permissions so
+/// routes can be guarded with `permission_required!(Backend, roles::ADMIN)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, FromRow)]
+pub struct Role {
+    pub name: String,
+}
+
+impl From<&str> for Role {
+    fn from(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+}
+
+#[async_trait]
+impl AuthzBackend for Backend {
+    type Permission = Role;
+
+    async fn get_group_permissions(
+        &self,
+        user: &Self::User,
+    ) -> Result<HashSet<Self::Permission>, Self::Error> {
+        let roles: Vec<Role> = sqlx::query_as(
+            "SELECT roles.name FROM roles
+            INNER JOIN user_roles ON user_roles.role_id = roles.id
+            WHERE user_roles.user_id = $1"
+        )
+            .bind(user.id)
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(roles.into_iter().collect())
+    }
+}
+
 // We use a type alias for convenience.
 //
 // Note that we've supplied our concrete backend here.
@@ -1,35 +1,16 @@
-use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
-};
 use std::fmt::Debug;
+use crate::error::AppError;
 
 // Custom error handler function
-pub fn e500<T>(e: T) -> ErrorResponse
+pub fn e500<T>(e: T) -> AppError
 where
     T: Debug + std::fmt::Display + 'static,
 {
-    ErrorResponse::InternalServerError(e.to_string())
+    AppError::Internal(e.to_string())
 }
 
-// Custom error response struct
-pub struct ErrorResponse {
-    status_code: StatusCode,
-    message: String,
-}
-
-impl ErrorResponse {
-    #[allow(non_snake_case)]
-    pub fn InternalServerError(message: String) -> Self {
-        Self {
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            message,
-        }
-    }
-}
-
-impl IntoResponse for ErrorResponse {
-    fn into_response(self) -> Response {
-        (self.status_code, self.message).into_response()
-    }
+/// Compares secrets without returning early on the first mismatch
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
@@ -0,0 +1,47 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+
+#[tokio::test]
+async fn notifications_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_account_notifications().await;
+    assert_is_redirect_to(&response, "/login?next=%2Faccount%2Fnotifications");
+}
+
+#[tokio::test]
+async fn notifications_default_to_opted_in() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.get_account_notifications().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains(r#"name="product_updates" id="product_updates" value="true" checked"#));
+    assert!(html_page.contains(r#"name="digests" id="digests" value="true" checked"#));
+}
+
+#[tokio::test]
+async fn notifications_can_be_updated() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    // Only the digests checkbox is ticked
+    let body = serde_json::json!({ "digests": "true" });
+    let response = app.post_account_notifications(&body).await;
+    assert_is_redirect_to(&response, "/account/notifications");
+
+    let saved = sqlx::query!(
+        "SELECT product_updates, digests FROM notification_preferences WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved preferences.");
+    assert!(!saved.product_updates);
+    assert!(saved.digests);
+
+    let html_page = app.get_account_notifications().await.text().await.unwrap();
+    assert!(html_page.contains(r#"name="product_updates" id="product_updates" value="true" />"#));
+    assert!(html_page.contains(r#"name="digests" id="digests" value="true" checked"#));
+}
@@ -0,0 +1,106 @@
+use axum_sass_template::account_deletion;
+use crate::helpers::{spawn_app, assert_is_redirect_to, TestApp};
+
+async fn is_scheduled(app: &TestApp) -> bool {
+    account_deletion::scheduled_for(&app.db_pool, app.test_user.user_id).await.unwrap().is_some()
+}
+
+#[tokio::test]
+async fn deletion_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_account_delete().await;
+    assert_is_redirect_to(&response, "/login?next=%2Faccount%2Fdelete");
+}
+
+#[tokio::test]
+async fn deletion_requires_the_current_password() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_account_delete("not my password").await;
+    assert_is_redirect_to(&response, "/account/delete");
+    assert!(!is_scheduled(&app).await);
+    let html_page = app.get_account_delete().await.text().await.unwrap();
+    assert!(html_page.contains("Incorrect password"));
+}
+
+#[tokio::test]
+async fn deletion_is_scheduled_and_can_be_cancelled() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_account_delete(&app.test_user.password).await;
+    assert_is_redirect_to(&response, "/account/delete");
+    assert!(is_scheduled(&app).await);
+    let html_page = app.get_account_delete().await.text().await.unwrap();
+    assert!(html_page.contains("Your account will be deleted on"));
+
+    let response = app.post_cancel_account_delete().await;
+    assert_is_redirect_to(&response, "/account/delete");
+    assert!(!is_scheduled(&app).await);
+
+    let actions: Vec<(String,)> = sqlx::query_as(
+        "SELECT action FROM audit_events WHERE action LIKE 'deletion_%' ORDER BY created_at"
+    )
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(actions, [("deletion_requested".to_string(),), ("deletion_cancelled".to_string(),)]);
+}
+
+#[tokio::test]
+async fn accounts_are_deleted_after_the_grace_period() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    app.login_test_user().await;
+    app.post_account_delete(&app.test_user.password).await;
+    let session_ids: Vec<String> = sqlx::query_scalar("SELECT session_id FROM user_sessions WHERE user_id = $1")
+        .bind(app.test_user.user_id)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(session_ids.len(), 1);
+
+    assert_eq!(account_deletion::delete_due(&app.db_pool).await.unwrap(), 0);
+    sqlx::query("UPDATE account_deletions SET scheduled_for = NOW() - INTERVAL '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(account_deletion::delete_due(&app.db_pool).await.unwrap(), 1);
+
+    let (users, roles): (i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM users WHERE id = $1), (SELECT COUNT(*) FROM user_roles WHERE user_id = $1)"
+    )
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!((users, roles), (0, 0));
+    let (deleted_events,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM audit_events WHERE action = 'account_deleted' AND target_id = $1"
+    )
+        .bind(app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deleted_events, 1);
+    let (events_with_email,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM audit_events WHERE position($1 in metadata::text) > 0"
+    )
+        .bind(&app.test_user.email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events_with_email, 0);
+
+    // The session was deleted with the account
+    let (sessions,): (i64,) = sqlx::query_as(r#"SELECT COUNT(*) FROM "tower_sessions"."session" WHERE id = ANY($1)"#)
+        .bind(&session_ids)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(sessions, 0);
+    let response = app.get_account_delete().await;
+    assert_is_redirect_to(&response, "/login?next=%2Faccount%2Fdelete");
+}
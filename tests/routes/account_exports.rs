@@ -0,0 +1,115 @@
+use axum_sass_template::account_exports;
+use std::io::Read;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::helpers::{spawn_app, assert_is_redirect_to, TestApp};
+
+/// Waits for the background job to build the user's export
+async fn wait_for_export(app: &TestApp) -> Uuid {
+    for _ in 0..50 {
+        let ready: Option<(Uuid,)> = sqlx::query_as(
+            "SELECT id FROM account_exports WHERE user_id = $1 AND status = 'ready'"
+        )
+            .bind(app.test_user.user_id)
+            .fetch_optional(&app.db_pool)
+            .await
+            .unwrap();
+        if let Some((export_id,)) = ready {
+            return export_id;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The export was not built in time");
+}
+
+fn read_file(zip: &mut zip::ZipArchive<std::io::Cursor<Vec<u8>>>, name: &str) -> serde_json::Value {
+    let mut contents = String::new();
+    zip.by_name(name).unwrap().read_to_string(&mut contents).unwrap();
+    serde_json::from_str(&contents).unwrap()
+}
+
+#[tokio::test]
+async fn export_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_account_export().await;
+    assert_is_redirect_to(&response, "/login?next=%2Faccount%2Fexport");
+}
+
+#[tokio::test]
+async fn requested_export_is_built_and_downloadable() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_account_export().await;
+    assert_is_redirect_to(&response, "/account/export");
+    let export_id = wait_for_export(&app).await;
+
+    let html_page = app.get_account_export().await.text().await.unwrap();
+    assert!(html_page.contains("Download"));
+
+    let response = app.get_account_export_download(export_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/zip");
+    let archive = response.bytes().await.unwrap().to_vec();
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+
+    let profile = read_file(&mut zip, "profile.json");
+    assert_eq!(profile["email"], app.test_user.email);
+    let sessions = read_file(&mut zip, "sessions.json");
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    let audit_events = read_file(&mut zip, "audit_events.json");
+    let actions: Vec<&str> = audit_events.as_array().unwrap().iter().map(|event| event["action"].as_str().unwrap()).collect();
+    assert!(actions.contains(&"login"));
+    assert!(actions.contains(&"data_export_requested"));
+}
+
+#[tokio::test]
+async fn only_one_export_is_in_progress_at_a_time() {
+    let app = spawn_app().await;
+
+    let first = account_exports::request(&app.db_pool, app.test_user.user_id).await.unwrap();
+    let second = account_exports::request(&app.db_pool, app.test_user.user_id).await.unwrap();
+    assert!(first.is_some());
+    assert!(second.is_none());
+}
+
+#[tokio::test]
+async fn expired_exports_cannot_be_downloaded() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.post_account_export().await;
+    let export_id = wait_for_export(&app).await;
+
+    sqlx::query("UPDATE account_exports SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+        .bind(export_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.get_account_export_download(export_id).await;
+    assert_is_redirect_to(&response, "/account/export");
+
+    assert_eq!(account_exports::prune(&app.db_pool).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn exports_of_other_users_cannot_be_downloaded() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let other_user: (Uuid,) = sqlx::query_as(
+        "INSERT INTO users (id, email, password_hash) VALUES ($1, 'other@example.com', 'hash') RETURNING id"
+    )
+        .bind(Uuid::new_v4())
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let export_id = account_exports::request(&app.db_pool, other_user.0).await.unwrap().unwrap();
+    sqlx::query("UPDATE account_exports SET status = 'ready', archive = 'zip', expires_at = NOW() + INTERVAL '1 hour'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.get_account_export_download(export_id).await;
+    assert_is_redirect_to(&response, "/account/export");
+}
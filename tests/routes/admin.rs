@@ -0,0 +1,56 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+
+#[tokio::test]
+async fn suppressions_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_admin_suppressions().await;
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fsuppressions");
+}
+
+#[tokio::test]
+async fn suppressions_are_forbidden_for_non_admins() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.get_admin_suppressions().await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("403 Forbidden"));
+}
+
+#[tokio::test]
+async fn admin_can_view_and_clear_suppressions() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    app.login_test_user().await;
+
+    let body = serde_json::json!({
+        "events": [{ "type": "complaint", "email": app.test_user.email }]
+    }).to_string();
+    let signature = app.sign_webhook(body.as_bytes());
+    app.post_email_events(&body, "application/json", &signature).await;
+
+    let response = app.get_admin_suppressions().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&app.test_user.email.to_lowercase()));
+
+    let suppression = sqlx::query!("SELECT id FROM email_suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.post_clear_suppression(suppression.id).await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    let remaining = sqlx::query!("SELECT id FROM email_suppressions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(remaining.is_empty());
+    let user = sqlx::query!("SELECT email_undeliverable FROM users WHERE id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!user.email_undeliverable);
+}
@@ -0,0 +1,233 @@
+use axum_sass_template::domain::NotificationKind;
+use axum_sass_template::notifications::{self, NewNotification};
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn openapi_document_lists_the_endpoints() {
+    let app = spawn_app().await;
+
+    let response = app.get_api("/openapi.json").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let spec: serde_json::Value = response.json().await.unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+    assert_eq!(spec["servers"][0]["url"], "/api/v1");
+    for path in ["/users/me", "/account/notification-preferences", "/account/tokens", "/account/preferences", "/notifications"] {
+        assert!(spec["paths"][path].is_object(), "{} is missing from the spec", path);
+    }
+    assert!(spec["components"]["schemas"]["ProblemDetails"].is_object());
+    assert!(spec["components"]["securitySchemes"]["bearer"].is_object());
+}
+
+#[tokio::test]
+async fn explorer_page_loads_the_spec() {
+    let app = spawn_app().await;
+
+    let response = app.get_api("/docs").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("data-openapi-url="));
+    assert!(html_page.contains("openapi.json"));
+}
+
+#[tokio::test]
+async fn anonymous_requests_get_a_problem_json_401() {
+    let app = spawn_app().await;
+
+    let response = app.get_api("/users/me").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 401);
+    assert_eq!(problem["instance"], "/api/v1/users/me");
+}
+
+#[tokio::test]
+async fn users_me_returns_the_logged_in_user() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.get_api("/users/me").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["email"], app.test_user.email.as_str());
+    assert_eq!(body["data"]["roles"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn users_me_works_with_an_api_token() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("read").await;
+
+    let response = app.bearer_client()
+        .get(format!("{}/api/v1/users/me", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["email"], app.test_user.email.as_str());
+}
+
+#[tokio::test]
+async fn notification_preferences_can_be_updated_with_a_write_token() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+    let client = app.bearer_client();
+    let url = format!("{}/api/v1/account/notification-preferences", &app.address);
+
+    let response = client
+        .put(&url)
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "product_updates": false, "digests": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = client.get(&url).bearer_auth(&token).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"], serde_json::json!({ "product_updates": false, "digests": true }));
+}
+
+#[tokio::test]
+async fn invalid_bodies_are_validation_errors() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+
+    let response = app.bearer_client()
+        .put(format!("{}/api/v1/account/notification-preferences", &app.address))
+        .bearer_auth(&token)
+        .header("Content-Type", "application/json")
+        .body(r#"{"digests": "yes"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert!(problem["errors"]["body"].is_array());
+}
+
+#[tokio::test]
+async fn lists_are_paginated() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    for _ in 0..3 {
+        app.create_api_token("read").await;
+    }
+
+    let body: serde_json::Value = app.get_api("/account/tokens?per_page=2").await.json().await.unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    assert_eq!(body["meta"], serde_json::json!({ "page": 1, "per_page": 2, "total": 3, "total_pages": 2 }));
+    assert_eq!(body["links"]["next"], "/api/v1/account/tokens?page=2&per_page=2");
+    assert!(body["data"][0].get("token_hash").is_none());
+
+    let response = app.get_api("/account/tokens?per_page=500").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert!(problem["errors"]["per_page"].is_array());
+}
+
+#[tokio::test]
+async fn preferences_can_be_updated_with_a_write_token() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+    let client = app.bearer_client();
+    let url = format!("{}/api/v1/account/preferences", &app.address);
+
+    let response = client
+        .put(&url)
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "locale": "de-AT", "timezone": "Europe/Berlin" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = client.get(&url).bearer_auth(&token).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["data"], serde_json::json!({ "locale": "de", "timezone": "Europe/Berlin" }));
+}
+
+#[tokio::test]
+async fn unknown_locales_and_timezones_are_validation_errors() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+
+    let response = app.bearer_client()
+        .put(format!("{}/api/v1/account/preferences", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "locale": "xx", "timezone": "Mars/Olympus_Mons" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert!(problem["errors"]["locale"].is_array());
+    assert!(problem["errors"]["timezone"].is_array());
+}
+
+#[tokio::test]
+async fn notifications_can_be_listed_and_marked_read() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+    let client = app.bearer_client();
+    for title in ["Your export is ready", "New sign in"] {
+        let new_notification = NewNotification::new(NotificationKind::Info, title);
+        notifications::create(&app.db_pool, app.test_user.user_id, &new_notification).await.unwrap();
+    }
+
+    let body: serde_json::Value = app.get_api("/notifications?per_page=1").await.json().await.unwrap();
+    assert_eq!(body["meta"]["total"], 2);
+    assert_eq!(body["data"][0]["title"], "New sign in");
+    assert_eq!(body["data"][0]["read_at"], serde_json::Value::Null);
+
+    let id = body["data"][0]["id"].as_str().unwrap();
+    let response = client
+        .post(format!("{}/api/v1/notifications/{}/read", &app.address, id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["data"]["read_at"].is_string());
+
+    let response = client
+        .post(format!("{}/api/v1/notifications/read-all", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(notifications::unread_count(&app.db_pool, app.test_user.user_id).await.unwrap(), 0);
+
+    let response = client
+        .post(format!("{}/api/v1/notifications/{}/read", &app.address, uuid::Uuid::new_v4()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn explorer_page_is_translated() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_with_accept_language("/api/v1/docs", "de").await.text().await.unwrap();
+    assert!(html_page.contains("API-Explorer"));
+    assert!(html_page.contains("Wird geladen…"));
+    assert!(!html_page.contains("Loading…"));
+}
@@ -0,0 +1,160 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+
+#[tokio::test]
+async fn new_tokens_are_shown_once() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let token = app.create_api_token("read").await;
+    assert!(token.starts_with("ast_"));
+
+    let html_page = app.get_account_tokens().await.text().await.unwrap();
+    assert!(html_page.contains("Test token"));
+    assert!(!html_page.contains(&token));
+
+    let stored: (String, Vec<String>) = sqlx::query_as("SELECT token_hash, scopes FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.0, token);
+    assert_eq!(stored.1, vec!["read"]);
+}
+
+#[tokio::test]
+async fn bearer_tokens_log_in_as_their_owner() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("read").await;
+
+    let response = app.bearer_client()
+        .get(format!("{}/protected", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let last_used: (Option<time::OffsetDateTime>,) = sqlx::query_as("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(last_used.0.is_some());
+}
+
+#[tokio::test]
+async fn read_only_tokens_cannot_make_changes() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("read").await;
+
+    let response = app.bearer_client()
+        .post(format!("{}/account/notifications", &app.address))
+        .bearer_auth(&token)
+        .form(&serde_json::json!({ "digests": "true" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn write_tokens_can_make_changes_without_a_csrf_token() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+
+    let response = app.bearer_client()
+        .post(format!("{}/account/notifications", &app.address))
+        .bearer_auth(&token)
+        .form(&serde_json::json!({ "digests": "true" }))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/account/notifications");
+}
+
+#[tokio::test]
+async fn invalid_tokens_are_unauthorized() {
+    let app = spawn_app().await;
+
+    for token in ["not-a-token", &format!("ast_{}_{}", "0".repeat(12), "0".repeat(64))] {
+        let response = app.bearer_client()
+            .get(format!("{}/protected", &app.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn revoked_and_expired_tokens_are_unauthorized() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let revoked = app.create_api_token("read").await;
+    let (token_id,): (uuid::Uuid,) = sqlx::query_as("SELECT id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.post_revoke_api_token(token_id).await;
+    assert_is_redirect_to(&response, "/account/tokens");
+
+    let expired = app.create_api_token("read").await;
+    sqlx::query("UPDATE api_tokens SET expires_at = NOW() - INTERVAL '1 day' WHERE revoked_at IS NULL")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    for token in [revoked, expired] {
+        let response = app.bearer_client()
+            .get(format!("{}/protected", &app.address))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn tokens_cannot_manage_tokens() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("write").await;
+
+    let response = app.bearer_client()
+        .get(format!("{}/account/tokens", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn invalid_token_forms_are_rejected() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.post_account_tokens(&serde_json::json!({
+        "name": "",
+        "access": "read",
+        "expires_in_days": "30",
+    })).await;
+    assert_is_redirect_to(&response, "/account/tokens");
+
+    let response = app.post_account_tokens(&serde_json::json!({
+        "name": "Admin",
+        "access": "admin",
+        "expires_in_days": "30",
+    })).await;
+    assert_is_redirect_to(&response, "/account/tokens");
+
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count.0, 0);
+}
@@ -0,0 +1,56 @@
+use crate::helpers::spawn_app;
+
+/// The stylesheet URL linked from a page
+fn stylesheet_url(html_page: &str) -> String {
+    let start = html_page.find("/public/css/main.").expect("No fingerprinted stylesheet");
+    let end = start + html_page[start..].find('"').unwrap();
+    html_page[start..end].to_string()
+}
+
+#[tokio::test]
+async fn pages_link_fingerprinted_assets() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    let url = stylesheet_url(&html_page);
+    let hash = url.trim_start_matches("/public/css/main.").trim_end_matches(".css");
+    assert_eq!(hash.len(), 16);
+    assert!(hash.bytes().all(|b| b.is_ascii_hexdigit()));
+    assert!(!html_page.contains("href=\"/public/css/main.css\""));
+    assert!(html_page.contains("/public/js/realtime."));
+}
+
+#[tokio::test]
+async fn fingerprinted_assets_are_cached_for_good() {
+    let app = spawn_app().await;
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+
+    let response = app.get_asset(&stylesheet_url(&html_page)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["cache-control"], "public, max-age=31536000, immutable");
+    assert_eq!(response.headers()["content-type"], "text/css");
+    let fingerprinted = response.text().await.unwrap();
+
+    let plain = app.get_asset("/public/css/main.css").await.text().await.unwrap();
+    assert_eq!(fingerprinted, plain);
+}
+
+#[tokio::test]
+async fn plain_asset_urls_are_revalidated() {
+    let app = spawn_app().await;
+
+    let response = app.get_asset("/public/css/main.css").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+}
+
+#[tokio::test]
+async fn outdated_fingerprints_get_the_current_file_uncached() {
+    let app = spawn_app().await;
+
+    let response = app.get_asset("/public/css/main.0123456789abcdef.css").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["cache-control"], "no-cache");
+    assert_eq!(response.headers()["content-type"], "text/css");
+}
@@ -0,0 +1,199 @@
+use axum_sass_template::audit::AuditContext;
+use axum_sass_template::domain::{UserEmail, UserPassword};
+use axum_sass_template::user::Backend;
+use secrecy::Secret;
+use uuid::Uuid;
+use crate::helpers::{spawn_app, assert_is_redirect_to, fake_email, TestApp};
+
+/// The actions recorded for the user, oldest first
+async fn actions_for(app: &TestApp, user_id: Uuid) -> Vec<String> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT action FROM audit_events WHERE actor_id = $1 OR target_id = $1 ORDER BY created_at, id"
+    )
+        .bind(user_id)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    rows.into_iter().map(|(action,)| action).collect()
+}
+
+#[tokio::test]
+async fn logins_and_logouts_are_recorded() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.post_logout().await;
+
+    assert_eq!(actions_for(&app, app.test_user.user_id).await, ["login", "logout"]);
+}
+
+#[tokio::test]
+async fn failed_logins_are_recorded_against_the_account() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong password",
+    });
+    app.post_login(&body).await;
+
+    assert_eq!(actions_for(&app, app.test_user.user_id).await, ["login_failed"]);
+    let (ip, metadata): (Option<String>, serde_json::Value) = sqlx::query_as("SELECT ip, metadata FROM audit_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(ip.as_deref(), Some("127.0.0.1"));
+    // The account's email is never copied into the log
+    assert_eq!(metadata, serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn registrations_are_recorded() {
+    let app = spawn_app().await;
+    let email = fake_email();
+    let body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r$ecret",
+    });
+    app.post_register(&body).await;
+
+    let (action, actor_id, target_id): (String, Option<Uuid>, Option<Uuid>) = sqlx::query_as(
+        "SELECT action, actor_id, target_id FROM audit_events"
+    )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(action, "registered");
+    assert!(actor_id.is_some());
+    assert_eq!(actor_id, target_id);
+}
+
+#[tokio::test]
+async fn account_changes_are_recorded() {
+    let app = spawn_app().await;
+    let backend = Backend::new(app.db_pool.clone());
+    let user_id = app.test_user.user_id;
+    let context = AuditContext::default();
+
+    let password = UserPassword::parse(Secret::new("N3w$ecret".to_string())).unwrap();
+    backend.change_password(user_id, password, user_id, &context).await.unwrap();
+    let email = UserEmail::parse(fake_email()).unwrap();
+    backend.change_email(user_id, email, user_id, &context).await.unwrap();
+    assert!(backend.grant_role(user_id, "admin", user_id, &context).await.unwrap());
+    assert!(!backend.grant_role(user_id, "admin", user_id, &context).await.unwrap());
+    assert!(backend.revoke_role(user_id, "admin", user_id, &context).await.unwrap());
+
+    assert_eq!(
+        actions_for(&app, user_id).await,
+        ["password_changed", "email_changed", "role_granted", "role_revoked"],
+    );
+}
+
+#[tokio::test]
+async fn audit_events_cannot_be_changed() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let update = sqlx::query("UPDATE audit_events SET action = 'logout'").execute(&app.db_pool).await;
+    assert!(update.is_err());
+    let delete = sqlx::query("DELETE FROM audit_events").execute(&app.db_pool).await;
+    assert!(delete.is_err());
+}
+
+#[tokio::test]
+async fn audit_log_is_forbidden_for_non_admins() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.get_admin_audit(&[]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let response = app.get_admin_audit_export(&[("format", "csv")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn admins_can_filter_the_audit_log() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    let body = serde_json::json!({ "email": "nobody@example.com", "password": "wrong password" });
+    app.post_login(&body).await;
+    app.login_test_user().await;
+
+    let html_page = app.get_admin_audit(&[]).await.text().await.unwrap();
+    assert!(html_page.contains("nobody@example.com"));
+    assert!(html_page.contains("login failed"));
+
+    let html_page = app.get_admin_audit(&[("action", "login")]).await.text().await.unwrap();
+    assert!(html_page.contains("1 event."));
+    assert!(!html_page.contains("nobody@example.com"));
+
+    let response = app.get_admin_audit(&[("user", &app.test_user.email)]).await;
+    assert!(response.text().await.unwrap().contains("1 event."));
+}
+
+#[tokio::test]
+async fn audit_log_pages_past_the_end_are_empty() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    app.login_test_user().await;
+
+    let response = app.get_admin_audit(&[("page", "300000000")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn invalid_audit_filters_are_rejected() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    app.login_test_user().await;
+
+    let response = app.get_admin_audit(&[("from", "yesterday")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let response = app.get_admin_audit_export(&[("format", "xml")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn audit_log_can_be_exported() {
+    let app = spawn_app().await;
+    app.make_test_user_admin().await;
+    app.login_test_user().await;
+
+    let response = app.get_admin_audit_export(&[("format", "csv"), ("action", "login")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/csv"));
+    assert!(response.headers()["content-disposition"].to_str().unwrap().starts_with("attachment"));
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("id,created_at,action"));
+    assert!(lines[1].contains(",login,"));
+
+    let response = app.get_admin_audit_export(&[("format", "json")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let events: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["action"], "login");
+    assert_eq!(events[0]["actor_email"], app.test_user.email);
+}
+
+#[tokio::test]
+async fn security_page_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_account_security().await;
+    assert_is_redirect_to(&response, "/login?next=%2Faccount%2Fsecurity");
+}
+
+#[tokio::test]
+async fn security_page_lists_the_users_activity() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({ "email": app.test_user.email, "password": "wrong password" });
+    app.post_login(&body).await;
+    app.login_test_user().await;
+
+    let response = app.get_account_security().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("login failed"));
+    assert!(html_page.contains("<td>login</td>"));
+    assert!(html_page.contains("127.0.0.1"));
+}
@@ -88,8 +88,7 @@ async fn post_register() {
     });
 
     let response = app.post_register(&body).await;
-    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
-    assert_is_redirect_to(&response, "/register");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
 
     password.push(rand_lowercase());
     let body = serde_json::json!({
@@ -98,8 +97,7 @@ async fn post_register() {
     });
 
     let response = app.post_register(&body).await;
-    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
-    assert_is_redirect_to(&response, "/register");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
 
     password.push(rand_uppercase());
     let body = serde_json::json!({
@@ -108,8 +106,7 @@ async fn post_register() {
     });
 
     let response = app.post_register(&body).await;
-    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
-    assert_is_redirect_to(&response, "/register");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
 
     password.push(rand_special_char());
     let body = serde_json::json!({
@@ -118,8 +115,7 @@ async fn post_register() {
     });
 
     let response = app.post_register(&body).await;
-    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
-    assert_is_redirect_to(&response, "/register");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
 
     for _ in 5..12 {
         password.push(rand_lowercase());
@@ -135,3 +131,95 @@ async fn post_register() {
     assert_is_redirect_to(&response, "/");
 }
 
+#[tokio::test]
+async fn invalid_registration_shows_each_field_error() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": "not-an-email",
+        "password": "password1!"
+    });
+
+    let response = app.post_register(&body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<input name="email" id="email" value="not-an-email" aria-invalid="true""#));
+    assert!(html_page.contains("<li>Enter a valid email address, like name@example.com</li>"));
+    assert!(html_page.contains("<li>Password must contain at least one uppercase letter</li>"));
+    assert!(!html_page.contains("password1!"));
+}
+
+#[tokio::test]
+async fn registering_a_taken_email_shows_a_field_error() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "Password1!"
+    });
+
+    let response = app.post_register(&body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let html_page = response.text().await.unwrap();
+    let email_input = format!(r#"<input name="email" id="email" value="{}" aria-invalid="true""#, app.test_user.email);
+    assert!(html_page.contains(&email_input));
+    assert!(html_page.contains("<li>An account with this email already exists. Log in instead?</li>"));
+}
+
+#[tokio::test]
+async fn post_login_ignores_offsite_next() {
+    let app = spawn_app().await;
+
+    for next_route in ["https://evil.example", "//evil.example", "/\\evil.example"] {
+        let body = serde_json::json!({
+            "email": app.test_user.email,
+            "password": app.test_user.password,
+            "next": next_route
+        });
+
+        let response = app.post_login(&body).await;
+        assert_is_redirect_to(&response, "/");
+    }
+}
+
+#[tokio::test]
+async fn failed_login_url_encodes_next() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong-password",
+        "next": "/protected?tab=a&b=c"
+    });
+
+    let response = app.post_login(&body).await;
+    assert_is_redirect_to(&response, "/login?next=%2Fprotected%3Ftab%3Da%26b%3Dc");
+}
+
+#[tokio::test]
+async fn failed_login_flashes_an_error_once() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong-password",
+    });
+
+    let response = app.post_login(&body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login(None).await.text().await.unwrap();
+    assert!(html_page.contains(r#"<div class="flash flash-error" role="alert">Invalid Credentials</div>"#));
+    let html_page = app.get_login(None).await.text().await.unwrap();
+    assert!(!html_page.contains("Invalid Credentials"));
+}
+
+#[tokio::test]
+async fn get_login_drops_offsite_next() {
+    let app = spawn_app().await;
+
+    let query_params = [("next", "https://evil.example")];
+    let response = app.get_login(Some(&query_params)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(!html_page.contains(r#"<input type="hidden" name="next""#));
+}
@@ -0,0 +1,67 @@
+use crate::helpers::spawn_app;
+use std::io::Read;
+
+fn gunzip(bytes: &[u8]) -> String {
+    let mut decompressed = String::new();
+    flate2::read::GzDecoder::new(bytes).read_to_string(&mut decompressed).unwrap();
+    decompressed
+}
+
+fn unbrotli(bytes: &[u8]) -> String {
+    let mut decompressed = String::new();
+    brotli::Decompressor::new(bytes, 4096).read_to_string(&mut decompressed).unwrap();
+    decompressed
+}
+
+#[tokio::test]
+async fn pages_are_compressed_when_accepted() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_encoding("/", "gzip").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    // Pages also vary by `HX-Request`, sent as its own header
+    assert!(response.headers().get_all("vary").iter().any(|vary| vary.to_str().unwrap().contains("accept-encoding")));
+    let html_page = gunzip(&response.bytes().await.unwrap());
+    assert!(html_page.contains("<html "));
+
+    let response = app.get_with_accept_encoding("/", "br;q=1.0, gzip;q=0.5").await;
+    assert_eq!(response.headers()["content-encoding"], "br");
+    assert!(unbrotli(&response.bytes().await.unwrap()).contains("<html "));
+}
+
+#[tokio::test]
+async fn pages_are_sent_as_they_are_otherwise() {
+    let app = spawn_app().await;
+
+    let response = app.get_homepage_html().await;
+    assert!(response.headers().get("content-encoding").is_none());
+
+    let response = app.get_with_accept_encoding("/", "identity").await;
+    assert!(response.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn small_responses_are_not_compressed() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_encoding("/health", "gzip").await;
+    assert!(response.status().is_success());
+    assert!(response.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn precompressed_assets_are_served_when_accepted() {
+    let app = spawn_app().await;
+    let css = app.get_asset("/public/css/main.css").await.text().await.unwrap();
+
+    let response = app.get_with_accept_encoding("/public/css/main.css", "br").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "br");
+    assert_eq!(response.headers()["content-type"], "text/css");
+    assert_eq!(unbrotli(&response.bytes().await.unwrap()), css);
+
+    let response = app.get_with_accept_encoding("/public/css/main.css", "gzip").await;
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(gunzip(&response.bytes().await.unwrap()), css);
+}
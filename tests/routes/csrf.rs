@@ -0,0 +1,120 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to, extract_csrf_token};
+
+#[tokio::test]
+async fn forms_include_the_csrf_token() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login(None).await.text().await.unwrap();
+    let token = extract_csrf_token(&html_page);
+    assert_eq!(token.len(), 64);
+
+    // The token is tied to the session and stays the same across pages
+    let html_page = app.get_register().await.text().await.unwrap();
+    assert_eq!(extract_csrf_token(&html_page), token);
+}
+
+#[tokio::test]
+async fn post_without_csrf_token_is_forbidden() {
+    let app = spawn_app().await;
+    app.csrf_token().await;
+
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "email": app.test_user.email,
+            "password": app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("403 Forbidden"));
+}
+
+#[tokio::test]
+async fn post_with_forged_csrf_token_is_forbidden() {
+    let app = spawn_app().await;
+    app.csrf_token().await;
+
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "email": app.test_user.email,
+            "password": app.test_user.password,
+            "csrf_token": "0".repeat(64),
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn token_from_another_session_is_forbidden() {
+    let app = spawn_app().await;
+    let other_session = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let html_page = other_session
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let forged = extract_csrf_token(&html_page);
+    app.csrf_token().await;
+
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "email": app.test_user.email,
+            "password": app.test_user.password,
+            "csrf_token": forged,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn csrf_token_header_is_accepted() {
+    let app = spawn_app().await;
+    let token = app.csrf_token().await;
+
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-CSRF-Token", token)
+        .form(&serde_json::json!({
+            "email": app.test_user.email,
+            "password": app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/");
+}
+
+#[tokio::test]
+async fn logout_is_a_post() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let response = app.api_client
+        .get(format!("{}/logout", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/");
+
+    let response = app.get_protected().await;
+    assert_is_redirect_to(&response, "/login?next=%2Fprotected");
+}
@@ -0,0 +1,20 @@
+use crate::helpers::spawn_app;
+
+// Tests run in the local environment, where templates and SCSS are reloaded
+
+#[tokio::test]
+async fn pages_include_the_live_reload_script() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains("/public/js/live_reload."));
+}
+
+#[tokio::test]
+async fn reloads_are_streamed_as_server_sent_events() {
+    let app = spawn_app().await;
+
+    let response = app.get_dev_reload().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+}
@@ -0,0 +1,116 @@
+use axum::{routing::get, Router};
+use crate::helpers::{spawn_app, spawn_app_with_routes};
+
+async fn panicking_handler() -> &'static str {
+    panic!("password=hunter42 connection refused")
+}
+
+#[tokio::test]
+async fn errors_are_problem_json_for_json_clients() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .post(format!("{}/webhooks/email-events", &app.address))
+        .header("Accept", "application/json")
+        .body("{}")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "application/problem+json");
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 401);
+    assert_eq!(problem["title"], "Unauthorized");
+    assert_eq!(problem["instance"], "/webhooks/email-events");
+    assert!(problem["correlation_id"].is_string());
+}
+
+#[tokio::test]
+async fn errors_are_html_pages_for_browsers() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .post(format!("{}/webhooks/email-events", &app.address))
+        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+        .body("{}")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let content_type = response.headers().get("Content-Type").unwrap().to_str().unwrap().to_string();
+    assert!(content_type.starts_with("text/html"));
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("401 Unauthorized"));
+    assert!(html_page.contains("Reference:"));
+}
+
+#[tokio::test]
+async fn unknown_routes_get_the_404_page() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_language("/no/such/page", "en").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert!(response.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Page not found</h1>"));
+    assert!(html_page.contains("<code>&#x2F;no&#x2F;such&#x2F;page</code>"));
+
+    let html_page = app.get_with_accept_language("/no/such/page", "de").await.text().await.unwrap();
+    assert!(html_page.contains("<h1>Seite nicht gefunden</h1>"));
+}
+
+#[tokio::test]
+async fn missing_static_files_get_the_404_page() {
+    let app = spawn_app().await;
+
+    let response = app.get_asset("/public/css/missing.css").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert!(response.text().await.unwrap().contains("<h1>Page not found</h1>"));
+}
+
+#[tokio::test]
+async fn unknown_api_routes_are_problem_json() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .get(format!("{}/api/v1/no-such-endpoint", &app.address))
+        .header("Accept", "text/html")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "application/problem+json");
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 404);
+    assert_eq!(problem["instance"], "/api/v1/no-such-endpoint");
+}
+
+#[tokio::test]
+async fn panicking_handlers_get_the_500_page_or_problem_json() {
+    let routes = Router::new()
+        .route("/panic", get(panicking_handler))
+        .route("/api/v1/panic", get(panicking_handler));
+    let app = spawn_app_with_routes(routes).await;
+
+    let response = app.get_with_accept_language("/panic", "en").await;
+    assert_eq!(response.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(response.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<html "));
+    assert!(html_page.contains("Reference:"));
+    assert!(!html_page.contains("hunter42"));
+
+    let response = app.api_client
+        .get(format!("{}/api/v1/panic", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.headers()["Content-Type"], "application/problem+json");
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 500);
+    assert_eq!(problem["instance"], "/api/v1/panic");
+    assert!(!problem.to_string().contains("hunter42"));
+}
@@ -0,0 +1,100 @@
+use axum_sass_template::realtime::{self, Event, ToastLevel};
+use futures_util::StreamExt;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Error, Message};
+use crate::helpers::spawn_app;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn anonymous_requests_are_unauthorized() {
+    let app = spawn_app().await;
+
+    let response = app.get_events().await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn events_are_streamed_as_server_sent_events() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let mut response = app.get_events().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    let event = Event::toast(ToastLevel::Success, "Export ready");
+    realtime::notify(&app.db_pool, app.test_user.user_id, &event).await.unwrap();
+
+    let mut received = String::new();
+    while !received.contains("Export ready") {
+        let chunk = tokio::time::timeout(TIMEOUT, response.chunk())
+            .await
+            .expect("No event was received")
+            .unwrap()
+            .expect("The stream ended");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.contains("event: toast"));
+}
+
+#[tokio::test]
+async fn other_users_events_are_not_streamed() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let mut response = app.get_events().await;
+    let event = Event::toast(ToastLevel::Info, "Not for you");
+    realtime::notify(&app.db_pool, uuid::Uuid::new_v4(), &event).await.unwrap();
+
+    // Only keep-alive comments could arrive, and not within this window
+    let chunk = tokio::time::timeout(Duration::from_millis(500), response.chunk()).await;
+    assert!(chunk.is_err());
+}
+
+#[tokio::test]
+async fn events_are_sent_over_websockets() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("read").await;
+
+    let url = format!("{}/events/ws", app.address.replacen("http", "ws", 1));
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.expect("Failed to connect");
+
+    let event = Event::JobProgress { job_id: uuid::Uuid::new_v4(), progress: 50, message: None };
+    realtime::notify(&app.db_pool, app.test_user.user_id, &event).await.unwrap();
+
+    let message = tokio::time::timeout(TIMEOUT, socket.next())
+        .await
+        .expect("No event was received")
+        .unwrap()
+        .unwrap();
+    let Message::Text(text) = message else {
+        panic!("Expected a text message, got {:?}", message);
+    };
+    assert_eq!(serde_json::from_str::<Event>(&text).unwrap(), event);
+}
+
+#[tokio::test]
+async fn websocket_upgrades_from_other_origins_are_rejected() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let token = app.create_api_token("read").await;
+    let url = format!("{}/events/ws", app.address.replacen("http", "ws", 1));
+
+    let mut request = url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+    request.headers_mut().insert("Origin", "https://evil.example".parse().unwrap());
+    let Err(Error::Http(response)) = tokio_tungstenite::connect_async(request).await else {
+        panic!("The upgrade was accepted");
+    };
+    assert_eq!(response.status(), 403);
+
+    // The app's own pages, at `base_url`, can connect
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+    request.headers_mut().insert("Origin", "http://127.0.0.1".parse().unwrap());
+    assert!(tokio_tungstenite::connect_async(request).await.is_ok());
+}
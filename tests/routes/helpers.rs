@@ -1,6 +1,7 @@
 use sqlx::{PgConnection, Executor, Connection};
 use axum_sass_template::configuration::{get_configuration, DatabaseSettings};
 use axum_sass_template::telemetry::{get_subscriber, init_subscriber};
+use axum::Router;
 use axum_sass_template::startup::Application;
 use sqlx::PgPool;
 use once_cell::sync::Lazy;
@@ -9,6 +10,9 @@ use fake::faker::internet::en::SafeEmail;
 use fake::Fake;
 use rand::Rng;
 use rand::seq::SliceRandom;
+use secrecy::{Secret, ExposeSecret};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
@@ -62,13 +66,32 @@ pub struct TestApp {
     pub api_client: reqwest::Client,
     pub test_user: TestUser,
     pub _db_settings: DatabaseSettings,
+    pub hmac_secret: Secret<String>,
+    pub webhook_secret: Secret<String>,
 }
 
 impl TestApp {
+    /// Gets the CSRF token of the current session from the login form
+    pub async fn csrf_token(&self) -> String {
+        let html_page = self.get_login(None).await.text().await.unwrap();
+        extract_csrf_token(&html_page)
+    }
+
+    /// Adds the session's CSRF token to a form body
+    pub async fn with_csrf_token<Body>(&self, body: &Body) -> serde_json::Value
+    where
+        Body: serde::Serialize
+    {
+        let mut body = serde_json::to_value(body).expect("Failed to serialize form body");
+        body["csrf_token"] = serde_json::Value::String(self.csrf_token().await);
+        body
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize
     {
+        let body = self.with_csrf_token(body).await;
         let url = format!("{}/login", &self.address);
         self.api_client
             .post(&url)
@@ -82,8 +105,9 @@ impl TestApp {
     where
         Body: serde::Serialize
     {
+        let body = self.with_csrf_token(body).await;
         self.api_client
-            .post(&format!("{}/register", &self.address))
+            .post(format!("{}/register", &self.address))
             .form(&body)
             .send()
             .await
@@ -92,7 +116,7 @@ impl TestApp {
 
     pub async fn get_register(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/register", &self.address))
+            .get(format!("{}/register", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -100,15 +124,25 @@ impl TestApp {
 
     pub async fn get_homepage_html(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to get homepage")
     }
 
+    pub async fn post_csp_report(&self, body: &str, content_type: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/csp-report", &self.address))
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_health_check(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/health", &self.address))
+            .get(format!("{}/health", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -129,9 +163,380 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn login_test_user(&self) {
+        let body = serde_json::json!({
+            "email": self.test_user.email,
+            "password": self.test_user.password,
+        });
+        let response = self.post_login(&body).await;
+        assert_is_redirect_to(&response, "/");
+    }
+
+    /// A GET sent the way htmx sends it, without boosting
+    pub async fn get_htmx(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .header("HX-Request", "true")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// A form POST sent the way htmx sends it, with the CSRF token added
+    pub async fn post_htmx<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        let body = self.with_csrf_token(body).await;
+        self.api_client
+            .post(format!("{}{}", &self.address, path))
+            .header("HX-Request", "true")
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_notifications(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/notifications", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_notifications<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        let body = self.with_csrf_token(body).await;
+        self.api_client
+            .post(format!("{}/account/notifications", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_tokens(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_tokens<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        let body = self.with_csrf_token(body).await;
+        self.api_client
+            .post(format!("{}/account/tokens", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Creates an API token for the logged in user and returns it
+    pub async fn create_api_token(&self, access: &str) -> String {
+        let response = self.post_account_tokens(&serde_json::json!({
+            "name": "Test token",
+            "access": access,
+            "expires_in_days": "30",
+        })).await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let html_page = response.text().await.unwrap();
+        let marker = r#"<code id="new-token">"#;
+        let start = html_page.find(marker).expect("No new token in the page") + marker.len();
+        let end = start + html_page[start..].find('<').unwrap();
+        html_page[start..end].to_string()
+    }
+
+    pub async fn post_revoke_api_token(&self, token_id: uuid::Uuid) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/account/tokens/{}/revoke", &self.address, token_id))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// A client without the session cookie, for requests made with an API token
+    pub fn bearer_client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+    }
+
+    pub async fn get_unsubscribe(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/unsubscribe", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Sends the RFC 8058 one-click unsubscribe request the way a mail client would
+    pub async fn post_unsubscribe(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/unsubscribe", &self.address))
+            .query(&[("token", token)])
+            .form(&[("List-Unsubscribe", "One-Click")])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Gives the test user the admin role
+    pub async fn make_test_user_admin(&self) {
+        sqlx::query!(
+            "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = 'admin'",
+            self.test_user.user_id,
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to make test user an admin.");
+    }
+
+    pub fn sign_webhook(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.webhook_secret.expose_secret().as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    pub async fn post_email_events(&self, body: &str, content_type: &str, signature: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/email-events", &self.address))
+            .header("Content-Type", content_type)
+            .header("X-Webhook-Signature", signature)
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_suppressions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_clear_suppression(&self, suppression_id: i32) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/admin/suppressions/{}/clear", &self.address, suppression_id))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/logout", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// GETs `path` under `/api/v1` with the session cookie
+    pub async fn get_api(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/api/v1{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_notifications(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/notifications", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_mark_notification_read<Body>(&self, notification_id: uuid::Uuid, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        let body = self.with_csrf_token(body).await;
+        self.api_client
+            .post(format!("{}/notifications/{}/read", &self.address, notification_id))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_mark_all_notifications_read(&self) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/notifications/read-all", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_events(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/events", &self.address))
+            .header("Accept", "text/event-stream")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_audit(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_audit_export(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit/export", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_security(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/security", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_export(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/export", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_export(&self) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/account/export", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_export_download(&self, export_id: uuid::Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/export/{}/download", &self.address, export_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_delete(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/delete", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_delete(&self, password: &str) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({ "password": password })).await;
+        self.api_client
+            .post(format!("{}/account/delete", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_account_delete(&self) -> reqwest::Response {
+        let body = self.with_csrf_token(&serde_json::json!({})).await;
+        self.api_client
+            .post(format!("{}/account/delete/cancel", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_dev_reload(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/dev/reload", &self.address))
+            .header("Accept", "text/event-stream")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `path` is what the page links, e.g. `/public/css/main.css`
+    pub async fn get_asset(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_with_accept_encoding(&self, path: &str, accept_encoding: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .header(reqwest::header::ACCEPT_ENCODING, accept_encoding)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_protected(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/protected", &self.address))
+            .get(format!("{}/protected", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_with_accept_language(&self, path: &str, accept_language: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .header(reqwest::header::ACCEPT_LANGUAGE, accept_language)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_preferences(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/account/preferences", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_preferences<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        let body = self.with_csrf_token(body).await;
+        self.api_client
+            .post(format!("{}/account/preferences", &self.address))
+            .form(&body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -139,6 +544,11 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_routes(Router::new()).await
+}
+
+/// Spawns the app with extra routes, behind the same layers as its own
+pub async fn spawn_app_with_routes(routes: Router) -> TestApp {
     /*
      * The first time 'initialize is invoked the code in 'TRACING' is executed.
      * All other invocations will instead skip execution (so init_subscriber() is only called once)
@@ -158,26 +568,29 @@ pub async fn spawn_app() -> TestApp {
 
     let application = Application::build(configuration.clone())
         .await
-        .expect("Failed to build application");
+        .expect("Failed to build application")
+        .with_routes(routes);
 
     let application_port = application.port();
     let address = format!("http://127.0.0.1:{}", application_port);
 
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
         .build()
         .unwrap();
-    let mut test_app = TestApp {
+    let test_app = TestApp {
         address,
         db_pool,
         _port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
-        _db_settings: configuration.database
+        _db_settings: configuration.database,
+        hmac_secret: configuration.application.hmac_secret,
+        webhook_secret: configuration.email.webhook_secret,
     };
-    test_app.test_user.store(&mut test_app.db_pool).await;
+    test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
@@ -207,6 +620,13 @@ pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
 
+pub fn extract_csrf_token(html_page: &str) -> String {
+    let marker = r#"name="csrf_token" value=""#;
+    let start = html_page.find(marker).expect("No CSRF token in the page") + marker.len();
+    let end = start + html_page[start..].find('"').unwrap();
+    html_page[start..end].to_string()
+}
+
 pub fn fake_email() -> String {
     SafeEmail().fake::<String>()
 }
@@ -219,16 +639,16 @@ pub fn rand_special_char() -> char {
 
 pub fn rand_digit() -> char {
     let mut rng = rand::thread_rng();
-    rng.gen_range(b'0'..=b'9' + 1) as char
+    rng.gen_range(b'0'..=b'9') as char
 }
 
 pub fn rand_lowercase() -> char {
     let mut rng = rand::thread_rng();
-    rng.gen_range(b'a'..=b'z' + 1) as char
+    rng.gen_range(b'a'..=b'z') as char
 }
 
 pub fn rand_uppercase() -> char {
     let mut rng = rand::thread_rng();
-    rng.gen_range(b'A'..=b'Z' + 1) as char
+    rng.gen_range(b'A'..=b'Z') as char
 }
 
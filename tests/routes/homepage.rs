@@ -8,5 +8,26 @@ async fn homepage() {
     assert_eq!(response.status(), reqwest::StatusCode::OK);
 
     let html_page = response.text().await.expect("Failed to read the response body");
-    assert!(html_page.contains("HOMEPAGE BABY!"));
+    assert!(html_page.contains("Welcome to Axum Sass Template!"));
+}
+
+#[tokio::test]
+async fn pages_show_who_is_logged_in() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains("Log in"));
+    assert!(!html_page.contains(&app.test_user.email));
+    assert!(!html_page.contains("/public/js/realtime."));
+
+    app.login_test_user().await;
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains(&app.test_user.email));
+    assert!(html_page.contains("Log out"));
+    assert!(html_page.contains("/public/js/realtime."));
+    assert!(!html_page.contains(r#"href="&#x2F;admin&#x2F;audit""#));
+
+    app.make_test_user_admin().await;
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains(r#"href="&#x2F;admin&#x2F;audit""#));
 }
@@ -0,0 +1,97 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to, fake_email};
+
+#[tokio::test]
+async fn htmx_gets_only_the_content_block() {
+    let app = spawn_app().await;
+
+    let response = app.get_htmx("/login").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.headers()["Vary"].to_str().unwrap().contains("HX-Request"));
+
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<input name="email" id="email""#));
+    assert!(!html.contains("<html "));
+    assert!(!html.contains("I am the navigation bar"));
+    assert!(html.contains(r#"<div id="flashes" hx-swap-oob="true">"#));
+
+    // Without the header the whole page is sent
+    let html = app.get_login(None).await.text().await.unwrap();
+    assert!(html.contains("<html "));
+    assert!(html.contains(r#"<div id="flashes">"#));
+    // The CDN copy of htmx only runs if it matches its hash
+    assert!(html.contains(r#"integrity="sha384-"#));
+    assert!(html.contains(r#"crossorigin="anonymous""#));
+}
+
+#[tokio::test]
+async fn boosted_requests_get_the_whole_page() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .get(format!("{}/login", &app.address))
+        .header("HX-Request", "true")
+        .header("HX-Boosted", "true")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.text().await.unwrap().contains("<html "));
+}
+
+#[tokio::test]
+async fn htmx_login_redirects_with_a_header() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": app.test_user.password,
+        "next": "/protected",
+    });
+
+    let response = app.post_htmx("/login", &body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["HX-Redirect"], "/protected");
+
+    // The success message waits for the page htmx loads next
+    let html = app.get_protected().await.text().await.unwrap();
+    assert!(html.contains("Successfully logged in as"));
+}
+
+#[tokio::test]
+async fn failed_htmx_login_swaps_the_form_with_an_error() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong-password",
+        "next": "/protected",
+    });
+
+    let response = app.post_htmx("/login", &body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let html = response.text().await.unwrap();
+    assert!(!html.contains("<html "));
+    assert!(html.contains(r#"<input type="hidden" name="next" value="&#x2F;protected""#));
+    assert!(html.contains(r#"hx-swap-oob="true""#));
+    assert!(html.contains("Invalid Credentials"));
+
+    // It was shown already, so the next page doesn't repeat it
+    let html = app.get_login(None).await.text().await.unwrap();
+    assert!(!html.contains("Invalid Credentials"));
+}
+
+#[tokio::test]
+async fn htmx_registration_shows_errors_in_place() {
+    let app = spawn_app().await;
+    let email = fake_email();
+
+    let response = app.post_htmx("/register", &serde_json::json!({ "email": email, "password": "short" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let html = response.text().await.unwrap();
+    assert!(!html.contains("<html "));
+    assert!(html.contains(r#"<ul id="password-errors" class="field-errors">"#));
+
+    let response = app.post_htmx("/register", &serde_json::json!({ "email": email, "password": "Hunter42!Hunter42!" })).await;
+    assert_eq!(response.headers()["HX-Redirect"], "/");
+
+    // Plain form posts are still redirected
+    let response = app.post_register(&serde_json::json!({ "email": fake_email(), "password": "Hunter42!Hunter42!" })).await;
+    assert_is_redirect_to(&response, "/");
+}
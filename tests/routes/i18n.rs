@@ -0,0 +1,163 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+
+#[tokio::test]
+async fn pages_are_shown_in_the_browsers_language() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_language("/login", "de-CH, de;q=0.9, en;q=0.8").await;
+    assert!(response.headers()["Vary"].to_str().unwrap().contains("Accept-Language"));
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="de">"#));
+    assert!(html.contains(r#"<input type="submit" value="Anmelden" />"#));
+
+    // Languages without a catalog fall back to English
+    let html = app.get_with_accept_language("/login", "fr").await.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="en">"#));
+    assert!(html.contains(r#"<input type="submit" value="login" />"#));
+}
+
+#[tokio::test]
+async fn lang_parameter_overrides_the_browser_and_is_remembered() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_language("/register?lang=de", "en").await;
+    let cookie = response.headers()["Set-Cookie"].to_str().unwrap().to_string();
+    assert!(cookie.starts_with("lang=de;"));
+    assert!(response.text().await.unwrap().contains("Konto registrieren"));
+
+    let html = app.get_with_accept_language("/register", "en").await.text().await.unwrap();
+    assert!(html.contains("Konto registrieren"));
+
+    // Unknown languages are ignored
+    let response = app.get_with_accept_language("/register?lang=xx", "en").await;
+    assert!(response.headers().get("Set-Cookie").is_none());
+}
+
+#[tokio::test]
+async fn validation_errors_are_translated() {
+    let app = spawn_app().await;
+    app.get_with_accept_language("/?lang=de", "de").await;
+
+    let response = app.post_register(&serde_json::json!({ "email": "not-an-email", "password": "short" })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Gib eine gültige E-Mail-Adresse ein"));
+    assert!(html.contains("Das Passwort muss mindestens 8 Zeichen lang sein"));
+}
+
+#[tokio::test]
+async fn flash_messages_are_translated() {
+    let app = spawn_app().await;
+    app.get_with_accept_language("/?lang=de", "de").await;
+
+    let body = serde_json::json!({ "email": app.test_user.email, "password": "wrong-password" });
+    app.post_login(&body).await;
+    let html = app.get_login(None).await.text().await.unwrap();
+    assert!(html.contains("Ungültige Anmeldedaten"));
+}
+
+#[tokio::test]
+async fn users_can_choose_their_language_and_timezone() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let html = app.get_account_preferences().await.text().await.unwrap();
+    assert!(html.contains(r#"<option value="de" lang="de">Deutsch</option>"#));
+    assert!(html.contains(r#"value="UTC""#));
+
+    let response = app.post_account_preferences(&serde_json::json!({ "locale": "de", "timezone": "Europe/Berlin" })).await;
+    assert_is_redirect_to(&response, "/account/preferences");
+
+    // The preference beats the browser's language
+    let html = app.get_with_accept_language("/account/preferences", "en").await.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="de">"#));
+    assert!(html.contains("Deine Einstellungen wurden gespeichert"));
+    assert!(html.contains(r#"value="Europe&#x2F;Berlin""#));
+
+    // Back to following the browser
+    app.post_account_preferences(&serde_json::json!({ "locale": "", "timezone": "Europe/Berlin" })).await;
+    let html = app.get_with_accept_language("/account/preferences", "en").await.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="en">"#));
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_shown_again_with_errors() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let body = serde_json::json!({ "locale": "xx", "timezone": "Mars/Olympus_Mons" });
+    let response = app.post_account_preferences(&body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<ul id="locale-errors" class="field-errors">"#));
+    assert!(html.contains(r#"value="Mars&#x2F;Olympus_Mons""#));
+    assert!(html.contains("Choose a timezone from the list"));
+}
+
+#[tokio::test]
+async fn saving_preferences_forgets_the_lang_override() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.get_with_accept_language("/?lang=de", "en").await;
+
+    let response = app.post_account_preferences(&serde_json::json!({ "locale": "en", "timezone": "UTC" })).await;
+    assert!(response.headers()["Set-Cookie"].to_str().unwrap().starts_with("lang=;"));
+    let html = app.get_with_accept_language("/register", "de").await.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="en">"#));
+}
+
+#[tokio::test]
+async fn error_responses_are_translated() {
+    let app = spawn_app().await;
+
+    let response = app.get_with_accept_language("/api/v1/users/me", "de").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["title"], "Nicht angemeldet");
+    assert_eq!(problem["detail"], "Melde dich an, um fortzufahren.");
+
+    let problem: serde_json::Value = app.get_with_accept_language("/api/v1/users/me", "en").await.json().await.unwrap();
+    assert_eq!(problem["title"], "Unauthorized");
+    assert_eq!(problem["detail"], "You need to log in to continue.");
+}
+
+#[tokio::test]
+async fn account_flashes_and_notifications_are_translated() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.get_with_accept_language("/?lang=de", "de").await;
+
+    app.post_account_delete("not my password").await;
+    let html = app.get_account_delete().await.text().await.unwrap();
+    assert!(html.contains("Falsches Passwort"));
+
+    app.post_account_delete(&app.test_user.password).await;
+    let html = app.get_notifications().await.text().await.unwrap();
+    assert!(html.contains("Die Löschung deines Kontos ist geplant"));
+    assert!(html.contains("Dein Konto und seine Daten werden am "));
+}
+
+#[tokio::test]
+async fn pages_are_translated() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    app.get_with_accept_language("/?lang=de", "de").await;
+
+    let html = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html.contains("Willkommen bei Axum Sass Template!"));
+    let html = app.get_account_security().await.text().await.unwrap();
+    assert!(html.contains("<td>Anmeldung</td>"));
+    assert!(html.contains("gerade eben"));
+    let html = app.get_account_tokens().await.text().await.unwrap();
+    assert!(html.contains("Du hast keine API-Tokens."));
+    assert!(html.contains("In 30 Tagen"));
+    let html = app.get_notifications().await.text().await.unwrap();
+    assert!(html.contains("Du hast keine Benachrichtigungen."));
+    let html = app.get_admin_audit(&[]).await.text().await.unwrap();
+    assert!(html.contains("Referenz:"));
+    assert!(html.contains("Zurück zur Startseite"));
+
+    app.make_test_user_admin().await;
+    let html = app.get_admin_audit(&[]).await.text().await.unwrap();
+    assert!(html.contains("1 Ereignis."));
+}
@@ -3,3 +3,24 @@ mod health_check;
 mod homepage;
 mod auth;
 mod protected;
+mod account;
+mod unsubscribe;
+mod webhooks;
+mod admin;
+mod errors;
+mod csrf;
+mod session;
+mod security_headers;
+mod rate_limit;
+mod api_tokens;
+mod api;
+mod events;
+mod notifications;
+mod audit;
+mod account_exports;
+mod account_deletion;
+mod dev_reload;
+mod assets;
+mod compression;
+mod htmx;
+mod i18n;
@@ -0,0 +1,140 @@
+use axum_sass_template::configuration::NotificationSettings;
+use axum_sass_template::domain::NotificationKind;
+use axum_sass_template::notifications::{self, NewNotification};
+use uuid::Uuid;
+use crate::helpers::{spawn_app, assert_is_redirect_to, TestApp};
+
+async fn create_notification(app: &TestApp, title: &str) -> Uuid {
+    let new_notification = NewNotification::new(NotificationKind::Info, title).with_url("/account/tokens");
+    notifications::create(&app.db_pool, app.test_user.user_id, &new_notification)
+        .await
+        .unwrap()
+        .id
+}
+
+async fn unread_count(app: &TestApp) -> u64 {
+    notifications::unread_count(&app.db_pool, app.test_user.user_id).await.unwrap()
+}
+
+#[tokio::test]
+async fn notifications_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_notifications().await;
+    assert_is_redirect_to(&response, "/login?next=%2Fnotifications");
+}
+
+#[tokio::test]
+async fn notifications_are_listed_with_an_unread_badge() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    create_notification(&app, "Your export is ready").await;
+    create_notification(&app, "New sign in").await;
+
+    let html_page = app.get_notifications().await.text().await.unwrap();
+    assert!(html_page.contains("Your export is ready"));
+    assert!(html_page.contains("New sign in"));
+    assert!(html_page.contains(r#"class="badge">2</span>"#));
+}
+
+#[tokio::test]
+async fn notifications_past_the_last_page_are_empty() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    create_notification(&app, "Your export is ready").await;
+
+    let response = app.api_client
+        .get(format!("{}/notifications?page=300000000", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(!response.text().await.unwrap().contains("Your export is ready"));
+}
+
+#[tokio::test]
+async fn notifications_show_when_they_were_sent() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let notification_id = create_notification(&app, "Hello").await;
+    sqlx::query("UPDATE notifications SET created_at = NOW() - INTERVAL '3 minutes' WHERE id = $1")
+        .bind(notification_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app.get_notifications().await.text().await.unwrap();
+    assert!(html_page.contains("3 minutes ago"));
+    assert!(html_page.contains(&format!("action=\"&#x2F;notifications&#x2F;{}&#x2F;read\"", notification_id)));
+}
+
+#[tokio::test]
+async fn notifications_can_be_marked_read() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let notification_id = create_notification(&app, "Hello").await;
+
+    let response = app.post_mark_notification_read(notification_id, &serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/notifications");
+    assert_eq!(unread_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn opening_a_notification_follows_its_link() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let notification_id = create_notification(&app, "Hello").await;
+
+    let response = app.post_mark_notification_read(notification_id, &serde_json::json!({ "open": "true" })).await;
+    assert_is_redirect_to(&response, "/account/tokens");
+}
+
+#[tokio::test]
+async fn all_notifications_can_be_marked_read() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    for title in ["One", "Two", "Three"] {
+        create_notification(&app, title).await;
+    }
+
+    let response = app.post_mark_all_notifications_read().await;
+    assert_is_redirect_to(&response, "/notifications");
+    assert_eq!(unread_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn other_users_notifications_cannot_be_marked_read() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+    let notification_id = create_notification(&app, "Not yours").await;
+    sqlx::query("UPDATE notifications SET user_id = (SELECT id FROM users WHERE id <> $1 LIMIT 1)")
+        .bind(app.test_user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.post_mark_notification_read(notification_id, &serde_json::json!({})).await;
+    let (read_at,): (Option<time::OffsetDateTime>,) = sqlx::query_as("SELECT read_at FROM notifications")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(read_at.is_none());
+}
+
+#[tokio::test]
+async fn old_notifications_are_pruned() {
+    let app = spawn_app().await;
+    let old_read = create_notification(&app, "Old and read").await;
+    let old_unread = create_notification(&app, "Old but unread").await;
+    create_notification(&app, "Recent").await;
+    sqlx::query("UPDATE notifications SET created_at = NOW() - INTERVAL '100 days' WHERE id = ANY($1)")
+        .bind([old_read, old_unread])
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    notifications::mark_read(&app.db_pool, app.test_user.user_id, old_read).await.unwrap();
+
+    let deleted = notifications::prune(&app.db_pool, &NotificationSettings::default()).await.unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(unread_count(&app).await, 2);
+}
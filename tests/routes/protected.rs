@@ -4,20 +4,38 @@ use crate::helpers::{spawn_app, assert_is_redirect_to};
 async fn get_protected() {
     let app = spawn_app().await;
 
-    // It will first reject
+    // Anonymous users are sent to the login page and brought back afterwards
     let response = app.get_protected().await;
-    assert_eq!(response.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    assert_is_redirect_to(&response, "/login?next=%2Fprotected");
 
-    // TODO: This is the same as the post_login test
-    let body = serde_json::json!({
-        "email": app.test_user.email,
-        "password": app.test_user.password,
-    });
-
-    let response = app.post_login(&body).await;
-    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
-    assert_is_redirect_to(&response, "/");
+    app.login_test_user().await;
 
     let response = app.get_protected().await;
     assert_eq!(response.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn get_protected_keeps_the_query_in_next() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .get(format!("{}/protected?tab=settings&page=2", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/login?next=%2Fprotected%3Ftab%3Dsettings%26page%3D2");
+}
+
+#[tokio::test]
+async fn get_protected_as_api_client_is_unauthorized() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .get(format!("{}/protected", &app.address))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "application/problem+json");
+}
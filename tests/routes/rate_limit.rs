@@ -0,0 +1,77 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn responses_carry_rate_limit_headers() {
+    let app = spawn_app().await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong-password",
+    })).await;
+
+    let headers = response.headers();
+    assert_eq!(headers["ratelimit-limit"], "10");
+    assert_eq!(headers["ratelimit-remaining"], "9");
+    assert_eq!(headers["ratelimit-policy"], "10;w=60");
+    assert!(headers.contains_key("ratelimit-reset"));
+}
+
+#[tokio::test]
+async fn reads_are_not_counted_against_login_attempts() {
+    let app = spawn_app().await;
+
+    let response = app.get_login(None).await;
+    assert!(!response.headers().contains_key("ratelimit-limit"));
+}
+
+#[tokio::test]
+async fn too_many_login_attempts_are_rejected() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "email": app.test_user.email,
+        "password": "wrong-password",
+    });
+
+    for _ in 0..10 {
+        let response = app.post_login(&body).await;
+        assert_ne!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+    assert_eq!(response.headers()["ratelimit-remaining"], "0");
+
+    // Even the right password is rejected until the window slides
+    let response = app.post_login(&serde_json::json!({
+        "email": app.test_user.email,
+        "password": app.test_user.password,
+    })).await;
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn rate_limited_json_requests_get_problem_details() {
+    let app = spawn_app().await;
+
+    let mut last = None;
+    for _ in 0..61 {
+        last = Some(
+            app.api_client
+                .post(format!("{}/csp-report", &app.address))
+                .header("Accept", "application/json")
+                .header("Content-Type", "application/csp-report")
+                .body(r#"{"csp-report": {}}"#)
+                .send()
+                .await
+                .expect("Failed to execute request."),
+        );
+    }
+
+    let response = last.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 429);
+}
@@ -0,0 +1,72 @@
+use crate::helpers::spawn_app;
+use reqwest::header::{CONTENT_SECURITY_POLICY, STRICT_TRANSPORT_SECURITY};
+
+#[tokio::test]
+async fn pages_set_security_headers() {
+    let app = spawn_app().await;
+
+    let response = app.get_homepage_html().await;
+    let headers = response.headers();
+
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["referrer-policy"], "strict-origin-when-cross-origin");
+    assert!(headers.contains_key("permissions-policy"));
+    let policy = headers[CONTENT_SECURITY_POLICY].to_str().unwrap();
+    assert!(policy.contains("frame-ancestors 'none'"));
+    assert!(policy.contains("report-uri /csp-report"));
+    // HSTS is only sent in production
+    assert!(!headers.contains_key(STRICT_TRANSPORT_SECURITY));
+}
+
+#[tokio::test]
+async fn inline_scripts_get_the_request_nonce() {
+    let app = spawn_app().await;
+
+    let response = app.get_homepage_html().await;
+    let policy = response.headers()[CONTENT_SECURITY_POLICY].to_str().unwrap().to_string();
+    let nonce = policy
+        .split("'nonce-")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').next())
+        .expect("No nonce in the policy")
+        .to_string();
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!(r#"<script nonce="{}">"#, nonce)));
+
+    // Every request gets a new nonce
+    let response = app.get_homepage_html().await;
+    let next_policy = response.headers()[CONTENT_SECURITY_POLICY].to_str().unwrap();
+    assert!(!next_policy.contains(&nonce));
+}
+
+#[tokio::test]
+async fn error_pages_get_the_request_nonce() {
+    let app = spawn_app().await;
+
+    let response = app.post_email_events("{}", "application/json", "invalid").await;
+    let policy = response.headers()[CONTENT_SECURITY_POLICY].to_str().unwrap().to_string();
+    let html_page = response.text().await.unwrap();
+    let nonce = policy.split("'nonce-").nth(1).unwrap().split('\'').next().unwrap();
+    assert!(html_page.contains(nonce));
+}
+
+#[tokio::test]
+async fn csp_reports_are_accepted() {
+    let app = spawn_app().await;
+
+    let legacy = r#"{"csp-report": {"document-uri": "http://127.0.0.1/", "violated-directive": "script-src"}}"#;
+    let response = app.post_csp_report(legacy, "application/csp-report").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let reports = r#"[{"type": "csp-violation", "body": {"documentURL": "http://127.0.0.1/", "effectiveDirective": "script-src-elem"}}]"#;
+    let response = app.post_csp_report(reports, "application/reports+json").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn invalid_csp_reports_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app.post_csp_report("not json", "application/csp-report").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,77 @@
+use axum_sass_template::session;
+use crate::helpers::spawn_app;
+use reqwest::header::{COOKIE, SET_COOKIE};
+
+fn session_cookie(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .find(|value| value.starts_with("id="))
+        .expect("No session cookie was set")
+}
+
+#[tokio::test]
+async fn session_cookie_is_signed_and_http_only() {
+    let app = spawn_app().await;
+
+    let response = app.get_login(None).await;
+    let cookie = session_cookie(&response);
+
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("SameSite=Lax"));
+    assert!(cookie.contains("Path=/"));
+    // Local configuration does not force secure cookies
+    assert!(!cookie.contains("Secure"));
+
+    // The signature is prepended to the session id
+    let value = cookie.trim_start_matches("id=").split(';').next().unwrap();
+    assert!(value.len() > 44);
+}
+
+#[tokio::test]
+async fn tampered_session_cookie_starts_a_new_session() {
+    let app = spawn_app().await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client.get(format!("{}/login", &app.address)).send().await.unwrap();
+    let cookie = session_cookie(&response);
+    let value = cookie.trim_start_matches("id=").split(';').next().unwrap().to_string();
+
+    // Keep the session id but drop the signature
+    let unsigned = format!("id={}", &value[44..]);
+    let response = client
+        .get(format!("{}/login", &app.address))
+        .header(COOKIE, unsigned)
+        .send()
+        .await
+        .unwrap();
+    let new_cookie = session_cookie(&response);
+    assert_ne!(new_cookie, cookie);
+
+    // The original signed cookie keeps the session, which is unchanged
+    let response = client
+        .get(format!("{}/login", &app.address))
+        .header(COOKIE, format!("id={}", value))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get(SET_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn sessions_are_listed_for_their_user_until_logged_out() {
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    let sessions = session::list_for_user(&app.db_pool, app.test_user.user_id).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+
+    app.post_logout().await;
+    assert!(session::list_for_user(&app.db_pool, app.test_user.user_id).await.unwrap().is_empty());
+    assert_eq!(session::prune(&app.db_pool).await.unwrap(), 1);
+}
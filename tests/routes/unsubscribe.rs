@@ -0,0 +1,54 @@
+use axum_sass_template::domain::{EmailCategory, UnsubscribeToken};
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn get_unsubscribe_shows_confirmation() {
+    let app = spawn_app().await;
+    let token = UnsubscribeToken::new(app.test_user.user_id, EmailCategory::ProductUpdates)
+        .sign(&app.hmac_secret);
+
+    let response = app.get_unsubscribe(&token).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Stop receiving product updates emails?"));
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_opts_the_user_out() {
+    let app = spawn_app().await;
+    let token = UnsubscribeToken::new(app.test_user.user_id, EmailCategory::Digests)
+        .sign(&app.hmac_secret);
+
+    let response = app.post_unsubscribe(&token).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let saved = sqlx::query!(
+        "SELECT product_updates, digests FROM notification_preferences WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved preferences.");
+    assert!(saved.product_updates);
+    assert!(!saved.digests);
+}
+
+#[tokio::test]
+async fn forged_unsubscribe_token_is_rejected() {
+    let app = spawn_app().await;
+    let token = UnsubscribeToken::new(app.test_user.user_id, EmailCategory::Digests)
+        .sign(&secrecy::Secret::new("not-the-app-secret".to_string()));
+
+    let response = app.post_unsubscribe(&token).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let saved = sqlx::query!(
+        "SELECT digests FROM notification_preferences WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .expect("Failed to query preferences.");
+    assert!(saved.is_none());
+}
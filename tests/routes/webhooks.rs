@@ -0,0 +1,80 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn unsigned_email_events_are_rejected() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "events": [{ "type": "hard_bounce", "email": app.test_user.email }]
+    }).to_string();
+
+    let response = app.post_email_events(&body, "application/json", "deadbeef").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let suppressions = sqlx::query!("SELECT id FROM email_suppressions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(suppressions.is_empty());
+}
+
+#[tokio::test]
+async fn hard_bounce_suppresses_address_and_flags_user() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "events": [
+            { "type": "hard_bounce", "email": app.test_user.email.to_uppercase(), "detail": "550 User unknown" },
+            { "type": "soft_bounce", "email": "someone-else@example.com" },
+        ]
+    }).to_string();
+    let signature = app.sign_webhook(body.as_bytes());
+
+    let response = app.post_email_events(&body, "application/json", &signature).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let json: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(json["received"], 2);
+    assert_eq!(json["suppressed"], 1);
+
+    let suppression = sqlx::query!("SELECT email, reason FROM email_suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch suppression.");
+    assert_eq!(suppression.email, app.test_user.email.to_lowercase());
+    assert_eq!(suppression.reason, "hard_bounce");
+
+    let user = sqlx::query!("SELECT email_undeliverable FROM users WHERE id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(user.email_undeliverable);
+}
+
+#[tokio::test]
+async fn raw_complaint_report_is_parsed() {
+    let app = spawn_app().await;
+    let body = format!(
+        "From feedback@example.com Mon Jul 29 11:00:00 2024
+Subject: FW: Spam report
+Content-Type: multipart/report; report-type=feedback-report; boundary=\"ABC\"
+
+--ABC
+Content-Type: message/feedback-report
+
+Feedback-Type: abuse
+Version: 1
+Original-Rcpt-To: <{}>
+
+--ABC--
+",
+        app.test_user.email
+    );
+    let signature = app.sign_webhook(body.as_bytes());
+
+    let response = app.post_email_events(&body, "application/mbox", &signature).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let suppression = sqlx::query!("SELECT reason FROM email_suppressions WHERE email = $1", app.test_user.email.to_lowercase())
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch suppression.");
+    assert_eq!(suppression.reason, "complaint");
+}